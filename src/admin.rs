@@ -0,0 +1,387 @@
+use crate::common::BlockPtr;
+use crate::common::Datasource;
+use crate::info;
+use kanal::Receiver;
+use kanal::Sender;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::Notify;
+use warp::http::StatusCode;
+use warp::Filter;
+use warp::Rejection;
+use warp::Reply;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SubgraphStatus {
+    pub name: String,
+    /// Last processed block
+    pub head: Option<BlockPtr>,
+    /// Blocks at or below this one have no history left to revert to
+    pub earliest_block: u64,
+    /// Most recent first, as kept by the block inspector
+    pub recent_block_ptrs: Vec<BlockPtr>,
+    pub datasources: Vec<Datasource>,
+    pub template_addresses: HashMap<String, HashSet<String>>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RuntimeStatus {
+    pub paused: bool,
+    pub downloaded: u64,
+    pub finished: u64,
+    pub subgraphs: Vec<SubgraphStatus>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AdminCommand {
+    /// Drop what a subgraph indexed from `block_number` onward, then index it again
+    Revert { subgraph: String, block_number: u64 },
+}
+
+/// Shared by the main flow and the admin routes, the status is a snapshot
+/// refreshed after every block batch. Commands are applied as soon as no batch is
+/// being processed, including while paused or waiting for the next batch
+#[derive(Clone)]
+pub struct AdminState {
+    status: Arc<RwLock<RuntimeStatus>>,
+    paused: Arc<AtomicBool>,
+    sender: Sender<AdminCommand>,
+    receiver: Receiver<AdminCommand>,
+    queued: Arc<Notify>,
+    /// Required as a bearer token by the control routes,
+    /// without it they only accept requests from localhost
+    token: Option<String>,
+}
+
+impl Default for AdminState {
+    fn default() -> Self {
+        let (sender, receiver) = kanal::unbounded();
+        Self {
+            status: Arc::default(),
+            paused: Arc::default(),
+            sender,
+            receiver,
+            queued: Arc::default(),
+            token: None,
+        }
+    }
+}
+
+impl AdminState {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token,
+            ..Default::default()
+        }
+    }
+
+    pub fn status(&self) -> RuntimeStatus {
+        let mut status = self.status.read().unwrap().clone();
+        status.paused = self.is_paused();
+        status
+    }
+
+    pub fn update_status(&self, downloaded: u64, finished: u64, subgraphs: Vec<SubgraphStatus>) {
+        let mut status = self.status.write().unwrap();
+        status.downloaded = downloaded;
+        status.finished = finished;
+        status.subgraphs = subgraphs;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        info!(Admin, "Processing paused"; paused => paused);
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub async fn wait_while_paused(&self) {
+        while self.is_paused() {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Resolves once a command is queued, including one queued before the call
+    pub async fn command_queued(&self) {
+        self.queued.notified().await
+    }
+
+    pub fn take_commands(&self) -> Vec<AdminCommand> {
+        let mut commands = vec![];
+        while let Ok(Some(command)) = self.receiver.try_recv() {
+            commands.push(command);
+        }
+        commands
+    }
+
+    /// Control requests carry the configured token, or come from localhost when there is none
+    fn is_authorized(&self, remote: Option<SocketAddr>, authorization: Option<String>) -> bool {
+        match &self.token {
+            Some(token) => authorization == Some(format!("Bearer {token}")),
+            None => remote.is_some_and(|addr| addr.ip().is_loopback()),
+        }
+    }
+
+    fn request_revert(
+        &self,
+        subgraph: String,
+        block_number: u64,
+    ) -> warp::reply::WithStatus<warp::reply::Json> {
+        let status = self
+            .status
+            .read()
+            .unwrap()
+            .subgraphs
+            .iter()
+            .find(|status| status.name == subgraph)
+            .cloned();
+
+        let Some(status) = status else {
+            return error_reply(
+                StatusCode::NOT_FOUND,
+                format!("No subgraph named `{subgraph}`"),
+            );
+        };
+
+        if block_number <= status.earliest_block {
+            let error = format!(
+                "Block {block_number} is not above the earliest block {}",
+                status.earliest_block
+            );
+            return error_reply(StatusCode::BAD_REQUEST, error);
+        }
+        match status.head {
+            Some(head) if block_number <= head.number => (),
+            _ => {
+                let error = format!("Block {block_number} has not been processed yet");
+                return error_reply(StatusCode::BAD_REQUEST, error);
+            }
+        }
+
+        info!(Admin, "Revert requested"; subgraph => subgraph, block_number => block_number);
+        self.sender
+            .send(AdminCommand::Revert {
+                subgraph,
+                block_number,
+            })
+            .expect("Admin channel closed");
+        self.queued.notify_one();
+        warp::reply::with_status(
+            warp::reply::json(&HashMap::from([("status", "scheduled")])),
+            StatusCode::ACCEPTED,
+        )
+    }
+
+    pub fn routes(&self) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        let admin = self.clone();
+        let state = warp::any().map(move || admin.clone());
+        // Pause, resume & revert change what gets indexed, the status is read-only
+        let authorized = state
+            .clone()
+            .and(warp::addr::remote())
+            .and(warp::header::optional::<String>("authorization"))
+            .map(
+                |admin: AdminState, remote: Option<SocketAddr>, authorization: Option<String>| {
+                    admin.is_authorized(remote, authorization)
+                },
+            );
+
+        let status = warp::path!("admin" / "status")
+            .and(warp::get())
+            .and(state.clone())
+            .map(|admin: AdminState| warp::reply::json(&admin.status()));
+
+        let pause = warp::path!("admin" / "pause")
+            .and(warp::post())
+            .and(state.clone())
+            .and(authorized.clone())
+            .map(|admin: AdminState, authorized: bool| {
+                if !authorized {
+                    return unauthorized_reply();
+                }
+                admin.set_paused(true);
+                warp::reply::with_status(warp::reply::json(&admin.status()), StatusCode::OK)
+            });
+
+        let resume = warp::path!("admin" / "resume")
+            .and(warp::post())
+            .and(state.clone())
+            .and(authorized.clone())
+            .map(|admin: AdminState, authorized: bool| {
+                if !authorized {
+                    return unauthorized_reply();
+                }
+                admin.set_paused(false);
+                warp::reply::with_status(warp::reply::json(&admin.status()), StatusCode::OK)
+            });
+
+        let revert = warp::path!("admin" / "revert" / String / u64)
+            .and(warp::post())
+            .and(state)
+            .and(authorized)
+            .map(
+                |subgraph: String, block_number: u64, admin: AdminState, authorized: bool| {
+                    if !authorized {
+                        return unauthorized_reply();
+                    }
+                    admin.request_revert(subgraph, block_number)
+                },
+            );
+
+        status.or(pause).or(resume).or(revert)
+    }
+}
+
+fn error_reply(status: StatusCode, error: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&HashMap::from([("error", error)])),
+        status,
+    )
+}
+
+fn unauthorized_reply() -> warp::reply::WithStatus<warp::reply::Json> {
+    error_reply(
+        StatusCode::UNAUTHORIZED,
+        "Missing or invalid admin token".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use df_logger::loggers::init_logger;
+
+    #[tokio::test]
+    async fn test_admin_routes() {
+        init_logger();
+        let admin = AdminState::default();
+        admin.update_status(
+            200,
+            100,
+            vec![SubgraphStatus {
+                name: "uniswap".to_string(),
+                head: Some(BlockPtr {
+                    number: 100,
+                    ..Default::default()
+                }),
+                earliest_block: 50,
+                ..Default::default()
+            }],
+        );
+        let routes = admin.routes();
+        let localhost: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/admin/status")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let status: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(status["finished"], 100);
+        assert_eq!(status["subgraphs"][0]["name"], "uniswap");
+
+        // Without a token, control routes only accept requests from localhost
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/pause")
+            .remote_addr("10.0.0.1:40000".parse().unwrap())
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!admin.is_paused());
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/pause")
+            .remote_addr(localhost)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(admin.is_paused());
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/revert/uniswap/90")
+            .remote_addr(localhost)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/revert/sushiswap/90")
+            .remote_addr(localhost)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        admin.command_queued().await;
+
+        for block_number in [50, 101] {
+            let response = warp::test::request()
+                .method("POST")
+                .path(&format!("/admin/revert/uniswap/{block_number}"))
+                .remote_addr(localhost)
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        assert_eq!(
+            admin.take_commands(),
+            vec![AdminCommand::Revert {
+                subgraph: "uniswap".to_string(),
+                block_number: 90
+            }]
+        );
+        assert!(admin.take_commands().is_empty());
+
+        warp::test::request()
+            .method("POST")
+            .path("/admin/resume")
+            .remote_addr(localhost)
+            .reply(&routes)
+            .await;
+        assert!(!admin.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_admin_token() {
+        init_logger();
+        let admin = AdminState::new(Some("secret".to_string()));
+        let routes = admin.routes();
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/pause")
+            .remote_addr("127.0.0.1:40000".parse().unwrap())
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/admin/pause")
+            .header("authorization", "Bearer secret")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(admin.is_paused());
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/admin/status")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,67 @@
+use super::asc::*;
+use super::block::AscEthereumBlock;
+use super::block::EthereumBlockData;
+use super::log::AscLogParamArray;
+use super::transaction::AscEthereumTransaction;
+use super::transaction::EthereumTransactionData;
+use crate::errors::AscError;
+use crate::impl_asc_type_struct;
+use crate::runtime::asc::base::asc_new;
+use crate::runtime::asc::base::AscHeap;
+use crate::runtime::asc::base::AscIndexId;
+use crate::runtime::asc::base::AscPtr;
+use crate::runtime::asc::base::AscType;
+use crate::runtime::asc::base::IndexForAscTypeId;
+use crate::runtime::asc::base::ToAscObj;
+use ethabi::LogParam;
+use semver::Version;
+use web3::types::Address;
+
+#[repr(C)]
+pub struct AscEthereumCall {
+    pub to: AscPtr<AscAddress>,
+    pub from: AscPtr<AscAddress>,
+    pub block: AscPtr<AscEthereumBlock>,
+    pub transaction: AscPtr<AscEthereumTransaction>,
+    pub inputs: AscPtr<AscLogParamArray>,
+    pub outputs: AscPtr<AscLogParamArray>,
+}
+
+impl_asc_type_struct!(
+    AscEthereumCall;
+    to => AscPtr<AscAddress>,
+    from => AscPtr<AscAddress>,
+    block => AscPtr<AscEthereumBlock>,
+    transaction => AscPtr<AscEthereumTransaction>,
+    inputs => AscPtr<AscLogParamArray>,
+    outputs => AscPtr<AscLogParamArray>
+);
+
+impl AscIndexId for AscEthereumCall {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumCall;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EthereumCallData {
+    pub to: Address,
+    pub from: Address,
+    pub block: EthereumBlockData,
+    pub transaction: EthereumTransactionData,
+    pub inputs: Vec<LogParam>,
+    /// Return values come from the transaction's call-trace,
+    /// filled in only when the function declares outputs
+    pub outputs: Vec<LogParam>,
+}
+
+impl ToAscObj<AscEthereumCall> for EthereumCallData {
+    fn to_asc_obj<H: AscHeap + ?Sized>(&self, heap: &mut H) -> Result<AscEthereumCall, AscError> {
+        Ok(AscEthereumCall {
+            to: asc_new(heap, &self.to)?,
+            from: asc_new(heap, &self.from)?,
+            block: asc_new(heap, &self.block)?,
+            transaction: asc_new(heap, &self.transaction)?,
+            inputs: asc_new(heap, &self.inputs)?,
+            outputs: asc_new(heap, &self.outputs)?,
+        })
+    }
+}
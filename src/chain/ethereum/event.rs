@@ -3,7 +3,9 @@ use super::block::AscEthereumBlock;
 use super::block::EthereumBlockData;
 use super::log::AscLogParamArray;
 use super::transaction::AscEthereumTransaction;
+use super::transaction::AscTransactionReceipt;
 use super::transaction::EthereumTransactionData;
+use super::transaction::EthereumTransactionReceipt;
 use crate::errors::AscError;
 use crate::impl_asc_type_struct;
 use crate::runtime::asc::base::asc_new;
@@ -46,6 +48,35 @@ impl AscIndexId for AscEthereumEvent<AscEthereumTransaction, AscEthereumBlock> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
 }
 
+/// Event layout for apiVersion >= 0.0.7, where `receipt` is always present but may be null
+#[repr(C)]
+pub struct AscEthereumEventV7<T: AscType, B: AscType> {
+    pub address: AscPtr<AscAddress>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+    pub block: AscPtr<B>,
+    pub transaction: AscPtr<T>,
+    pub params: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscTransactionReceipt>,
+}
+
+impl_asc_type_struct!(
+    AscEthereumEventV7<T: AscType, B: AscType>;
+    address => AscPtr<AscAddress>,
+    log_index => AscPtr<AscBigInt>,
+    transaction_log_index => AscPtr<AscBigInt>,
+    log_type => AscPtr<AscString>,
+    block => AscPtr<B>,
+    transaction => AscPtr<T>,
+    params => AscPtr<AscLogParamArray>,
+    receipt => AscPtr<AscTransactionReceipt>
+);
+
+impl AscIndexId for AscEthereumEventV7<AscEthereumTransaction, AscEthereumBlock> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct EthereumEventData {
     pub address: Address,
@@ -55,6 +86,8 @@ pub struct EthereumEventData {
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
     pub params: Vec<LogParam>,
+    /// Only attached when the event-handler asks for it with `receipt: true`, fetched from the rpc
+    pub receipt: Option<EthereumTransactionReceipt>,
 }
 
 impl<T, B> ToAscObj<AscEthereumEvent<T, B>> for EthereumEventData
@@ -86,3 +119,41 @@ where
         })
     }
 }
+
+impl<T, B> ToAscObj<AscEthereumEventV7<T, B>> for EthereumEventData
+where
+    T: AscType + AscIndexId,
+    B: AscType + AscIndexId,
+    EthereumTransactionData: ToAscObj<T>,
+    EthereumBlockData: ToAscObj<B>,
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumEventV7<T, B>, AscError> {
+        let AscEthereumEvent {
+            address,
+            log_index,
+            transaction_log_index,
+            log_type,
+            block,
+            transaction,
+            params,
+        } = <Self as ToAscObj<AscEthereumEvent<T, B>>>::to_asc_obj(self, heap)?;
+
+        Ok(AscEthereumEventV7 {
+            address,
+            log_index,
+            transaction_log_index,
+            log_type,
+            block,
+            transaction,
+            params,
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| asc_new(heap, receipt))
+                .unwrap_or(Ok(AscPtr::null()))?,
+        })
+    }
+}
@@ -3,6 +3,7 @@ pub mod asc;
 
 // Subgraph AssemblyScript User-facing Types
 pub mod block;
+pub mod call;
 pub mod ethereum_call;
 pub mod event;
 pub mod log;
@@ -1,5 +1,8 @@
+use crate::runtime::asc::native_types::store::Bytes;
 use crate::runtime::asc::native_types::store::StoreValueKind;
 use crate::runtime::asc::native_types::store::Value;
+use crate::runtime::bignumber::bigdecimal::BigDecimal;
+use crate::runtime::bignumber::bigint::BigInt;
 use semver::Version;
 use serde::Deserialize;
 use serde::Serialize;
@@ -20,11 +23,34 @@ pub struct MappingABI {
 pub struct EventHandler {
     pub event: String,
     pub handler: String,
+    /// Accepted values of the indexed topics, any value is accepted when not set
+    pub topic1: Option<Vec<String>>,
+    pub topic2: Option<Vec<String>>,
+    pub topic3: Option<Vec<String>>,
+    #[serde(default)]
+    pub receipt: bool,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct BlockHandler {
-    pub filter: Option<String>,
+    pub filter: Option<BlockHandlerFilter>,
+    pub handler: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BlockHandlerFilter {
+    /// Blocks with a transaction calling the datasource's address
+    Call,
+    /// Every N blocks, counting from the datasource's start-block
+    Polling { every: u64 },
+    /// Only the datasource's start-block
+    Once,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CallHandler {
+    pub function: String,
     pub handler: String,
 }
 
@@ -43,6 +69,7 @@ pub struct Mapping {
     pub abis: Vec<MappingABI>,
     pub eventHandlers: Option<Vec<EventHandler>>,
     pub blockHandlers: Option<Vec<BlockHandler>>,
+    pub callHandlers: Option<Vec<CallHandler>>,
     pub transactionHandlers: Option<Vec<TransactionHandler>>,
     pub file: String,
 }
@@ -54,6 +81,23 @@ pub struct Datasource {
     pub network: String,
     pub source: Source,
     pub mapping: Mapping,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<HashMap<String, ContextValue>>,
+}
+
+/// Datasource context value, written as `{ type, data }` like in graph-node's subgraph.yaml
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum ContextValue {
+    String(String),
+    Int(i32),
+    Int8(i64),
+    BigDecimal(BigDecimal),
+    Bool(bool),
+    List(Vec<ContextValue>),
+    Null,
+    Bytes(Bytes),
+    BigInt(BigInt),
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -69,6 +113,17 @@ pub struct Source {
 pub struct SubgraphYaml {
     pub dataSources: Vec<Datasource>,
     pub templates: Option<Vec<Datasource>>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    pub graft: Option<Graft>,
+}
+
+/// Start from the data of another subgraph as it was at `block`,
+/// `base` being the database/namespace where that subgraph is stored
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Graft {
+    pub base: String,
+    pub block: u64,
 }
 
 #[derive(Debug)]
@@ -76,6 +131,7 @@ pub enum HandlerTypes {
     EthereumBlock,
     EthereumTransaction,
     EthereumEvent,
+    EthereumCall,
 }
 
 #[derive(Debug, PartialEq, Clone, Deserialize)]
@@ -110,6 +166,14 @@ pub struct BlockPtr {
     pub parent_hash: String,
 }
 
+/// A handler failure that was skipped over, with `features: [nonFatalErrors]`
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct SubgraphErrorRecord {
+    pub block_ptr: BlockPtr,
+    pub handler: String,
+    pub message: String,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum StartBlock {
     Number(u64),
@@ -1,4 +1,5 @@
 use super::base::*;
+use crate::runtime::asc::native_types::store::Value;
 use ethabi::Contract;
 use semver::Version;
 use std::collections::HashMap;
@@ -37,6 +38,13 @@ impl SubgraphYaml {
             .filter_map(|ds| ds.source.startBlock)
             .min()
     }
+
+    /// Whether deterministic handler errors are skipped instead of failing the subgraph
+    pub fn non_fatal_errors(&self) -> bool {
+        self.features
+            .iter()
+            .any(|feature| feature == "nonFatalErrors")
+    }
 }
 
 impl BlockPtr {
@@ -108,6 +116,73 @@ impl WASMs {
     }
 }
 
+impl Datasource {
+    pub fn context_entity(&self) -> Option<RawEntity> {
+        self.context.clone().map(|context| {
+            context
+                .into_iter()
+                .map(|(field, value)| (field, Value::from(value)))
+                .collect()
+        })
+    }
+
+    pub fn set_context_entity(&mut self, context: Option<RawEntity>) {
+        self.context = context.map(|context| {
+            context
+                .into_iter()
+                .map(|(field, value)| (field, ContextValue::from(value)))
+                .collect()
+        });
+    }
+}
+
+impl BlockHandlerFilter {
+    /// `called` tells whether a transaction of the block called the datasource's address
+    pub fn matches(&self, start_block: u64, block_number: u64, called: bool) -> bool {
+        match self {
+            BlockHandlerFilter::Call => called,
+            BlockHandlerFilter::Polling { every } => {
+                *every > 0 && (block_number - start_block) % every == 0
+            }
+            BlockHandlerFilter::Once => block_number == start_block,
+        }
+    }
+}
+
+impl From<ContextValue> for Value {
+    fn from(value: ContextValue) -> Self {
+        match value {
+            ContextValue::String(s) => Value::String(s),
+            ContextValue::Int(n) => Value::Int(n),
+            ContextValue::Int8(n) => Value::Int8(n),
+            ContextValue::BigDecimal(n) => Value::BigDecimal(n),
+            ContextValue::Bool(b) => Value::Bool(b),
+            ContextValue::List(list) => Value::List(list.into_iter().map(Value::from).collect()),
+            ContextValue::Null => Value::Null,
+            ContextValue::Bytes(bytes) => Value::Bytes(bytes),
+            ContextValue::BigInt(n) => Value::BigInt(n),
+        }
+    }
+}
+
+impl From<Value> for ContextValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::String(s) => ContextValue::String(s),
+            Value::Int(n) => ContextValue::Int(n),
+            Value::Int8(n) => ContextValue::Int8(n),
+            Value::BigDecimal(n) => ContextValue::BigDecimal(n),
+            Value::Bool(b) => ContextValue::Bool(b),
+            Value::List(list) => {
+                ContextValue::List(list.into_iter().map(ContextValue::from).collect())
+            }
+            Value::Null => ContextValue::Null,
+            Value::Bytes(bytes) => ContextValue::Bytes(bytes),
+            Value::BigInt(n) => ContextValue::BigInt(n),
+        }
+    }
+}
+
 impl From<&DatasourceBundle> for Datasource {
     fn from(source: &DatasourceBundle) -> Self {
         source.ds.clone()
@@ -138,6 +213,10 @@ impl DatasourceBundle {
     pub fn start_block(&self) -> u64 {
         self.ds.source.startBlock.unwrap_or(0)
     }
+
+    pub fn context(&self) -> Option<RawEntity> {
+        self.ds.context_entity()
+    }
 }
 
 impl DatasourceBundles {
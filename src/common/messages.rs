@@ -1,15 +1,19 @@
+use super::base::BlockHandlerFilter;
 use super::base::BlockPtr;
+use super::base::Datasource;
 use super::base::EntityID;
 use super::base::EntityType;
 use super::base::FieldName;
 use super::base::RawEntity;
 use crate::chain::ethereum::block::EthereumBlockData;
+use crate::chain::ethereum::call::EthereumCallData;
 use crate::chain::ethereum::event::EthereumEventData;
 use crate::chain::ethereum::transaction::EthereumTransactionData;
 use crate::chain::ethereum::transaction::EthereumTransactionReceipt;
+use std::collections::HashSet;
 use web3::types::Log;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BlockDataMessage {
     Ethereum {
         block: EthereumBlockData,
@@ -30,19 +34,65 @@ impl BlockDataMessage {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EthereumFilteredEvent {
     pub datasource: String,
     pub handler: String,
+    /// The handler asked for the transaction receipt, fetched right before it runs
+    pub receipt: bool,
     pub event: EthereumEventData,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+pub struct EthereumFilteredCall {
+    pub datasource: String,
+    pub handler: String,
+    /// ABI of the called function, its outputs are decoded once the call is known to succeed
+    pub function: ethabi::Function,
+    pub call: EthereumCallData,
+}
+
+#[derive(Debug, Clone)]
+pub struct EthereumFilteredBlockHandler {
+    pub datasource: String,
+    pub handler: String,
+    /// Set for templates, whose instances are only known while processing the block
+    pub template: Option<TemplateBlockFilter>,
+}
+
+/// Block-handler filter of a template, checked against each of its created instances
+#[derive(Debug, Clone)]
+pub struct TemplateBlockFilter {
+    pub filter: Option<BlockHandlerFilter>,
+    /// Lowercase addresses called by the block's transactions, only kept for the `call` filter
+    pub called: HashSet<String>,
+}
+
+impl TemplateBlockFilter {
+    /// Instances start at the block they were created in, like datasources at their start-block
+    pub fn matches(&self, instance: &Datasource, block_number: u64) -> bool {
+        let created_at = instance.source.startBlock.unwrap_or_default();
+        let called = instance
+            .source
+            .address
+            .as_ref()
+            .is_some_and(|address| self.called.contains(&address.to_lowercase()));
+
+        block_number >= created_at
+            && self.filter.as_ref().map_or(true, |filter| {
+                filter.matches(created_at, block_number, called)
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum FilteredDataMessage {
     Ethereum {
         events: Vec<EthereumFilteredEvent>,
         block: EthereumBlockData,
         txs: Vec<EthereumTransactionReceipt>,
+        block_handlers: Vec<EthereumFilteredBlockHandler>,
+        calls: Vec<EthereumFilteredCall>,
     },
 }
 
@@ -210,6 +210,33 @@ impl Schemas {
         field_kind
     }
 
+    /// Grafted data is copied as-is, so fields of the base schema
+    /// can not be removed or change type, only new fields can be added
+    pub fn check_graft_compatibility(&self, base: &Schemas) -> Result<(), String> {
+        for (entity_type, (base_schema, _)) in base.0.iter() {
+            let Some((schema, _)) = self.0.get(entity_type) else {
+                continue;
+            };
+
+            for (field_name, base_field) in base_schema.iter() {
+                match schema.get(field_name) {
+                    None => {
+                        return Err(format!("field `{entity_type}.{field_name}` was removed"));
+                    }
+                    Some(field)
+                        if field.kind != base_field.kind
+                            || field.list_inner_kind != base_field.list_inner_kind =>
+                    {
+                        return Err(format!("field `{entity_type}.{field_name}` changed type"));
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn parse_entity_field(field_type: Type) -> FieldKind {
         match field_type {
             Type::NamedType(name_type) => {
@@ -280,4 +307,33 @@ mod test {
         let entity_type = "Pool";
         let _token = schemas.0.get(entity_type).unwrap();
     }
+
+    #[test]
+    fn test_graft_compatibility() {
+        init_logger();
+
+        let base = Schemas::new_from_graphql_schema(
+            "type Token @entity { id: ID! name: String! holders: [Bytes!]! }",
+        );
+        let extended = Schemas::new_from_graphql_schema(
+            "type Token @entity { id: ID! name: String! holders: [Bytes!]! decimals: Int }
+             type Pool @entity { id: ID! }",
+        );
+        assert!(extended.check_graft_compatibility(&base).is_ok());
+
+        let removed =
+            Schemas::new_from_graphql_schema("type Token @entity { id: ID! holders: [Bytes!]! }");
+        assert_eq!(
+            removed.check_graft_compatibility(&base),
+            Err("field `Token.name` was removed".to_string())
+        );
+
+        let retyped = Schemas::new_from_graphql_schema(
+            "type Token @entity { id: ID! name: String! holders: [String!]! }",
+        );
+        assert_eq!(
+            retyped.check_graft_compatibility(&base),
+            Err("field `Token.holders` changed type".to_string())
+        );
+    }
 }
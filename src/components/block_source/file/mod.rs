@@ -0,0 +1,269 @@
+use super::metrics::BlockSourceMetrics;
+use crate::common::BlockDataMessage;
+use crate::components::Valve;
+use crate::config::FileFormat;
+use crate::config::FileSourceConfig;
+use crate::errors::SourceError;
+use crate::proto::ethereum::Block;
+use df_logger::*;
+use kanal::AsyncSender;
+use prometheus::Registry;
+use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Only the number of a block, decoding it skips the rest of the block data
+#[derive(Clone, PartialEq, Message, Deserialize)]
+struct BlockNumber {
+    #[prost(uint64, tag = "4")]
+    block_number: u64,
+}
+
+pub struct FileSource {
+    dir: PathBuf,
+    format: FileFormat,
+    compression: bool,
+    start_block: u64,
+    query_step: usize,
+    metrics: BlockSourceMetrics,
+}
+
+impl FileSource {
+    pub fn new(cfg: FileSourceConfig, start_block: u64, registry: &Registry) -> Self {
+        info!(
+            FileSource,
+            "Setup done";
+            dir => cfg.dir,
+            format => format!("{:?}", cfg.format),
+            compression => cfg.compression,
+            start_block => start_block
+        );
+        Self {
+            dir: PathBuf::from(cfg.dir),
+            format: cfg.format,
+            compression: cfg.compression,
+            start_block,
+            query_step: cfg.query_step.max(1) as usize,
+            metrics: BlockSourceMetrics::new(registry),
+        }
+    }
+
+    async fn list_files(&self) -> Result<Vec<PathBuf>, SourceError> {
+        let mut entries = tokio::fs::read_dir(&self.dir)
+            .await
+            .map_err(|e| SourceError::FileSourceError(format!("Failed to read dir: {:?}", e)))?;
+        let mut files = vec![];
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| SourceError::FileSourceError(format!("Failed to read dir: {:?}", e)))?
+        {
+            let path = entry.path();
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if path.is_file() && !is_hidden {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    async fn read_file(&self, path: &PathBuf) -> Result<Vec<Block>, SourceError> {
+        let timer = self.metrics.block_source_query_duration.start_timer();
+        let mut blocks = self.decode_file::<Block>(path).await?;
+        blocks.sort_unstable_by_key(|b| b.block_number);
+        timer.stop_and_record();
+        self.metrics.block_source_query_count.inc();
+        Ok(blocks)
+    }
+
+    async fn decode_file<T: Message + Default + DeserializeOwned>(
+        &self,
+        path: &PathBuf,
+    ) -> Result<Vec<T>, SourceError> {
+        let data = tokio::fs::read(path).await.map_err(|e| {
+            SourceError::FileSourceError(format!("Failed to read file {:?}: {:?}", path, e))
+        })?;
+
+        let data = if self.compression {
+            lz4::block::decompress(&data, None).map_err(|e| {
+                SourceError::FileSourceError(format!("Failed to decompress {:?}: {:?}", path, e))
+            })?
+        } else {
+            data
+        };
+
+        let blocks = match self.format {
+            FileFormat::Protobuf => {
+                let mut buf = data.as_slice();
+                let mut blocks = vec![];
+                while !buf.is_empty() {
+                    let block = T::decode_length_delimited(&mut buf).map_err(|e| {
+                        SourceError::FileSourceError(format!(
+                            "Failed to decode block in {:?}: {:?}",
+                            path, e
+                        ))
+                    })?;
+                    blocks.push(block);
+                }
+                blocks
+            }
+            FileFormat::Jsonl => data
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                .map(serde_json::from_slice::<T>)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        Ok(blocks)
+    }
+
+    /// Files are replayed in order of their lowest block number,
+    /// so every file is expected to hold a contiguous range of blocks.
+    /// Only the block numbers are decoded here, the full blocks are decoded once, when replayed
+    async fn sorted_files(&self) -> Result<Vec<PathBuf>, SourceError> {
+        let mut files = vec![];
+
+        for path in self.list_files().await? {
+            let numbers = self.decode_file::<BlockNumber>(&path).await?;
+            let first = numbers.iter().map(|b| b.block_number).min();
+            let last = numbers.iter().map(|b| b.block_number).max();
+            match (first, last) {
+                (Some(first), Some(last)) if last >= self.start_block => files.push((first, path)),
+                _ => (),
+            }
+        }
+
+        files.sort_unstable_by_key(|(first_block, _)| *first_block);
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    pub async fn get_block_stream(
+        &self,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> Result<(), SourceError> {
+        let files = self.sorted_files().await?;
+        info!(
+            BlockSource,
+            "start replaying block-data from files ⚓";
+            number_of_files => files.len()
+        );
+
+        for path in files {
+            let blocks = self.read_file(&path).await?;
+            let start_time = self.metrics.block_source_serialized_duration.start_timer();
+            let mut blocks = blocks
+                .into_iter()
+                .filter(|b| b.block_number >= self.start_block)
+                .map(BlockDataMessage::from)
+                .collect::<Vec<_>>();
+            start_time.stop_and_record();
+
+            info!(
+                FileSource,
+                "block file loaded";
+                file => format!("{:?}", path),
+                number_of_blocks => blocks.len()
+            );
+
+            while !blocks.is_empty() {
+                let rest = blocks.split_off(self.query_step.min(blocks.len()));
+                let batch = std::mem::replace(&mut blocks, rest);
+                self.metrics
+                    .block_source_total_blocks
+                    .inc_by(batch.len() as u64);
+                valve.set_downloaded(batch.last().map(|b| b.get_block_ptr().number).unwrap());
+                sender.send(batch).await?;
+                valve.temporarily_close().await;
+            }
+        }
+
+        warn!(BlockSource, "No more block to replay...");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ValveConfig;
+    use crate::proto::ethereum::Header;
+    use df_logger::loggers::init_logger;
+    use prometheus::default_registry;
+
+    fn mock_block(block_number: u64) -> Block {
+        let hash = |n: u64| format!("0x{:064x}", n);
+        Block {
+            chain_id: 1,
+            block_hash: hash(block_number + 1),
+            parent_hash: hash(block_number),
+            block_number,
+            header: Some(Header {
+                author: format!("0x{:040x}", 0),
+                state_root: hash(0),
+                transactions_root: hash(0),
+                receipts_root: hash(0),
+                gas_used: "0".to_string(),
+                gas_limit: "0".to_string(),
+                timestamp: "0".to_string(),
+                difficulty: "0".to_string(),
+                total_difficulty: "0".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_source() {
+        init_logger();
+
+        let dir = std::env::temp_dir().join("dfr_file_source_test");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // File names are deliberately in reverse order of their block ranges
+        for (file_name, range) in [("a.pb", 3..6), ("b.pb", 0..3)] {
+            let mut data = vec![];
+            for block_number in range.rev() {
+                mock_block(block_number)
+                    .encode_length_delimited(&mut data)
+                    .unwrap();
+            }
+            let data = lz4::block::compress(&data, None, true).unwrap();
+            std::fs::write(dir.join(file_name), data).unwrap();
+        }
+
+        let cfg = FileSourceConfig {
+            dir: dir.to_string_lossy().to_string(),
+            format: FileFormat::Protobuf,
+            compression: true,
+            query_step: 2,
+        };
+        let registry = default_registry();
+        let source = FileSource::new(cfg, 1, registry);
+        let valve_cfg = ValveConfig {
+            allowed_lag: 100,
+            wait_time: 0,
+        };
+        let valve = Valve::new(&valve_cfg, registry);
+        let (sender, recv) = kanal::bounded_async(10);
+
+        source.get_block_stream(sender, valve).await.unwrap();
+
+        let mut batches = vec![];
+        while let Ok(Some(blocks)) = recv.try_recv() {
+            let numbers = blocks
+                .iter()
+                .map(|b| b.get_block_ptr().number)
+                .collect::<Vec<_>>();
+            batches.push(numbers);
+        }
+
+        assert_eq!(batches, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
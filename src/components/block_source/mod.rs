@@ -1,4 +1,5 @@
 mod metrics;
+mod rpc;
 
 use super::Valve;
 use crate::common::BlockDataMessage;
@@ -9,6 +10,7 @@ use crate::config::SourceTypes;
 use crate::errors::SourceError;
 use kanal::AsyncSender;
 use prometheus::Registry;
+use rpc::RpcSource;
 
 #[cfg(feature = "deltalake")]
 mod delta;
@@ -22,11 +24,19 @@ mod pubsub;
 #[cfg(feature = "pubsub")]
 use pubsub::PubSubSource;
 
+#[cfg(feature = "file")]
+mod file;
+#[cfg(feature = "file")]
+use file::FileSource;
+
 enum Source {
     #[cfg(feature = "deltalake")]
     Delta(DeltaClient),
     #[cfg(feature = "pubsub")]
     PubSub(PubSubSource),
+    Rpc(RpcSource),
+    #[cfg(feature = "file")]
+    File(FileSource),
 }
 
 pub struct BlockSource {
@@ -53,6 +63,23 @@ impl BlockSource {
                 sub_id,
                 compression,
             } => Source::PubSub(PubSubSource::new(sub_id.clone(), compression.clone()).await?),
+            SourceTypes::Rpc(rpc_cfg) => Source::Rpc(
+                RpcSource::new(
+                    rpc_cfg.to_owned(),
+                    &config.rpc_endpoint,
+                    start_block,
+                    registry,
+                )
+                .await?,
+            ),
+            #[cfg(feature = "file")]
+            SourceTypes::File(file_cfg) => {
+                let start_block = match start_block {
+                    StartBlock::Number(block) => block,
+                    StartBlock::Latest => 0,
+                };
+                Source::File(FileSource::new(file_cfg.to_owned(), start_block, registry))
+            }
         };
         Ok(Self {
             source,
@@ -82,6 +109,19 @@ impl BlockSource {
                 };
                 query_blocks.await?
             }
+            Source::Rpc(source) => {
+                let query_blocks = match self.chain {
+                    Chain::Ethereum => source.get_block_stream(sender, valve),
+                };
+                query_blocks.await?
+            }
+            #[cfg(feature = "file")]
+            Source::File(source) => {
+                let query_blocks = match self.chain {
+                    Chain::Ethereum => source.get_block_stream(sender, valve),
+                };
+                query_blocks.await?
+            }
         };
 
         Ok(())
@@ -0,0 +1,331 @@
+use super::metrics::BlockSourceMetrics;
+use crate::chain::ethereum::block::EthereumBlockData;
+use crate::chain::ethereum::transaction::EthereumTransactionData;
+use crate::common::BlockDataMessage;
+use crate::common::StartBlock;
+use crate::components::Valve;
+use crate::config::RpcSourceConfig;
+use crate::errors::SourceError;
+use df_logger::*;
+use futures_util::stream;
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+use kanal::AsyncSender;
+use prometheus::Registry;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio_retry::strategy::jitter;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+use web3::transports::WebSocket;
+use web3::types::BlockId;
+use web3::types::BlockNumber;
+use web3::types::FilterBuilder;
+use web3::types::Log;
+use web3::types::U64;
+use web3::Transport;
+use web3::Web3;
+
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+pub struct RpcSource<T: Transport = WebSocket> {
+    client: Web3<T>,
+    start_block: StartBlock,
+    query_step: u64,
+    confirmations: u64,
+    poll_interval: Duration,
+    max_concurrent_requests: usize,
+    max_retries: usize,
+    metrics: BlockSourceMetrics,
+}
+
+impl RpcSource {
+    pub async fn new(
+        cfg: RpcSourceConfig,
+        default_endpoint: &str,
+        start_block: StartBlock,
+        registry: &Registry,
+    ) -> Result<Self, SourceError> {
+        let endpoint = cfg.endpoint.unwrap_or(default_endpoint.to_owned());
+        let client = Web3::new(WebSocket::new(&endpoint).await?);
+        info!(
+            RpcSource,
+            "Setup done";
+            start_block => format!("{:?}", start_block),
+            query_step => cfg.query_step,
+            confirmations => cfg.confirmations
+        );
+        Ok(Self {
+            client,
+            start_block,
+            query_step: cfg.query_step.max(1),
+            confirmations: cfg.confirmations,
+            poll_interval: Duration::from_millis(cfg.poll_interval_ms),
+            max_concurrent_requests: cfg.max_concurrent_requests.max(1),
+            max_retries: cfg.max_retries,
+            metrics: BlockSourceMetrics::new(registry),
+        })
+    }
+}
+
+impl<T: Transport> RpcSource<T> {
+    /// Waits 500ms after the first failure, doubling up to 10s, then gives up after `max_retries`
+    async fn retry<R, F>(&self, request: impl FnMut() -> F) -> Result<R, SourceError>
+    where
+        F: Future<Output = Result<R, SourceError>>,
+    {
+        let strategy = ExponentialBackoff::from_millis(2)
+            .factor(250)
+            .max_delay(RETRY_MAX_DELAY)
+            .map(jitter)
+            .take(self.max_retries);
+        Retry::spawn(strategy, request).await
+    }
+
+    /// Latest block that is at least `confirmations` blocks deep
+    async fn get_safe_head(&self) -> Result<u64, SourceError> {
+        let head = self.client.eth().block_number().await?.as_u64();
+        Ok(head.saturating_sub(self.confirmations))
+    }
+
+    async fn query_block(
+        &self,
+        block_number: u64,
+        logs: &HashMap<u64, Vec<Log>>,
+    ) -> Result<BlockDataMessage, SourceError> {
+        let block = self
+            .client
+            .eth()
+            .block_with_txs(BlockId::Number(BlockNumber::Number(U64::from(block_number))))
+            .await?
+            .ok_or(SourceError::RpcMissingBlock(block_number))?;
+
+        let logs = logs.get(&block_number).cloned().unwrap_or_default();
+
+        if logs.iter().any(|log| log.block_hash != block.hash) {
+            return Err(SourceError::RpcInconsistentLogs(block_number));
+        }
+
+        Ok(BlockDataMessage::Ethereum {
+            block: EthereumBlockData::from(&block),
+            transactions: block
+                .transactions
+                .iter()
+                .map(EthereumTransactionData::from)
+                .collect(),
+            logs,
+        })
+    }
+
+    async fn query_blocks(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<BlockDataMessage>, SourceError> {
+        let timer = self.metrics.block_source_query_duration.start_timer();
+        let filter = FilterBuilder::default()
+            .from_block(BlockNumber::Number(U64::from(from_block)))
+            .to_block(BlockNumber::Number(U64::from(to_block)))
+            .build();
+
+        let mut logs = HashMap::<u64, Vec<Log>>::new();
+        for log in self.client.eth().logs(filter).await? {
+            if log.removed == Some(true) {
+                continue;
+            }
+            let block_number = log.block_number.map(|n| n.as_u64()).unwrap_or_default();
+            logs.entry(block_number).or_default().push(log);
+        }
+
+        for block_logs in logs.values_mut() {
+            block_logs.sort_by_key(|log| log.log_index);
+        }
+
+        let blocks = stream::iter(from_block..=to_block)
+            .map(|n| self.query_block(n, &logs))
+            .buffered(self.max_concurrent_requests)
+            .try_collect::<Vec<_>>()
+            .await?;
+        timer.stop_and_record();
+        self.metrics.block_source_query_count.inc();
+        Ok(blocks)
+    }
+
+    pub async fn get_block_stream(
+        &self,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> Result<(), SourceError> {
+        let mut start_block = match self.start_block {
+            StartBlock::Number(block_number) => block_number,
+            StartBlock::Latest => self.get_safe_head().await?,
+        };
+        info!(BlockSource, "start polling for block-data ⚓"; start_block => start_block);
+
+        loop {
+            let safe_head = self.retry(|| self.get_safe_head()).await?;
+
+            if start_block > safe_head {
+                tokio::time::sleep(self.poll_interval).await;
+                continue;
+            }
+
+            let end_block = safe_head.min(start_block + self.query_step - 1);
+            let blocks = self
+                .retry(|| self.query_blocks(start_block, end_block))
+                .await?;
+
+            self.metrics
+                .block_source_total_blocks
+                .inc_by(blocks.len() as u64);
+
+            info!(
+                RpcSource,
+                "block batch downloaded";
+                from_block => start_block,
+                to_block => end_block,
+                chain_head => safe_head + self.confirmations
+            );
+
+            valve.set_downloaded(end_block);
+            sender.send(blocks).await?;
+            start_block = end_block + 1;
+            valve.temporarily_close().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::BoxFuture;
+    use jsonrpc_core::Call;
+    use jsonrpc_core::Params;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use web3::helpers::build_request;
+    use web3::types::H160;
+    use web3::types::H256;
+    use web3::types::U256;
+    use web3::RequestId;
+
+    /// Answers like a node whose head is block 100, with blocks that have no transactions
+    #[derive(Debug, Clone, Default)]
+    struct MockProvider {
+        /// Requests failing before the provider answers again
+        failures: Arc<AtomicUsize>,
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    fn block(number: u64) -> Value {
+        json!({
+            "hash": H256::from_low_u64_be(number),
+            "parentHash": H256::from_low_u64_be(number - 1),
+            "sha3Uncles": H256::zero(),
+            "miner": H160::zero(),
+            "stateRoot": H256::zero(),
+            "transactionsRoot": H256::zero(),
+            "receiptsRoot": H256::zero(),
+            "number": U64::from(number),
+            "gasUsed": U256::zero(),
+            "gasLimit": U256::zero(),
+            "extraData": "0x",
+            "timestamp": U256::from(number),
+            "difficulty": U256::zero(),
+            "uncles": [],
+            "transactions": [],
+        })
+    }
+
+    impl Transport for MockProvider {
+        type Out = BoxFuture<'static, web3::error::Result<Value>>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+            (1, build_request(1, method, params))
+        }
+
+        fn send(&self, _id: RequestId, request: Call) -> Self::Out {
+            let provider = self.clone();
+            Box::pin(async move {
+                let in_flight = provider.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                provider
+                    .max_in_flight
+                    .fetch_max(in_flight, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                provider.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                let failures = &provider.failures;
+                let failed =
+                    failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+                if failed.is_ok() {
+                    return Err(web3::Error::Unreachable);
+                }
+
+                let Call::MethodCall(call) = request else {
+                    return Err(web3::Error::InvalidResponse("not a method call".to_owned()));
+                };
+                let params = match call.params {
+                    Params::Array(params) => params,
+                    _ => vec![],
+                };
+                match call.method.as_str() {
+                    "eth_blockNumber" => Ok(json!(U64::from(100))),
+                    "eth_getLogs" => Ok(json!([])),
+                    "eth_getBlockByNumber" => {
+                        let number = serde_json::from_value::<U64>(params[0].clone()).unwrap();
+                        Ok(block(number.as_u64()))
+                    }
+                    method => Err(web3::Error::InvalidResponse(method.to_owned())),
+                }
+            })
+        }
+    }
+
+    fn rpc_source(provider: MockProvider, max_retries: usize) -> RpcSource<MockProvider> {
+        RpcSource {
+            client: Web3::new(provider),
+            start_block: StartBlock::Number(1),
+            query_step: 50,
+            confirmations: 10,
+            poll_interval: Duration::from_millis(10),
+            max_concurrent_requests: 4,
+            max_retries,
+            metrics: BlockSourceMetrics::new(&Registry::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_blocks() {
+        let provider = MockProvider::default();
+        let source = rpc_source(provider.clone(), 0);
+
+        assert_eq!(source.get_safe_head().await.unwrap(), 90);
+
+        let blocks = source.query_blocks(1, 50).await.unwrap();
+        let numbers = blocks
+            .iter()
+            .map(|block| block.get_block_ptr().number)
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, (1..=50).collect::<Vec<_>>());
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_retry_limit() {
+        let provider = MockProvider::default();
+        let source = rpc_source(provider.clone(), 2);
+
+        provider.failures.store(2, Ordering::SeqCst);
+        let safe_head = source.retry(|| source.get_safe_head()).await;
+        assert_eq!(safe_head.unwrap(), 90);
+
+        provider.failures.store(3, Ordering::SeqCst);
+        let safe_head = source.retry(|| source.get_safe_head()).await;
+        assert!(matches!(safe_head, Err(SourceError::RpcRequestFail(_))));
+    }
+}
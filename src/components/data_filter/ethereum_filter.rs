@@ -1,4 +1,6 @@
-use super::utils::get_handler_for_log;
+use super::utils::get_handlers_for_call;
+use super::utils::get_handlers_for_log;
+use super::utils::parse_call;
 use super::utils::parse_event;
 use super::DataFilterTrait;
 use crate::chain::ethereum::block::EthereumBlockData;
@@ -6,18 +8,24 @@ use crate::chain::ethereum::transaction::EthereumTransactionData;
 use crate::chain::ethereum::transaction::EthereumTransactionReceipt;
 use crate::common::ABIs;
 use crate::common::BlockDataMessage;
+use crate::common::BlockHandlerFilter;
 use crate::common::Datasource;
+use crate::common::EthereumFilteredBlockHandler;
+use crate::common::EthereumFilteredCall;
 use crate::common::EthereumFilteredEvent;
 use crate::common::FilteredDataMessage;
+use crate::common::TemplateBlockFilter;
 use crate::errors::FilterError;
 use df_logger::debug;
 use ethabi::Contract;
+use std::collections::HashSet;
 use web3::types::Log;
 
 #[derive(Debug, Clone)]
 struct DatasourceWithContract {
     ds: Datasource,
     contract: Contract,
+    is_template: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -26,13 +34,19 @@ pub struct EthereumFilter {
 }
 
 impl EthereumFilter {
-    pub fn new(datasources: Vec<Datasource>, abis: ABIs) -> Self {
+    pub fn new(datasources: Vec<Datasource>, templates: Vec<Datasource>, abis: ABIs) -> Self {
         let ds = datasources
             .into_iter()
-            .map(|ds| {
+            .map(|ds| (ds, false))
+            .chain(templates.into_iter().map(|ds| (ds, true)))
+            .map(|(ds, is_template)| {
                 let abi_name = ds.source.abi.clone();
                 let contract = abis.get_contract(&abi_name).unwrap();
-                DatasourceWithContract { ds, contract }
+                DatasourceWithContract {
+                    ds,
+                    contract,
+                    is_template,
+                }
             })
             .collect::<Vec<_>>();
         Self { ds }
@@ -46,7 +60,7 @@ impl EthereumFilter {
     ) -> Result<Vec<EthereumFilteredEvent>, FilterError> {
         let result = logs
             .into_iter()
-            .filter_map(|log| {
+            .flat_map(|log| {
                 let source = self.ds.iter().find(|s| {
                     s.ds.source
                         .address
@@ -57,12 +71,12 @@ impl EthereumFilter {
                         .unwrap_or(false)
                 });
 
-                if let Some(DatasourceWithContract { ds, contract }) = source {
-                    let event_handler = get_handler_for_log(ds, &log.topics[0]);
-
-                    event_handler.as_ref()?;
+                if let Some(DatasourceWithContract { ds, contract, .. }) = source {
+                    let event_handlers = get_handlers_for_log(ds, &log.topics);
 
-                    let event_handler = event_handler.unwrap();
+                    if event_handlers.is_empty() {
+                        return vec![];
+                    }
 
                     //Parse the event
                     let tx = txs
@@ -71,13 +85,16 @@ impl EthereumFilter {
                         .expect("No Tx found for log");
 
                     let event = parse_event(contract, log, block_header.to_owned(), tx)
-                        .map(|e| EthereumFilteredEvent {
-                            event: e,
+                        .expect("Parsing failed");
+                    event_handlers
+                        .into_iter()
+                        .map(|event_handler| EthereumFilteredEvent {
+                            event: event.clone(),
                             handler: event_handler.handler,
+                            receipt: event_handler.receipt,
                             datasource: ds.name.clone(),
                         })
-                        .expect("Parsing failed");
-                    Some(event)
+                        .collect()
                 } else {
                     let tx = txs
                         .get(log.transaction_index.unwrap().as_usize())
@@ -96,13 +113,18 @@ impl EthereumFilter {
                                 tx.clone(),
                             )
                             .and_then(|e| {
-                                let handler = get_handler_for_log(&ds.ds, &log.topics[0]);
-                                if let Some(event_handler) = handler {
-                                    return Some(EthereumFilteredEvent {
-                                        event: e,
-                                        handler: event_handler.handler,
-                                        datasource: ds.ds.name.clone(),
-                                    });
+                                let handlers = get_handlers_for_log(&ds.ds, &log.topics);
+                                if !handlers.is_empty() {
+                                    let events = handlers
+                                        .into_iter()
+                                        .map(|event_handler| EthereumFilteredEvent {
+                                            event: e.clone(),
+                                            handler: event_handler.handler,
+                                            receipt: event_handler.receipt,
+                                            datasource: ds.ds.name.clone(),
+                                        })
+                                        .collect::<Vec<_>>();
+                                    return Some(events);
                                 }
                                 debug!(DataFilter,
                                     "No handler found for log";
@@ -113,6 +135,7 @@ impl EthereumFilter {
                                 None
                             })
                         })
+                        .unwrap_or_default()
                 }
             })
             .collect::<Vec<_>>();
@@ -158,9 +181,146 @@ impl EthereumFilter {
         }
     }
 
-    // TODO: implement filter_block
+    fn filter_block_handlers(
+        &self,
+        block_header: &EthereumBlockData,
+        transactions: &[EthereumTransactionData],
+    ) -> Vec<EthereumFilteredBlockHandler> {
+        let block_number = block_header.number.as_u64();
+        // NOTE: without call-traces, only top-level calls from transactions are seen
+        let called = transactions
+            .iter()
+            .filter_map(|tx| tx.to)
+            .map(|to| format!("{:?}", to))
+            .collect::<HashSet<_>>();
+        let mut result = vec![];
+
+        for DatasourceWithContract {
+            ds, is_template, ..
+        } in self.ds.iter()
+        {
+            let block_handlers = ds.mapping.blockHandlers.clone().unwrap_or_default();
+
+            // Only created instances of a template handle blocks, each from its own creation
+            if *is_template {
+                for block_handler in block_handlers {
+                    let called = match block_handler.filter {
+                        Some(BlockHandlerFilter::Call) => called.clone(),
+                        _ => HashSet::new(),
+                    };
+                    result.push(EthereumFilteredBlockHandler {
+                        datasource: ds.name.clone(),
+                        handler: block_handler.handler,
+                        template: Some(TemplateBlockFilter {
+                            filter: block_handler.filter,
+                            called,
+                        }),
+                    });
+                }
+                continue;
+            }
+
+            let start_block = ds.source.startBlock.unwrap_or_default();
+
+            if block_number < start_block {
+                continue;
+            }
+
+            let is_called = ds
+                .source
+                .address
+                .as_ref()
+                .is_some_and(|address| called.contains(&address.to_lowercase()));
+
+            for block_handler in block_handlers {
+                let should_handle = block_handler.filter.as_ref().map_or(true, |filter| {
+                    filter.matches(start_block, block_number, is_called)
+                });
+
+                if should_handle {
+                    result.push(EthereumFilteredBlockHandler {
+                        datasource: ds.name.clone(),
+                        handler: block_handler.handler,
+                        template: None,
+                    });
+                }
+            }
+        }
+
+        result
+    }
+
+    fn filter_calls(
+        &self,
+        block_header: &EthereumBlockData,
+        transactions: &[EthereumTransactionData],
+    ) -> Vec<EthereumFilteredCall> {
+        let has_call_handlers = self
+            .ds
+            .iter()
+            .any(|ds| ds.ds.mapping.callHandlers.is_some());
+
+        if !has_call_handlers {
+            return vec![];
+        }
+
+        // NOTE: without call-traces, only top-level calls from transactions are seen
+        transactions
+            .iter()
+            .flat_map(|tx| {
+                let (Some(to), Some(selector)) = (tx.to, tx.input.get(..4)) else {
+                    return vec![];
+                };
+                let to = format!("{:?}", to);
+                let sources: Vec<&DatasourceWithContract> = self
+                    .ds
+                    .iter()
+                    .filter(|s| {
+                        s.ds.source
+                            .address
+                            .as_ref()
+                            .map(|addr| addr.to_lowercase() == to)
+                            .unwrap_or(false)
+                    })
+                    .collect();
 
-    // TODO: implement filter_call_function
+                // Datasources without address (eg: templates) may handle calls to any address
+                let candidates: Vec<&DatasourceWithContract> = match sources.is_empty() {
+                    false => sources,
+                    true => self
+                        .ds
+                        .iter()
+                        .filter(|s| s.ds.source.address.is_none())
+                        .collect(),
+                };
+
+                // Every matching handler of every matching datasource runs
+                candidates
+                    .into_iter()
+                    .flat_map(|s| {
+                        let handlers = get_handlers_for_call(&s.ds, selector);
+                        if handlers.is_empty() {
+                            return vec![];
+                        }
+                        let Some((function, call)) =
+                            parse_call(&s.contract, block_header.to_owned(), tx.to_owned())
+                        else {
+                            return vec![];
+                        };
+                        handlers
+                            .into_iter()
+                            .map(|handler| EthereumFilteredCall {
+                                datasource: s.ds.name.clone(),
+                                handler: handler.handler,
+                                function: function.clone(),
+                                call: call.clone(),
+                            })
+                            .collect()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
 }
 
 impl DataFilterTrait for EthereumFilter {
@@ -175,8 +335,16 @@ impl DataFilterTrait for EthereumFilter {
                 transactions,
             } => {
                 let txs = self.collect_txs(&block, &transactions, &logs)?;
+                let block_handlers = self.filter_block_handlers(&block, &transactions);
+                let calls = self.filter_calls(&block, &transactions);
                 let events = self.collect_events(block.clone(), transactions, logs)?;
-                Ok(FilteredDataMessage::Ethereum { events, block, txs })
+                Ok(FilteredDataMessage::Ethereum {
+                    events,
+                    block,
+                    txs,
+                    block_handlers,
+                    calls,
+                })
             }
         }
     }
@@ -283,7 +451,7 @@ mod test {
             .unwrap();
         let datasources_1: Vec<Datasource> = test_manifest.datasources().into();
 
-        let mut filter = EthereumFilter::new(datasources_1.clone(), ABIs::default());
+        let mut filter = EthereumFilter::new(datasources_1.clone(), vec![], ABIs::default());
         let header = EthereumBlockData::default();
         let txs = vec![EthereumTransactionData::default()];
 
@@ -340,4 +508,133 @@ mod test {
             2670201350
         );
     }
+
+    #[test]
+    fn test_filter_block_handlers() {
+        init_logger();
+        let address = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
+        let ds: Datasource = serde_json::from_value(serde_json::json!({
+            "kind": "ethereum/contract",
+            "name": "Token",
+            "network": "mainnet",
+            "source": { "address": address, "abi": "ERC20", "startBlock": 100 },
+            "mapping": {
+                "kind": "ethereum/events",
+                "apiVersion": "0.0.7",
+                "entities": [],
+                "abis": [],
+                "blockHandlers": [
+                    { "handler": "handleEveryBlock" },
+                    { "handler": "handleCall", "filter": { "kind": "call" } },
+                    { "handler": "handlePolling", "filter": { "kind": "polling", "every": 10 } },
+                    { "handler": "handleOnce", "filter": { "kind": "once" } }
+                ],
+                "file": "Token.wasm"
+            }
+        }))
+        .unwrap();
+        let mut abis = ABIs::default();
+        abis.insert("ERC20".to_string(), serde_json::json!([]));
+        let filter = EthereumFilter::new(vec![ds], vec![], abis);
+
+        let handlers_at = |number: u64, txs: Vec<EthereumTransactionData>| {
+            let header = EthereumBlockData {
+                number: number.into(),
+                ..Default::default()
+            };
+            filter
+                .filter_block_handlers(&header, &txs)
+                .into_iter()
+                .map(|h| h.handler)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(handlers_at(99, vec![]).is_empty());
+        assert_eq!(
+            handlers_at(100, vec![]),
+            vec!["handleEveryBlock", "handlePolling", "handleOnce"]
+        );
+        assert_eq!(handlers_at(105, vec![]), vec!["handleEveryBlock"]);
+        assert_eq!(
+            handlers_at(110, vec![]),
+            vec!["handleEveryBlock", "handlePolling"]
+        );
+
+        let call = EthereumTransactionData {
+            to: Some(address.parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            handlers_at(111, vec![call]),
+            vec!["handleEveryBlock", "handleCall"]
+        );
+    }
+
+    #[test]
+    fn test_filter_template_block_handlers() {
+        init_logger();
+        let template: Datasource = serde_json::from_value(serde_json::json!({
+            "kind": "ethereum/contract",
+            "name": "Pool",
+            "network": "mainnet",
+            "source": { "abi": "ERC20" },
+            "mapping": {
+                "kind": "ethereum/events",
+                "apiVersion": "0.0.7",
+                "entities": [],
+                "abis": [],
+                "blockHandlers": [
+                    { "handler": "handleEveryBlock" },
+                    { "handler": "handleCall", "filter": { "kind": "call" } },
+                    { "handler": "handlePolling", "filter": { "kind": "polling", "every": 10 } },
+                    { "handler": "handleOnce", "filter": { "kind": "once" } }
+                ],
+                "file": "Pool.wasm"
+            }
+        }))
+        .unwrap();
+        let mut abis = ABIs::default();
+        abis.insert("ERC20".to_string(), serde_json::json!([]));
+        let filter = EthereumFilter::new(vec![], vec![template.clone()], abis);
+
+        let address = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
+        let mut instance = template;
+        instance.source.address = Some(address.to_lowercase());
+        instance.source.startBlock = Some(100);
+
+        let call = EthereumTransactionData {
+            to: Some(address.parse().unwrap()),
+            ..Default::default()
+        };
+        let handlers_at = |number: u64, txs: Vec<EthereumTransactionData>| {
+            let header = EthereumBlockData {
+                number: number.into(),
+                ..Default::default()
+            };
+            let block_handlers = filter.filter_block_handlers(&header, &txs);
+            // Without instances, nothing is decided by the filter itself
+            assert_eq!(block_handlers.len(), 4);
+            block_handlers
+                .into_iter()
+                .filter(|h| h.template.as_ref().unwrap().matches(&instance, number))
+                .map(|h| h.handler)
+                .collect::<Vec<_>>()
+        };
+
+        // Anchored at the instance's creation block, not at the template's
+        assert!(handlers_at(99, vec![call.clone()]).is_empty());
+        assert_eq!(
+            handlers_at(100, vec![]),
+            vec!["handleEveryBlock", "handlePolling", "handleOnce"]
+        );
+        assert_eq!(handlers_at(105, vec![]), vec!["handleEveryBlock"]);
+        assert_eq!(
+            handlers_at(110, vec![]),
+            vec!["handleEveryBlock", "handlePolling"]
+        );
+        assert_eq!(
+            handlers_at(111, vec![call]),
+            vec!["handleEveryBlock", "handleCall"]
+        );
+    }
 }
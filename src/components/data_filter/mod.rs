@@ -39,10 +39,13 @@ impl DataFilter {
     pub fn new(
         chain: Chain,
         datasources: Vec<Datasource>,
+        templates: Vec<Datasource>,
         abis: ABIs,
     ) -> Result<Self, FilterError> {
         let filter = match chain {
-            Chain::Ethereum => DataFilter::Ethereum(EthereumFilter::new(datasources, abis)),
+            Chain::Ethereum => {
+                DataFilter::Ethereum(EthereumFilter::new(datasources, templates, abis))
+            }
         };
         Ok(filter)
     }
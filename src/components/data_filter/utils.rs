@@ -1,9 +1,14 @@
 use crate::chain::ethereum::block::EthereumBlockData;
+use crate::chain::ethereum::call::EthereumCallData;
 use crate::chain::ethereum::event::EthereumEventData;
 use crate::chain::ethereum::transaction::EthereumTransactionData;
+use crate::common::CallHandler;
 use crate::common::Datasource;
 use crate::common::EventHandler;
 use ethabi::Contract;
+use ethabi::Function;
+use ethabi::LogParam;
+use std::str::FromStr;
 use tiny_keccak::Hasher;
 use web3::types::Log;
 use web3::types::H256;
@@ -39,21 +44,89 @@ pub fn parse_event(
             log_type: log.log_type,
             block: block_header,
             transaction,
+            receipt: None,
         })
         .ok()
 }
 
-pub fn get_handler_for_log(source: &Datasource, topic0: &H256) -> Option<EventHandler> {
+pub fn parse_call(
+    contract: &Contract,
+    block_header: EthereumBlockData,
+    transaction: EthereumTransactionData,
+) -> Option<(Function, EthereumCallData)> {
+    let selector = transaction.input.get(..4)?;
+    let function = contract
+        .functions()
+        .find(|function| function.short_signature().as_slice() == selector)?;
+    let tokens = function.decode_input(&transaction.input[4..]).ok()?;
+    let inputs = function
+        .inputs
+        .iter()
+        .zip(tokens)
+        .map(|(param, value)| LogParam {
+            name: param.name.clone(),
+            value,
+        })
+        .collect();
+
+    let call = EthereumCallData {
+        to: transaction.to?,
+        from: transaction.from,
+        block: block_header,
+        transaction,
+        inputs,
+        outputs: vec![],
+    };
+    Some((function.clone(), call))
+}
+
+pub fn get_handlers_for_call(source: &Datasource, selector: &[u8]) -> Vec<CallHandler> {
+    source
+        .mapping
+        .callHandlers
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|handler| &parse_topic0_event(&handler.function)[..4] == selector)
+        .collect()
+}
+
+/// Every handler whose event & topic filters match runs, in the order of the manifest
+pub fn get_handlers_for_log(source: &Datasource, topics: &[H256]) -> Vec<EventHandler> {
+    let Some(topic0) = topics.first() else {
+        return vec![];
+    };
     source
         .mapping
         .eventHandlers
         .clone()
-        .and_then(|event_handlers| {
-            event_handlers
-                .iter()
-                .find(|handler| &parse_topic0_event(&handler.event) == topic0)
-                .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|handler| {
+            &parse_topic0_event(&handler.event) == topic0
+                && match_topic_filter(&handler.topic1, topics.get(1))
+                && match_topic_filter(&handler.topic2, topics.get(2))
+                && match_topic_filter(&handler.topic3, topics.get(3))
         })
+        .collect()
+}
+
+fn match_topic_filter(filter: &Option<Vec<String>>, topic: Option<&H256>) -> bool {
+    match (filter, topic) {
+        (None, _) => true,
+        (Some(values), _) if values.is_empty() => true,
+        (Some(_), None) => false,
+        (Some(values), Some(topic)) => values
+            .iter()
+            .filter_map(|value| parse_topic_value(value))
+            .any(|value| &value == topic),
+    }
+}
+
+/// Topic values may be written shorter than 32 bytes (eg: an address), so they are left-padded
+fn parse_topic_value(value: &str) -> Option<H256> {
+    let value = value.trim_start_matches("0x");
+    H256::from_str(&format!("{:0>64}", value)).ok()
 }
 
 fn parse_topic0_event(handler: &str) -> H256 {
@@ -69,7 +142,6 @@ fn parse_topic0_event(handler: &str) -> H256 {
 mod tests {
     use super::*;
     use df_logger::loggers::init_logger;
-    use std::str::FromStr;
 
     #[test]
     fn test_convert_event_name_to_topic0() {
@@ -77,6 +149,10 @@ mod tests {
         let event_handler = EventHandler {
             event: "Transfer(indexed address,indexed address,uint256)".to_string(),
             handler: "handleTransfer".to_string(),
+            topic1: None,
+            topic2: None,
+            topic3: None,
+            receipt: false,
         };
         assert_eq!(
             parse_topic0_event(event_handler.event.as_str()),
@@ -84,4 +160,109 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn test_parse_call() {
+        init_logger();
+        let contract: Contract = serde_json::from_str(
+            r#"[{"constant":false,"inputs":[{"name":"_to","type":"address"},{"name":"_value","type":"uint256"}],"name":"transfer","outputs":[{"name":"","type":"bool"}],"payable":false,"stateMutability":"nonpayable","type":"function"}]"#,
+        )
+        .unwrap();
+        let ds: Datasource = serde_json::from_value(serde_json::json!({
+            "kind": "ethereum/contract",
+            "name": "Token",
+            "network": "mainnet",
+            "source": { "address": null, "abi": "ERC20" },
+            "mapping": {
+                "kind": "ethereum/events",
+                "apiVersion": "0.0.7",
+                "entities": [],
+                "abis": [],
+                "callHandlers": [
+                    { "function": "transfer(address,uint256)", "handler": "handleTransferCall" },
+                    { "function": "approve(address,uint256)", "handler": "handleApproveCall" },
+                    { "function": "transfer(address,uint256)", "handler": "handleTransferFee" }
+                ],
+                "file": "Token.wasm"
+            }
+        }))
+        .unwrap();
+
+        let receiver = ethabi::Address::from_low_u64_be(1);
+        let mut input = hex::decode("a9059cbb").unwrap();
+        input.extend(ethabi::encode(&[
+            ethabi::Token::Address(receiver),
+            ethabi::Token::Uint(100.into()),
+        ]));
+        let tx = EthereumTransactionData {
+            to: Some(ethabi::Address::from_low_u64_be(2)),
+            input,
+            ..Default::default()
+        };
+
+        let handlers: Vec<String> = get_handlers_for_call(&ds, &tx.input[..4])
+            .into_iter()
+            .map(|handler| handler.handler)
+            .collect();
+        assert_eq!(handlers, vec!["handleTransferCall", "handleTransferFee"]);
+        assert!(get_handlers_for_call(&ds, &[0, 0, 0, 0]).is_empty());
+
+        let (function, call) = parse_call(&contract, EthereumBlockData::default(), tx).unwrap();
+        assert_eq!(function.name, "transfer");
+        assert_eq!(function.outputs.len(), 1);
+        assert_eq!(call.to, ethabi::Address::from_low_u64_be(2));
+        assert_eq!(call.inputs[0].name, "_to");
+        assert_eq!(call.inputs[0].value, ethabi::Token::Address(receiver));
+        assert_eq!(call.inputs[1].value, ethabi::Token::Uint(100.into()));
+        assert!(call.outputs.is_empty());
+    }
+
+    #[test]
+    fn test_topic_filters() {
+        init_logger();
+        let ds: Datasource = serde_json::from_value(serde_json::json!({
+            "kind": "ethereum/contract",
+            "name": "Token",
+            "network": "mainnet",
+            "source": { "address": null, "abi": "ERC20" },
+            "mapping": {
+                "kind": "ethereum/events",
+                "apiVersion": "0.0.7",
+                "entities": [],
+                "abis": [],
+                "eventHandlers": [
+                    {
+                        "event": "Transfer(indexed address,indexed address,uint256)",
+                        "handler": "handleMint",
+                        "topic1": ["0x0000000000000000000000000000000000000000"],
+                        "receipt": true
+                    },
+                    {
+                        "event": "Transfer(indexed address,indexed address,uint256)",
+                        "handler": "handleTransfer"
+                    }
+                ],
+                "file": "Token.wasm"
+            }
+        }))
+        .unwrap();
+        let topic0 =
+            H256::from_str("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")
+                .unwrap();
+        let sender = H256::from_low_u64_be(1);
+        let receiver = H256::from_low_u64_be(2);
+
+        // A mint matches both handlers, each of them runs
+        let mint = get_handlers_for_log(&ds, &[topic0, H256::zero(), receiver]);
+        let handlers = mint.iter().map(|h| h.handler.as_str()).collect::<Vec<_>>();
+        assert_eq!(handlers, vec!["handleMint", "handleTransfer"]);
+        assert!(mint[0].receipt);
+
+        let transfer = get_handlers_for_log(&ds, &[topic0, sender, receiver]);
+        assert_eq!(transfer.len(), 1);
+        assert_eq!(transfer[0].handler, "handleTransfer");
+        assert!(!transfer[0].receipt);
+
+        assert!(get_handlers_for_log(&ds, &[]).is_empty());
+    }
 }
@@ -0,0 +1,507 @@
+use super::BlockInspectionResult;
+use super::DataFilter;
+use super::Inspector;
+use super::ManifestAgent;
+use super::Subgraph;
+use crate::admin::AdminCommand;
+use crate::admin::SubgraphStatus;
+use crate::common::BlockDataMessage;
+use crate::common::BlockPtr;
+use crate::common::Schemas;
+use crate::common::StartBlock;
+use crate::config::Config;
+use crate::config::SubgraphConfig;
+use crate::database::DatabaseAgent;
+use crate::database::ExternDB;
+use crate::errors::DatabaseError;
+use crate::errors::MainError;
+use crate::info;
+use crate::ipfs_client::IpfsAgent;
+use crate::metrics::remove_subgraph_registry;
+use crate::metrics::subgraph_registry;
+use crate::rpc_client::RpcAgent;
+use crate::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+/// A subgraph with its own manifest, database & block cursor
+pub struct HostedSubgraph {
+    cfg: SubgraphConfig,
+    manifest: ManifestAgent,
+    db: DatabaseAgent,
+    inspector: Inspector,
+    filter: DataFilter,
+    rpc: RpcAgent,
+    subgraph: Subgraph,
+    block_data_retention: Option<u64>,
+    reorg_threshold: u16,
+    /// When the shared stream is behind this subgraph,
+    /// blocks are skipped until this one is received
+    resume_from: Option<u64>,
+    /// Commit of the last hand-off, written while the next blocks are processed
+    commit: Option<JoinHandle<Result<(), DatabaseError>>>,
+    commit_every_blocks: Option<u64>,
+    commit_every_ms: Option<u64>,
+    max_cache_entities: Option<usize>,
+    /// Last processed block whose changes are still only in memory
+    uncommitted_block: Option<BlockPtr>,
+    uncommitted_blocks: u64,
+    last_commit: Instant,
+}
+
+impl HostedSubgraph {
+    pub async fn new(config: &Config, cfg: &SubgraphConfig) -> Result<Self, MainError> {
+        let registry = &subgraph_registry(&cfg.name);
+
+        let manifest = ManifestAgent::new(&cfg.dir).await?;
+        let db = DatabaseAgent::new(
+            &cfg.database,
+            manifest.schemas(),
+            cfg.entity_cache_size,
+            registry,
+        )
+        .await?;
+        // Before removing orphans, which would wipe the rows of an interrupted graft copy
+        if let Some(graft) = manifest.graft() {
+            db.graft(&graft).await?;
+        }
+        db.remove_orphans().await?;
+        db.save_schema(&manifest.schema_source()).await?;
+        manifest.restore_datasources(db.load_datasources().await?);
+
+        let inspector = Inspector::new(
+            db.get_recent_block_pointers(config.reorg_threshold).await?,
+            manifest.min_start_block(),
+            config.reorg_threshold,
+        );
+        let filter = DataFilter::new(
+            config.chain.clone(),
+            manifest.datasources().into(),
+            manifest.templates().into(),
+            manifest.abis(),
+        )?;
+        let rpc = RpcAgent::new(config, manifest.abis(), registry).await?;
+        let ipfs = IpfsAgent::new(config.ipfs.as_ref())?;
+        let mut subgraph = Subgraph::new(
+            &db,
+            &rpc,
+            &ipfs,
+            &manifest,
+            &config.wasm_host,
+            &config.timeout,
+            registry,
+        );
+        subgraph.create_sources()?;
+
+        let mut this = Self {
+            cfg: cfg.clone(),
+            manifest,
+            db,
+            inspector,
+            filter,
+            rpc,
+            subgraph,
+            block_data_retention: config.block_data_retention,
+            reorg_threshold: config.reorg_threshold,
+            resume_from: None,
+            commit: None,
+            commit_every_blocks: cfg.commit_every_blocks,
+            commit_every_ms: cfg.commit_every_ms,
+            max_cache_entities: cfg.max_cache_entities,
+            uncommitted_block: None,
+            uncommitted_blocks: 0,
+            last_commit: Instant::now(),
+        };
+        this.rewind();
+
+        info!(
+            SubgraphHost,
+            "Subgraph ready!";
+            subgraph => this.cfg.name,
+            next_start_block => this.inspector.get_expected_block_number()
+        );
+        Ok(this)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.cfg.name
+    }
+
+    pub fn schemas(&self) -> Schemas {
+        self.manifest.schemas()
+    }
+
+    pub fn extern_db(&self) -> Arc<ExternDB> {
+        self.db.extern_db()
+    }
+
+    pub fn status(&self) -> SubgraphStatus {
+        let recent_block_ptrs = self.inspector.recent_block_ptrs();
+        SubgraphStatus {
+            name: self.cfg.name.clone(),
+            head: recent_block_ptrs.first().cloned(),
+            earliest_block: self.db.earliest_block(),
+            recent_block_ptrs,
+            datasources: self.manifest.active_datasources(),
+            template_addresses: self.manifest.template_addresses(),
+        }
+    }
+
+    /// Manual revert, `block_number` must be above the earliest block still having
+    /// its history and not above the processed head. Returns false when it is not
+    async fn revert(&mut self, block_number: u64) -> Result<bool, MainError> {
+        let earliest_block = self
+            .db
+            .get_earliest_block_ptr()
+            .await?
+            .map_or(0, |block_ptr| block_ptr.number)
+            .max(self.db.earliest_block());
+        let head = self
+            .inspector
+            .recent_block_ptrs()
+            .first()
+            .map(|block_ptr| block_ptr.number);
+
+        if block_number <= earliest_block || !head.is_some_and(|head| block_number <= head) {
+            warn!(
+                SubgraphHost,
+                "Cannot revert, block out of range";
+                subgraph => self.cfg.name,
+                block_number => block_number,
+                earliest_block => earliest_block,
+                head => format!("{:?}", head)
+            );
+            return Ok(false);
+        }
+
+        self.revert_from(block_number).await?;
+        Ok(true)
+    }
+
+    /// Drop everything indexed from `block_number` onward, so the stream
+    /// has to start over for the subgraph to index it again
+    async fn revert_from(&mut self, block_number: u64) -> Result<(), MainError> {
+        self.wait_for_commit().await?;
+        self.db.revert_from_block(block_number).await?;
+        self.manifest.revert_datasources(block_number);
+        self.uncommitted_block = None;
+        self.uncommitted_blocks = 0;
+        self.inspector = Inspector::new(
+            self.db
+                .get_recent_block_pointers(self.reorg_threshold)
+                .await?,
+            self.manifest.min_start_block(),
+            self.reorg_threshold,
+        );
+        Ok(())
+    }
+
+    /// Drop what was processed but not committed yet,
+    /// then pick up from the last committed block
+    async fn reset(&mut self) -> Result<(), MainError> {
+        if let Err(e) = self.wait_for_commit().await {
+            warn!(SubgraphHost, "Last commit failed"; subgraph => self.cfg.name, error => e.to_string());
+        }
+
+        let committed = self
+            .db
+            .get_recent_block_pointers(self.reorg_threshold)
+            .await?;
+        let next_block = match committed.iter().map(|block_ptr| block_ptr.number).max() {
+            Some(block_number) => block_number + 1,
+            None => match self.manifest.min_start_block() {
+                StartBlock::Number(block_number) => block_number,
+                StartBlock::Latest => 0,
+            },
+        };
+        self.revert_from(next_block).await
+    }
+
+    /// Cancel-safe: the pending write is only dropped once it has completed
+    async fn wait_for_commit(&mut self) -> Result<(), MainError> {
+        if let Some(commit) = self.commit.as_mut() {
+            let result = commit.await.expect("Commit task panicked");
+            self.commit = None;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Hand the changes of the blocks processed so far over to a background write
+    async fn commit(&mut self) -> Result<(), MainError> {
+        if self.uncommitted_block.is_none() {
+            return Ok(());
+        }
+
+        // Snapshots of the previous hand-off are readable from memory until this one.
+        // The block is only taken afterwards so a cancelled wait loses nothing
+        self.wait_for_commit().await?;
+        let block_ptr = self.uncommitted_block.take().unwrap();
+        let commit = self.db.take_commit(
+            block_ptr,
+            self.manifest.take_pending_datasources(),
+            self.block_data_retention,
+        )?;
+        self.commit = Some(tokio::spawn(commit.write()));
+        self.uncommitted_blocks = 0;
+        self.last_commit = Instant::now();
+        Ok(())
+    }
+
+    /// Commit what is still in memory, then wait for it to be written
+    async fn finish(&mut self) -> Result<(), MainError> {
+        self.commit().await?;
+        self.wait_for_commit().await
+    }
+
+    fn has_commit_schedule(&self) -> bool {
+        self.commit_every_blocks.is_some()
+            || self.commit_every_ms.is_some()
+            || self.max_cache_entities.is_some()
+    }
+
+    fn is_commit_due(&self) -> bool {
+        self.commit_every_blocks
+            .is_some_and(|blocks| self.uncommitted_blocks >= blocks)
+            || self
+                .commit_every_ms
+                .is_some_and(|ms| self.last_commit.elapsed() >= Duration::from_millis(ms))
+            || self
+                .max_cache_entities
+                .is_some_and(|max| self.db.count_cached_entities() >= max)
+    }
+
+    /// Skip what was already processed when the shared stream starts over
+    fn rewind(&mut self) {
+        self.resume_from = match self.inspector.get_expected_block_number() {
+            StartBlock::Number(block_number) => Some(block_number),
+            StartBlock::Latest => None,
+        };
+    }
+
+    /// `caught_up` is set when no other batch is waiting, eg: at chain head
+    async fn handle_blocks(
+        &mut self,
+        blocks: Vec<BlockDataMessage>,
+        caught_up: bool,
+    ) -> Result<(), MainError> {
+        let time = Instant::now();
+        let blocks = self.filter.filter_multi(blocks)?;
+        let count_blocks = blocks.len();
+        if blocks.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            SubgraphHost,
+            "data scanned & filtered 🔎";
+            subgraph => self.cfg.name,
+            exec_time => format!("{:?}", time.elapsed()),
+            count_blocks => count_blocks
+        );
+
+        let time = Instant::now();
+
+        for block in blocks {
+            let block_ptr = block.get_block_ptr();
+
+            if self
+                .resume_from
+                .is_some_and(|block_number| block_ptr.number < block_number)
+            {
+                continue;
+            }
+            self.resume_from = None;
+
+            self.rpc.set_block_ptr(&block_ptr);
+            self.manifest.set_block_ptr(&block_ptr);
+
+            match self.inspector.check_block(block_ptr.clone()) {
+                BlockInspectionResult::UnexpectedBlock
+                | BlockInspectionResult::UnrecognizedBlock => {
+                    panic!("Bad block data from source");
+                }
+                BlockInspectionResult::BlockAlreadyProcessed
+                | BlockInspectionResult::MaybeReorg => {
+                    continue;
+                }
+                BlockInspectionResult::ForkBlock => {
+                    // Changes still in memory are committed first, so the revert covers them too
+                    self.commit().await?;
+                    self.wait_for_commit().await?;
+                    self.db.revert_from_block(block_ptr.number).await?;
+                    self.manifest.revert_datasources(block_ptr.number);
+                }
+                BlockInspectionResult::OkToProceed => (),
+            };
+
+            if self.subgraph.should_process(&block) {
+                self.subgraph.process(block)?;
+                self.rpc.clear_block_level_cache();
+            }
+
+            self.uncommitted_block = Some(block_ptr);
+            self.uncommitted_blocks += 1;
+            if self.is_commit_due() {
+                self.commit().await?;
+            }
+        }
+
+        // Nothing was processed while still catching up with the stream
+        if self.resume_from.is_some() {
+            return Ok(());
+        }
+
+        let elapsed = time.elapsed();
+
+        // Without a schedule every batch is committed, once caught up every block is
+        if !self.has_commit_schedule() || caught_up {
+            self.commit().await?;
+        }
+
+        info!(
+            SubgraphHost,
+            "BLOCK BATCH PROCESSED DONE  🎉🎉🎉🎉";
+            subgraph => self.cfg.name,
+            exec_time => format!("{:?}", elapsed),
+            number_of_blocks => count_blocks,
+            avg_speed => format!("~{:.0} blocks/sec", count_blocks as f64 / elapsed.as_secs_f64())
+        );
+        Ok(())
+    }
+}
+
+/// Every subgraph of the runtime, all fed from a single block stream
+pub struct SubgraphHost {
+    subgraphs: Vec<HostedSubgraph>,
+}
+
+impl SubgraphHost {
+    pub async fn new(config: &Config) -> Result<Self, MainError> {
+        let mut subgraphs = vec![];
+        for cfg in config.hosted_subgraphs() {
+            subgraphs.push(HostedSubgraph::new(config, &cfg).await?);
+        }
+        Ok(Self { subgraphs })
+    }
+
+    /// Store & schema of every hosted subgraph, by name
+    pub fn stores(&self) -> Vec<(String, Arc<ExternDB>, Schemas)> {
+        self.subgraphs
+            .iter()
+            .map(|subgraph| {
+                (
+                    subgraph.name().to_owned(),
+                    subgraph.extern_db(),
+                    subgraph.schemas(),
+                )
+            })
+            .collect()
+    }
+
+    /// Block to (re)start the shared stream from: the lowest cursor of all subgraphs.
+    /// A subgraph starting from `Latest` picks up wherever the stream begins
+    pub fn start_block(&mut self) -> StartBlock {
+        self.subgraphs.iter_mut().for_each(HostedSubgraph::rewind);
+        self.subgraphs
+            .iter()
+            .filter_map(|subgraph| subgraph.resume_from)
+            .min()
+            .map(StartBlock::Number)
+            .unwrap_or(StartBlock::Latest)
+    }
+
+    pub fn status(&self) -> Vec<SubgraphStatus> {
+        self.subgraphs.iter().map(HostedSubgraph::status).collect()
+    }
+
+    /// Returns true when the stream has to start over
+    pub async fn apply(&mut self, command: AdminCommand) -> Result<bool, MainError> {
+        match command {
+            AdminCommand::Revert {
+                subgraph,
+                block_number,
+            } => {
+                let Some(hosted) = self
+                    .subgraphs
+                    .iter_mut()
+                    .find(|hosted| hosted.name() == subgraph)
+                else {
+                    warn!(SubgraphHost, "Cannot revert, subgraph not hosted"; subgraph => subgraph);
+                    return Ok(false);
+                };
+                warn!(SubgraphHost, "Manual revert"; subgraph => subgraph, block_number => block_number);
+                hosted.revert(block_number).await
+            }
+        }
+    }
+
+    /// Recover every subgraph from a failed batch
+    pub async fn reset(&mut self) -> Result<(), MainError> {
+        for subgraph in self.subgraphs.iter_mut() {
+            subgraph.reset().await?;
+        }
+        Ok(())
+    }
+
+    /// Commit what is still in memory & wait for the writes to complete
+    pub async fn finish(&mut self) -> Result<(), MainError> {
+        for subgraph in self.subgraphs.iter_mut() {
+            subgraph.finish().await?;
+        }
+        Ok(())
+    }
+
+    /// Every subgraph filters & processes its own copy of the batch
+    pub async fn handle_blocks(
+        &mut self,
+        blocks: Vec<BlockDataMessage>,
+        caught_up: bool,
+    ) -> Result<(), MainError> {
+        let Some((last, others)) = self.subgraphs.split_last_mut() else {
+            return Ok(());
+        };
+
+        for subgraph in others {
+            subgraph.handle_blocks(blocks.clone(), caught_up).await?;
+        }
+
+        last.handle_blocks(blocks, caught_up).await
+    }
+
+    /// Add & remove subgraphs to match a reloaded config. Returns true when
+    /// an added subgraph needs blocks that the stream has already passed,
+    /// so the stream has to start over
+    pub async fn reload(&mut self, config: &Config, next_block: u64) -> Result<bool, MainError> {
+        let wanted = config.hosted_subgraphs();
+        let (kept, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.subgraphs)
+            .into_iter()
+            .partition(|subgraph| wanted.contains(&subgraph.cfg));
+        self.subgraphs = kept;
+
+        for mut subgraph in removed {
+            subgraph.finish().await?;
+            remove_subgraph_registry(subgraph.name());
+            warn!(SubgraphHost, "Subgraph removed"; subgraph => subgraph.name());
+        }
+
+        let mut restart = false;
+
+        for cfg in wanted {
+            if self.subgraphs.iter().any(|subgraph| subgraph.cfg == cfg) {
+                continue;
+            }
+
+            let subgraph = HostedSubgraph::new(config, &cfg).await?;
+            restart |= subgraph
+                .resume_from
+                .is_some_and(|block_number| block_number < next_block);
+            warn!(SubgraphHost, "Subgraph added"; subgraph => cfg.name);
+            self.subgraphs.push(subgraph);
+        }
+
+        Ok(restart)
+    }
+}
@@ -53,6 +53,11 @@ impl Inspector {
         self.start_block.clone()
     }
 
+    /// Most recent first
+    pub fn recent_block_ptrs(&self) -> Vec<BlockPtr> {
+        self.recent_block_ptrs.iter().cloned().collect()
+    }
+
     pub fn check_block(&mut self, new_block_ptr: BlockPtr) -> BlockInspectionResult {
         match self.recent_block_ptrs.front() {
             None => match self.get_expected_block_number() {
@@ -26,7 +26,7 @@ impl LocalFileLoader {
         let subgraph_yaml = LocalFileLoader::load_yaml(subgraph_dir)?;
         let abis = LocalFileLoader::load_abis(subgraph_dir, &subgraph_yaml)?;
         let wasms = LocalFileLoader::load_wasm(subgraph_dir, &subgraph_yaml)?;
-        let schema = LocalFileLoader::load_schema(subgraph_dir)?;
+        let (schema_source, schema) = LocalFileLoader::load_schema(subgraph_dir)?;
         let datasources = DatasourceBundles::from((&subgraph_yaml.dataSources, &abis, &wasms));
         let templates = DatasourceBundles::from((
             subgraph_yaml.templates.clone().unwrap_or(vec![]).as_ref(),
@@ -39,20 +39,24 @@ impl LocalFileLoader {
             abis,
             wasms,
             schema,
+            schema_source,
             datasources,
             templates,
             block_ptr: BlockPtr::default(),
             templates_address_filter: HashMap::default(),
+            template_instances: HashMap::default(),
+            pending_datasources: vec![],
         };
 
         Ok(manifest)
     }
 
-    fn load_schema(subgraph_dir: &str) -> Result<Schemas, ManifestLoaderError> {
+    fn load_schema(subgraph_dir: &str) -> Result<(String, Schemas), ManifestLoaderError> {
         let schema_path = format!("{}/schema.graphql", subgraph_dir);
         let schema =
             read_to_string(schema_path).map_err(|_| ManifestLoaderError::SchemaParsingError)?;
-        Ok(Schemas::new_from_graphql_schema(&schema))
+        let schemas = Schemas::new_from_graphql_schema(&schema);
+        Ok((schema, schemas))
     }
 
     fn load_yaml(subgraph_dir: &str) -> Result<SubgraphYaml, ManifestLoaderError> {
@@ -104,6 +108,7 @@ impl LocalFileLoader {
 mod test {
     use super::*;
     use crate::components::ManifestAgent;
+    use crate::runtime::asc::native_types::store::Value;
     use df_logger::loggers::init_logger;
 
     #[test]
@@ -139,4 +144,105 @@ mod test {
         assert_eq!(m.datasources().len(), 2);
         assert_eq!(m.datasource_and_templates().len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_create_datasource_with_context() {
+        init_logger();
+        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build")
+            .await
+            .unwrap();
+        let template = m.datasource_and_templates().inner().pop().unwrap().name();
+        let address = "0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8".to_string();
+        let context = RawEntity::from([(
+            "token0".to_string(),
+            Value::String("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()),
+        )]);
+
+        m.set_block_ptr(&BlockPtr {
+            number: 12376729,
+            ..Default::default()
+        });
+        m.create_datasource(&template, vec![address.clone()], Some(context.clone()))
+            .unwrap();
+
+        let address = address.to_lowercase();
+        assert!(m.should_process_address(&template, &address));
+        let instance = m.get_template_instance(&template, &address).unwrap();
+        assert_eq!(instance.source.startBlock, Some(12376729));
+        assert_eq!(instance.context_entity(), Some(context.clone()));
+
+        // Created instances survive a round-trip through storage
+        let pending = m.take_pending_datasources();
+        assert_eq!(pending.len(), 1);
+        assert!(m.take_pending_datasources().is_empty());
+        let stored = serde_json::to_value(&pending).unwrap();
+
+        let restarted = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build")
+            .await
+            .unwrap();
+        restarted.restore_datasources(serde_json::from_value(stored).unwrap());
+        assert!(restarted.should_process_address(&template, &address));
+        let instance = restarted
+            .get_template_instance(&template, &address)
+            .unwrap();
+        assert_eq!(instance.context_entity(), Some(context));
+    }
+
+    #[tokio::test]
+    async fn test_create_datasource_twice() {
+        init_logger();
+        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build")
+            .await
+            .unwrap();
+        let template = m.datasource_and_templates().inner().pop().unwrap().name();
+        let address = "0x000000000000000000000000000000000000000a";
+
+        for block_number in [10, 10, 20] {
+            m.set_block_ptr(&BlockPtr {
+                number: block_number,
+                ..Default::default()
+            });
+            m.create_datasource(&template, vec![address.to_uppercase()], None)
+                .unwrap();
+        }
+
+        let pending = m.take_pending_datasources();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].source.startBlock, Some(10));
+        let instance = m.get_template_instance(&template, address).unwrap();
+        assert_eq!(instance.source.startBlock, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_revert_datasources() {
+        init_logger();
+        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build")
+            .await
+            .unwrap();
+        let template = m.datasource_and_templates().inner().pop().unwrap().name();
+        let addresses = [
+            "0x000000000000000000000000000000000000000a",
+            "0x000000000000000000000000000000000000000b",
+        ];
+
+        for (block_number, address) in [(10, addresses[0]), (20, addresses[1])] {
+            m.set_block_ptr(&BlockPtr {
+                number: block_number,
+                ..Default::default()
+            });
+            m.create_datasource(&template, vec![address.to_string()], None)
+                .unwrap();
+        }
+
+        m.revert_datasources(15);
+
+        assert!(m.get_template_instance(&template, addresses[0]).is_some());
+        assert!(m.get_template_instance(&template, addresses[1]).is_none());
+        assert!(m.should_process_address(&template, addresses[0]));
+        assert!(!m.should_process_address(&template, addresses[1]));
+
+        let pending = m.take_pending_datasources();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].source.address.as_deref(), Some(addresses[0]));
+    }
 }
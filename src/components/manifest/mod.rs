@@ -1,8 +1,10 @@
 mod local;
 
 use crate::common::*;
+use crate::debug;
 use crate::error;
 use crate::errors::ManifestLoaderError;
+use crate::warn;
 use local::LocalFileLoader;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -16,9 +18,12 @@ pub struct ManifestBundle {
     abis: ABIs,
     wasms: WASMs,
     schema: Schemas,
+    schema_source: String,
     datasources: DatasourceBundles,
     block_ptr: BlockPtr,
     templates_address_filter: HashMap<String, HashSet<String>>,
+    template_instances: HashMap<(String, String), Datasource>,
+    pending_datasources: Vec<Datasource>,
 }
 
 #[derive(Clone, Default)]
@@ -47,6 +52,11 @@ impl ManifestAgent {
         manifest.schema.clone()
     }
 
+    pub fn schema_source(&self) -> String {
+        let manifest = self.0.borrow();
+        manifest.schema_source.clone()
+    }
+
     pub fn get_wasm(&self, source_name: &str) -> Vec<u8> {
         let manifest = self.0.borrow();
         manifest.wasms.get(source_name).unwrap()
@@ -89,6 +99,21 @@ impl ManifestAgent {
         manifest.subgraph_yaml.min_start_block().into()
     }
 
+    pub fn non_fatal_errors(&self) -> bool {
+        let manifest = self.0.borrow();
+        manifest.subgraph_yaml.non_fatal_errors()
+    }
+
+    pub fn graft(&self) -> Option<Graft> {
+        let manifest = self.0.borrow();
+        manifest.subgraph_yaml.graft.clone()
+    }
+
+    pub fn templates(&self) -> DatasourceBundles {
+        let manifest = self.0.borrow();
+        manifest.templates.clone()
+    }
+
     pub fn datasource_and_templates(&self) -> DatasourceBundles {
         let manifest = self.0.borrow();
         let mut active_ds = manifest.datasources.clone();
@@ -101,6 +126,7 @@ impl ManifestAgent {
         &self,
         name: &str,
         params: Vec<String>,
+        context: Option<RawEntity>,
     ) -> Result<(), ManifestLoaderError> {
         let mut manifest = self.0.borrow_mut();
         let address = params.first().cloned().map(|s| s.to_lowercase());
@@ -113,21 +139,130 @@ impl ManifestAgent {
             return Err(ManifestLoaderError::CreateDatasourceFail);
         }
 
-        if !manifest.templates_address_filter.contains_key(name) {
-            manifest
-                .templates_address_filter
-                .insert(name.to_string(), HashSet::new());
+        let address = address.unwrap();
+        let key = (name.to_string(), address.clone());
+        // Pending instances are template instances too, the first one keeps its startBlock
+        if manifest.template_instances.contains_key(&key) {
+            debug!(Manifest, "datasource already exists, skip"; name => name, address => address);
+            return Ok(());
         }
 
+        let mut datasource = manifest
+            .templates
+            .ds
+            .iter()
+            .find(|ds| ds.name() == name)
+            .map(|ds| ds.ds.clone())
+            .ok_or(ManifestLoaderError::InvalidDataSource(name.to_owned()))?;
+
+        datasource.source.address = Some(address.clone());
+        datasource.source.startBlock = Some(manifest.block_ptr.number);
+        datasource.set_context_entity(context);
+
         manifest
             .templates_address_filter
-            .get_mut(name)
-            .unwrap()
-            .insert(address.clone().unwrap());
+            .entry(name.to_string())
+            .or_default()
+            .insert(address.clone());
+        manifest.template_instances.insert(key, datasource.clone());
+        manifest.pending_datasources.push(datasource);
 
         Ok(())
     }
 
+    /// Re-register template instances that were created before a restart
+    pub fn restore_datasources(&self, datasources: Vec<Datasource>) {
+        let mut manifest = self.0.borrow_mut();
+
+        for datasource in datasources {
+            let name = datasource.name.clone();
+            let is_template = manifest.templates.ds.iter().any(|ds| ds.name() == name);
+
+            match datasource.source.address.clone() {
+                Some(address) if is_template => {
+                    let address = address.to_lowercase();
+                    manifest
+                        .templates_address_filter
+                        .entry(name.clone())
+                        .or_default()
+                        .insert(address.clone());
+                    manifest
+                        .template_instances
+                        .insert((name, address), datasource);
+                }
+                _ => {
+                    warn!(Manifest, "skip restoring unknown datasource"; name => name);
+                }
+            }
+        }
+    }
+
+    /// Template instances created since the last call, to be persisted on commit
+    pub fn take_pending_datasources(&self) -> Vec<Datasource> {
+        let mut manifest = self.0.borrow_mut();
+        std::mem::take(&mut manifest.pending_datasources)
+    }
+
+    /// Forget template instances created at or after `from_block`, when a reorg undoes them
+    pub fn revert_datasources(&self, from_block: u64) {
+        let mut manifest = self.0.borrow_mut();
+        let is_reverted = |ds: &Datasource| ds.source.startBlock.unwrap_or_default() >= from_block;
+
+        manifest.pending_datasources.retain(|ds| !is_reverted(ds));
+
+        let reverted = manifest
+            .template_instances
+            .iter()
+            .filter(|(_, ds)| is_reverted(ds))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for (name, address) in reverted {
+            if let Some(addresses) = manifest.templates_address_filter.get_mut(&name) {
+                addresses.remove(&address);
+            }
+            manifest.template_instances.remove(&(name, address));
+        }
+    }
+
+    /// Datasources from the manifest & those created from templates so far
+    pub fn active_datasources(&self) -> Vec<Datasource> {
+        let manifest = self.0.borrow();
+        manifest
+            .datasources
+            .ds
+            .iter()
+            .map(|bundle| bundle.ds.clone())
+            .chain(manifest.template_instances.values().cloned())
+            .collect()
+    }
+
+    /// Instances of a template by creation block, ties broken by address
+    pub fn template_instances(&self, name: &str) -> Vec<Datasource> {
+        let manifest = self.0.borrow();
+        let mut instances = manifest
+            .template_instances
+            .iter()
+            .filter(|((template, _), _)| template == name)
+            .map(|(_, ds)| ds.clone())
+            .collect::<Vec<_>>();
+        instances.sort_by_key(|ds| (ds.source.startBlock, ds.source.address.clone()));
+        instances
+    }
+
+    pub fn template_addresses(&self) -> HashMap<String, HashSet<String>> {
+        let manifest = self.0.borrow();
+        manifest.templates_address_filter.clone()
+    }
+
+    pub fn get_template_instance(&self, name: &str, address: &str) -> Option<Datasource> {
+        let manifest = self.0.borrow();
+        manifest
+            .template_instances
+            .get(&(name.to_owned(), address.to_owned()))
+            .cloned()
+    }
+
     pub fn should_process_address(&self, name: &str, address: &str) -> bool {
         let manifest = self.0.borrow();
         let template = manifest.templates_address_filter.get(name);
@@ -1,5 +1,6 @@
 mod block_source;
 mod data_filter;
+mod host;
 mod inspector;
 mod manifest;
 mod subgraph;
@@ -7,6 +8,7 @@ mod valve;
 
 pub use block_source::BlockSource;
 pub use data_filter::DataFilter;
+pub use host::SubgraphHost;
 pub use inspector::BlockInspectionResult;
 pub use inspector::Inspector;
 pub use manifest::ManifestAgent;
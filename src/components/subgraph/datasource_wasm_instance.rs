@@ -1,16 +1,27 @@
+use crate::chain::ethereum::block::AscEthereumBlock;
+use crate::chain::ethereum::event::AscEthereumEvent;
+use crate::chain::ethereum::event::AscEthereumEventV7;
+use crate::chain::ethereum::event::EthereumEventData;
+use crate::chain::ethereum::transaction::AscEthereumTransaction;
 use crate::common::Datasource;
 use crate::common::DatasourceBundle;
 use crate::common::HandlerTypes;
+use crate::common::RawEntity;
 use crate::components::ManifestAgent;
+use crate::config::WasmHostConfig;
 use crate::database::DatabaseAgent;
+use crate::errors::NonDeterministicError;
 use crate::errors::SubgraphError;
+use crate::ipfs_client::IpfsAgent;
 use crate::rpc_client::RpcAgent;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscIndexId;
 use crate::runtime::asc::base::AscType;
 use crate::runtime::asc::base::ToAscObj;
 use crate::runtime::wasm_host::AscHost;
+use semver::Version;
 use std::collections::HashMap;
+use std::time::Instant;
 use wasmer::Exports;
 use wasmer::Function;
 use wasmer::Value;
@@ -37,6 +48,7 @@ pub struct EthereumHandlers {
     pub block: HashMap<String, Handler>,
     pub transaction: HashMap<String, Handler>,
     pub events: HashMap<String, Handler>,
+    pub calls: HashMap<String, Handler>,
 }
 
 pub struct DatasourceWasmInstance {
@@ -52,6 +64,7 @@ impl TryFrom<(&AscHost, &Datasource)> for EthereumHandlers {
         let mut eth_event_handlers = HashMap::new();
         let mut eth_block_handlers = HashMap::new();
         let mut eth_transaction_handlers = HashMap::new();
+        let mut eth_call_handlers = HashMap::new();
 
         for event_handler in ds.mapping.eventHandlers.clone().unwrap_or_default().iter() {
             // FIXME: assuming handlers are ethereum-event handler, must fix later
@@ -76,20 +89,40 @@ impl TryFrom<(&AscHost, &Datasource)> for EthereumHandlers {
             eth_transaction_handlers.insert(transaction_handler.handler.to_owned(), handler);
         }
 
+        for call_handler in ds.mapping.callHandlers.clone().unwrap_or_default().iter() {
+            let handler = Handler::new(&host.instance.exports, &call_handler.handler)?;
+            eth_call_handlers.insert(call_handler.handler.to_owned(), handler);
+        }
+
         Ok(EthereumHandlers {
             block: eth_block_handlers,
             events: eth_event_handlers,
             transaction: eth_transaction_handlers,
+            calls: eth_call_handlers,
         })
     }
 }
 
-impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)>
-    for DatasourceWasmInstance
+impl
+    TryFrom<(
+        DatasourceBundle,
+        DatabaseAgent,
+        RpcAgent,
+        IpfsAgent,
+        ManifestAgent,
+        WasmHostConfig,
+    )> for DatasourceWasmInstance
 {
     type Error = SubgraphError;
     fn try_from(
-        value: (DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent),
+        value: (
+            DatasourceBundle,
+            DatabaseAgent,
+            RpcAgent,
+            IpfsAgent,
+            ManifestAgent,
+            WasmHostConfig,
+        ),
     ) -> Result<Self, Self::Error> {
         let host = AscHost::try_from(value.clone())
             .map_err(|e| SubgraphError::CreateSourceFail(e.to_string()))?;
@@ -118,16 +151,65 @@ impl DatasourceWasmInstance {
             HandlerTypes::EthereumTransaction => {
                 self.ethereum_handlers.transaction.get(handler_name)
             }
+            HandlerTypes::EthereumCall => self.ethereum_handlers.calls.get(handler_name),
         }
         .ok_or(SubgraphError::InvalidHandlerName(handler_name.to_owned()))?;
 
+        self.host.reset_gas();
         let asc_data = asc_new(&mut self.host, &data)?;
-        handler.inner.call(
+        let result = handler.inner.call(
             &mut self.host.store,
             &[Value::I32(asc_data.wasm_ptr() as i32)],
-        )?;
+        );
+
+        match result {
+            Err(_) if self.host.gas_exhausted() => {
+                Err(SubgraphError::GasExhausted(handler_name.to_owned()))
+            }
+            // A handler that returned past its deadline still succeeded
+            Err(_) if self.host.timed_out() => Err(SubgraphError::Timeout(handler_name.to_owned())),
+            Err(e) => match e.downcast::<NonDeterministicError>() {
+                Ok(cause) => Err(SubgraphError::NonDeterministic(
+                    handler_name.to_owned(),
+                    cause,
+                )),
+                Err(e) => Err(SubgraphError::HandlerFailed(
+                    handler_name.to_owned(),
+                    e.message(),
+                )),
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Events carry their transaction receipt starting from apiVersion 0.0.7
+    pub fn invoke_event(
+        &mut self,
+        handler_name: &str,
+        event: EthereumEventData,
+    ) -> Result<(), SubgraphError> {
+        if self.host.api_version >= Version::new(0, 0, 7) {
+            self.invoke::<AscEthereumEventV7<AscEthereumTransaction, AscEthereumBlock>>(
+                HandlerTypes::EthereumEvent,
+                handler_name,
+                event,
+            )
+        } else {
+            self.invoke::<AscEthereumEvent<AscEthereumTransaction, AscEthereumBlock>>(
+                HandlerTypes::EthereumEvent,
+                handler_name,
+                event,
+            )
+        }
+    }
+
+    pub fn set_datasource_instance(&mut self, address: Option<String>, context: Option<RawEntity>) {
+        self.host.set_datasource_instance(address, context);
+    }
 
-        Ok(())
+    /// Bound the wall-clock time of the next handler calls
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.host.set_deadline(deadline);
     }
 
     pub fn should_reset(&self) -> bool {
@@ -144,7 +226,6 @@ mod tests {
     use df_logger::loggers::init_logger;
     use num_bigint::BigInt;
     use prometheus::Registry;
-    use semver::Version;
     use std::str::FromStr;
     use web3::types::Address;
     use web3::types::Log;
@@ -173,6 +254,7 @@ mod tests {
                 block: HashMap::new(),
                 transaction: txs_handlers,
                 events: HashMap::new(),
+                calls: HashMap::new(),
             },
         };
         let mut logs = vec![];
@@ -13,6 +13,10 @@ pub struct SubgraphMetrics {
     pub current_block_number: IntGauge,
     pub datasource_creation_counter: IntCounter,
     pub datasource_creation_duration: Histogram,
+    pub handler_timeout_counter: IntCounter,
+    pub block_retry_counter: IntCounter,
+    pub block_skip_counter: IntCounter,
+    pub non_fatal_error_counter: IntCounter,
 }
 
 impl SubgraphMetrics {
@@ -60,6 +64,39 @@ impl SubgraphMetrics {
             .register(Box::new(datasource_creation_duration.clone()))
             .unwrap_or_default();
 
+        let handler_timeout_counter = IntCounter::new(
+            "handler_timeout_counter",
+            "count handlers interrupted by a timeout",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(handler_timeout_counter.clone()))
+            .unwrap_or_default();
+
+        let block_retry_counter = IntCounter::new(
+            "block_retry_counter",
+            "count blocks processed again after a timeout",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(block_retry_counter.clone()))
+            .unwrap_or_default();
+
+        let block_skip_counter =
+            IntCounter::new("block_skip_counter", "count blocks skipped after a timeout").unwrap();
+        registry
+            .register(Box::new(block_skip_counter.clone()))
+            .unwrap_or_default();
+
+        let non_fatal_error_counter = IntCounter::new(
+            "non_fatal_error_counter",
+            "count handler errors skipped with nonFatalErrors",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(non_fatal_error_counter.clone()))
+            .unwrap_or_default();
+
         Self {
             block_process_duration,
             eth_event_process_duration,
@@ -67,6 +104,10 @@ impl SubgraphMetrics {
             current_block_number,
             datasource_creation_counter,
             datasource_creation_duration,
+            handler_timeout_counter,
+            block_retry_counter,
+            block_skip_counter,
+            non_fatal_error_counter,
         }
     }
 }
@@ -4,50 +4,80 @@ mod metrics;
 use super::ManifestAgent;
 use crate::chain::ethereum::block::EthereumBlockData;
 use crate::chain::ethereum::transaction::EthereumTransactionReceipt;
+use crate::common::Datasource;
+use crate::common::EthereumFilteredBlockHandler;
+use crate::common::EthereumFilteredCall;
 use crate::common::EthereumFilteredEvent;
 use crate::common::FilteredDataMessage;
 use crate::common::HandlerTypes;
+use crate::common::SubgraphErrorRecord;
+use crate::config::TimeoutConfig;
+use crate::config::TimeoutPolicy;
+use crate::config::WasmHostConfig;
 use crate::database::DatabaseAgent;
+use crate::error;
+use crate::errors::RPCError;
 use crate::errors::SubgraphError;
 use crate::info;
+use crate::ipfs_client::IpfsAgent;
 use crate::rpc_client::RpcAgent;
+use crate::warn;
 use datasource_wasm_instance::DatasourceWasmInstance;
+use ethabi::LogParam;
 use metrics::SubgraphMetrics;
 use prometheus::Registry;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+use std::time::Instant;
+use web3::types::H256;
+use web3::types::U256;
+use web3::types::U64;
 
 pub struct Subgraph {
     sources: HashMap<(String, Option<String>), DatasourceWasmInstance>,
     metrics: SubgraphMetrics,
     rpc: RpcAgent,
+    ipfs: IpfsAgent,
     db: DatabaseAgent,
     manifest: ManifestAgent,
+    wasm_host_cfg: WasmHostConfig,
+    timeout_cfg: TimeoutConfig,
+    block_deadline: Option<Instant>,
 }
 
 impl Subgraph {
     pub fn new(
         db: &DatabaseAgent,
         rpc: &RpcAgent,
+        ipfs: &IpfsAgent,
         manifest: &ManifestAgent,
+        wasm_host_cfg: &WasmHostConfig,
+        timeout_cfg: &TimeoutConfig,
         registry: &Registry,
     ) -> Self {
         Self {
             sources: HashMap::new(),
             metrics: SubgraphMetrics::new(registry),
             rpc: rpc.clone(),
+            ipfs: ipfs.clone(),
             db: db.clone(),
             manifest: manifest.clone(),
+            wasm_host_cfg: wasm_host_cfg.clone(),
+            timeout_cfg: timeout_cfg.clone(),
+            block_deadline: None,
         }
     }
 
     pub fn should_process(&self, data: &FilteredDataMessage) -> bool {
         match data {
-            FilteredDataMessage::Ethereum { events, .. } => {
-                return !events.is_empty()
-                    || self
-                        .sources
-                        .values()
-                        .any(|ds| !ds.ethereum_handlers.block.is_empty());
+            FilteredDataMessage::Ethereum {
+                events,
+                block_handlers,
+                calls,
+                ..
+            } => {
+                return !events.is_empty() || !block_handlers.is_empty() || !calls.is_empty();
             }
         }
     }
@@ -72,7 +102,9 @@ impl Subgraph {
                         ds,
                         self.db.clone(),
                         self.rpc.clone(),
+                        self.ipfs.clone(),
                         self.manifest.clone(),
+                        self.wasm_host_cfg.clone(),
                     ))?,
                 );
             }
@@ -87,30 +119,37 @@ impl Subgraph {
         events: Vec<EthereumFilteredEvent>,
         block: EthereumBlockData,
         txs: Vec<EthereumTransactionReceipt>,
+        block_handlers: Vec<EthereumFilteredBlockHandler>,
+        calls: Vec<EthereumFilteredCall>,
     ) -> Result<(), SubgraphError> {
-        //Handle ethereum blocks
-        let mut block_handlers = HashMap::new();
-        for ((source_name, _), source_instance) in self.sources.iter() {
-            let source_block_handlers = source_instance
-                .ethereum_handlers
-                .block
-                .keys()
-                .cloned()
-                .collect::<Vec<String>>();
-            block_handlers.insert(source_name.to_owned(), source_block_handlers);
-        }
+        let block_number = block.number.as_u64();
+        let (template_block_handlers, block_handlers): (Vec<_>, Vec<_>) = block_handlers
+            .into_iter()
+            .partition(|block_handler| block_handler.template.is_some());
 
-        for (source_name, ethereum_handlers) in block_handlers {
-            // FIXME: this is not correct, block-handler may have filter itself,
-            // thus not all datasource would handle the same block
+        //Handle ethereum blocks, already filtered by each block-handler's filter
+        for block_handler in block_handlers {
             let (_, source_instance) = self
                 .sources
                 .iter_mut()
-                .find(|((name, _), _)| name == &source_name)
-                .ok_or(SubgraphError::InvalidSourceID(source_name.to_owned()))?;
-            for handler in ethereum_handlers {
-                self.metrics.eth_trigger_counter.inc();
-                source_instance.invoke(HandlerTypes::EthereumBlock, &handler, block.clone())?;
+                .find(|((name, _), _)| name == &block_handler.datasource)
+                .ok_or(SubgraphError::InvalidSourceID(
+                    block_handler.datasource.to_owned(),
+                ))?;
+            self.metrics.eth_trigger_counter.inc();
+            source_instance.set_deadline(handler_deadline(&self.timeout_cfg, self.block_deadline));
+            source_instance.invoke(
+                HandlerTypes::EthereumBlock,
+                &block_handler.handler,
+                block.clone(),
+            )?;
+        }
+
+        //Handle template instances created before this block
+        for instance in self.template_instances(&template_block_handlers) {
+            let created_at = instance.source.startBlock.unwrap_or_default();
+            if created_at < block_number {
+                self.handle_template_block(&template_block_handlers, &block, &instance)?;
             }
         }
 
@@ -135,6 +174,8 @@ impl Subgraph {
                     .ok_or(SubgraphError::InvalidSourceID(source_name.to_owned()))?;
                 for handler in ethereum_handlers {
                     self.metrics.eth_trigger_counter.inc();
+                    source_instance
+                        .set_deadline(handler_deadline(&self.timeout_cfg, self.block_deadline));
                     source_instance.invoke(
                         HandlerTypes::EthereumTransaction,
                         &handler,
@@ -144,59 +185,159 @@ impl Subgraph {
             }
         }
 
-        //Hande ethereum events
-        for event in events {
-            let ds_name = event.datasource.clone();
-            let handler_name = event.handler.clone();
-            let event_address = format!("{:?}", event.event.address).to_lowercase();
+        //Handle ethereum events & calls, in the order they happened on chain
+        let mut receipts = HashMap::new();
+        for trigger in order_triggers(events, calls) {
+            match trigger {
+                EthereumTrigger::Event(event) => self.handle_event(event, &mut receipts)?,
+                EthereumTrigger::Call(call) => self.handle_call(call, &mut receipts)?,
+            }
+        }
 
-            if let Some(source) = self
-                .sources
-                .get_mut(&(ds_name.clone(), Some(event_address)))
-            {
-                self.metrics.eth_trigger_counter.inc();
-                let timer = self
-                    .metrics
-                    .eth_event_process_duration
-                    .with_label_values(&[&ds_name, &handler_name])
-                    .start_timer();
-                source.invoke(HandlerTypes::EthereumEvent, &event.handler, event.event)?;
-                self.create_sources()?;
-                timer.stop_and_record();
-                continue;
+        //Handle template instances created by this block, including by their own block handlers
+        let mut handled = HashSet::new();
+        loop {
+            let created = self
+                .template_instances(&template_block_handlers)
+                .into_iter()
+                .filter(|instance| instance.source.startBlock == Some(block_number))
+                .filter(|instance| {
+                    handled.insert((instance.name.clone(), instance.source.address.clone()))
+                })
+                .collect::<Vec<_>>();
+
+            if created.is_empty() {
+                break;
             }
 
-            if let Some(source) = self.sources.get_mut(&(ds_name.clone(), None)) {
-                if !self.manifest.should_process_address(
-                    &event.datasource,
-                    &format!("{:?}", event.event.address).to_lowercase(),
-                ) {
-                    // NOTE: This datasource is based from a template,
-                    // and this address is not relevant to process
-                    continue;
-                }
+            for instance in created {
+                self.handle_template_block(&template_block_handlers, &block, &instance)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_event(
+        &mut self,
+        mut event: EthereumFilteredEvent,
+        receipts: &mut HashMap<H256, EthereumTransactionReceipt>,
+    ) -> Result<(), SubgraphError> {
+        let event_address = format!("{:?}", event.event.address).to_lowercase();
+        let source = find_source(
+            &mut self.sources,
+            &self.manifest,
+            &event.datasource,
+            &event_address,
+        );
 
-                // NOTE: This datasource is either based from a template or a no-address datasource,
-                // this address might be relevant if the datasource is template, or
-                // directly relevant to the no-address datasource
-                self.metrics.eth_trigger_counter.inc();
-                let timer = self
-                    .metrics
-                    .eth_event_process_duration
-                    .with_label_values(&[&ds_name, &handler_name])
-                    .start_timer();
-                source.invoke(HandlerTypes::EthereumEvent, &event.handler, event.event)?;
-                self.create_sources()?;
-                timer.stop_and_record();
+        if let Some(source) = source {
+            if event.receipt {
+                let tx_hash = event.event.transaction.hash;
+                let receipt = fetch_receipt(&mut self.rpc, receipts, tx_hash).map_err(|e| {
+                    SubgraphError::NonDeterministic(event.handler.clone(), e.into())
+                })?;
+                event.event.receipt = Some(receipt);
             }
+            self.metrics.eth_trigger_counter.inc();
+            let timer = self
+                .metrics
+                .eth_event_process_duration
+                .with_label_values(&[&event.datasource, &event.handler])
+                .start_timer();
+            source.set_deadline(handler_deadline(&self.timeout_cfg, self.block_deadline));
+            source.invoke_event(&event.handler, event.event)?;
+            self.create_sources()?;
+            timer.stop_and_record();
+        }
+        Ok(())
+    }
+
+    fn handle_call(
+        &mut self,
+        mut call: EthereumFilteredCall,
+        receipts: &mut HashMap<H256, EthereumTransactionReceipt>,
+    ) -> Result<(), SubgraphError> {
+        let call_address = format!("{:?}", call.call.to).to_lowercase();
+        let source = find_source(
+            &mut self.sources,
+            &self.manifest,
+            &call.datasource,
+            &call_address,
+        );
 
-            continue;
+        if let Some(source) = source {
+            let succeeded = complete_call(&mut self.rpc, receipts, &mut call)
+                .map_err(|e| SubgraphError::NonDeterministic(call.handler.clone(), e.into()))?;
+            if !succeeded {
+                return Ok(());
+            }
+            self.metrics.eth_trigger_counter.inc();
+            source.set_deadline(handler_deadline(&self.timeout_cfg, self.block_deadline));
+            source.invoke(HandlerTypes::EthereumCall, &call.handler, call.call)?;
+            self.create_sources()?;
+        }
+        Ok(())
+    }
+
+    fn template_instances(
+        &self,
+        template_block_handlers: &[EthereumFilteredBlockHandler],
+    ) -> Vec<Datasource> {
+        let mut templates = template_block_handlers
+            .iter()
+            .map(|block_handler| block_handler.datasource.clone())
+            .collect::<Vec<_>>();
+        templates.sort();
+        templates.dedup();
+        templates
+            .iter()
+            .flat_map(|template| self.manifest.template_instances(template))
+            .collect()
+    }
+
+    /// Run the block handlers of a template whose filter matches this instance
+    fn handle_template_block(
+        &mut self,
+        template_block_handlers: &[EthereumFilteredBlockHandler],
+        block: &EthereumBlockData,
+        instance: &Datasource,
+    ) -> Result<(), SubgraphError> {
+        let block_number = block.number.as_u64();
+        let address = instance.source.address.clone().unwrap_or_default();
+
+        for block_handler in template_block_handlers {
+            let matches = block_handler
+                .template
+                .as_ref()
+                .is_some_and(|template| template.matches(instance, block_number));
+            if block_handler.datasource != instance.name || !matches {
+                continue;
+            }
+
+            let source = find_source(
+                &mut self.sources,
+                &self.manifest,
+                &block_handler.datasource,
+                &address,
+            )
+            .ok_or(SubgraphError::InvalidSourceID(
+                block_handler.datasource.to_owned(),
+            ))?;
+            self.metrics.eth_trigger_counter.inc();
+            source.set_deadline(handler_deadline(&self.timeout_cfg, self.block_deadline));
+            source.invoke(
+                HandlerTypes::EthereumBlock,
+                &block_handler.handler,
+                block.clone(),
+            )?;
+            self.create_sources()?;
         }
 
         Ok(())
     }
 
-    pub fn process(&mut self, msg: FilteredDataMessage) -> Result<(), SubgraphError> {
+    pub fn process(&mut self, mut msg: FilteredDataMessage) -> Result<(), SubgraphError> {
         let block_ptr = msg.get_block_ptr();
 
         self.metrics
@@ -204,13 +345,259 @@ impl Subgraph {
             .set(block_ptr.number as i64);
 
         let timer = self.metrics.block_process_duration.start_timer();
-        match msg {
-            FilteredDataMessage::Ethereum { events, block, txs } => {
-                self.handle_ethereum_data(events, block, txs)?
+        let mut attempt = 0;
+
+        loop {
+            // Only keep a copy of the block when it may have to be processed again
+            let retry = (self.timeout_cfg.policy == TimeoutPolicy::Retry
+                && attempt < self.timeout_cfg.max_retries)
+                .then(|| msg.clone());
+
+            self.db.checkpoint();
+            self.block_deadline = self
+                .timeout_cfg
+                .block_ms
+                .map(|ms| Instant::now() + Duration::from_millis(ms));
+
+            let result = self.handle_message(msg);
+            if result.is_err() {
+                // An aborted handler leaves the heap & globals of its instance half-mutated
+                self.reset_sources()?;
             }
-        };
-        timer.stop_and_record();
 
+            match result {
+                Ok(()) => break,
+                Err(SubgraphError::Timeout(handler)) => {
+                    self.metrics.handler_timeout_counter.inc();
+
+                    if let Some(retry) = retry {
+                        attempt += 1;
+                        warn!(
+                            Subgraph,
+                            "Block ran out of time, processing it again";
+                            handler => handler,
+                            block_number => block_ptr.number,
+                            attempt => attempt
+                        );
+                        self.discard_block(block_ptr.number);
+                        self.metrics.block_retry_counter.inc();
+                        msg = retry;
+                        continue;
+                    }
+
+                    if self.timeout_cfg.policy == TimeoutPolicy::Skip {
+                        warn!(
+                            Subgraph,
+                            "Block ran out of time, skipping it";
+                            handler => handler,
+                            block_number => block_ptr.number
+                        );
+                        self.discard_block(block_ptr.number);
+                        self.metrics.block_skip_counter.inc();
+                        break;
+                    }
+
+                    error!(
+                        Subgraph,
+                        "Block ran out of time, subgraph failed";
+                        handler => handler,
+                        block_number => block_ptr.number
+                    );
+                    return Err(SubgraphError::Timeout(handler));
+                }
+                // Never recorded as non-fatal, the same block may succeed once the service is back
+                Err(e @ SubgraphError::NonDeterministic(..)) => {
+                    error!(
+                        Subgraph,
+                        "Handler failed outside of the mapping, subgraph failed";
+                        block_number => block_ptr.number,
+                        error => e.to_string()
+                    );
+                    self.discard_block(block_ptr.number);
+                    return Err(e);
+                }
+                Err(e) => {
+                    let handler = match &e {
+                        SubgraphError::HandlerFailed(handler, _)
+                        | SubgraphError::GasExhausted(handler) => handler.clone(),
+                        _ => return Err(e),
+                    };
+
+                    if !self.manifest.non_fatal_errors() {
+                        error!(
+                            Subgraph,
+                            "Handler failed, subgraph failed";
+                            block_number => block_ptr.number,
+                            error => e.to_string()
+                        );
+                        return Err(e);
+                    }
+
+                    // With `nonFatalErrors`, a deterministic failure only drops the block's changes
+                    warn!(
+                        Subgraph,
+                        "Handler failed, block changes discarded";
+                        block_number => block_ptr.number,
+                        error => e.to_string()
+                    );
+                    self.discard_block(block_ptr.number);
+                    self.metrics.non_fatal_error_counter.inc();
+                    self.db.record_subgraph_error(SubgraphErrorRecord {
+                        block_ptr: block_ptr.clone(),
+                        handler,
+                        message: e.to_string(),
+                    });
+                    break;
+                }
+            }
+        }
+
+        timer.stop_and_record();
         Ok(())
     }
+
+    fn handle_message(&mut self, msg: FilteredDataMessage) -> Result<(), SubgraphError> {
+        match msg {
+            FilteredDataMessage::Ethereum {
+                events,
+                block,
+                txs,
+                block_handlers,
+                calls,
+            } => self.handle_ethereum_data(events, block, txs, block_handlers, calls),
+        }
+    }
+
+    fn reset_sources(&mut self) -> Result<(), SubgraphError> {
+        self.sources.clear();
+        info!(Subgraph, "recreating datasource-wasm host instances");
+        self.create_sources()
+    }
+
+    /// Undo the entity changes & datasource creations of a partially processed block
+    fn discard_block(&mut self, block_number: u64) {
+        self.db.rollback();
+        self.manifest.revert_datasources(block_number);
+    }
+}
+
+enum EthereumTrigger {
+    Event(EthereumFilteredEvent),
+    Call(EthereumFilteredCall),
+}
+
+/// Sort by transaction, a call coming before the logs emitted while it runs and logs by
+/// log index. The sort is stable, handlers of a same log keep the order of the manifest
+fn order_triggers(
+    events: Vec<EthereumFilteredEvent>,
+    calls: Vec<EthereumFilteredCall>,
+) -> Vec<EthereumTrigger> {
+    let mut triggers = calls
+        .into_iter()
+        .enumerate()
+        .map(|(position, call)| {
+            let key = (call.call.transaction.index, 0, U256::from(position));
+            (key, EthereumTrigger::Call(call))
+        })
+        .chain(events.into_iter().map(|event| {
+            let key = (event.event.transaction.index, 1, event.event.log_index);
+            (key, EthereumTrigger::Event(event))
+        }))
+        .collect::<Vec<_>>();
+    triggers.sort_by_key(|(key, _)| *key);
+    triggers.into_iter().map(|(_, trigger)| trigger).collect()
+}
+
+/// The earliest of the handler's own time limit and the one of its block
+fn handler_deadline(
+    timeout_cfg: &TimeoutConfig,
+    block_deadline: Option<Instant>,
+) -> Option<Instant> {
+    let deadline = timeout_cfg
+        .handler_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+
+    match (deadline, block_deadline) {
+        (Some(deadline), Some(block_deadline)) => Some(deadline.min(block_deadline)),
+        (deadline, block_deadline) => deadline.or(block_deadline),
+    }
+}
+
+/// Receipts are not part of the block data, so they come from the rpc, once per transaction
+fn fetch_receipt(
+    rpc: &mut RpcAgent,
+    receipts: &mut HashMap<H256, EthereumTransactionReceipt>,
+    tx_hash: H256,
+) -> Result<EthereumTransactionReceipt, RPCError> {
+    if let Some(receipt) = receipts.get(&tx_hash) {
+        return Ok(receipt.clone());
+    }
+    let receipt = rpc.get_transaction_receipt(tx_hash)?;
+    receipts.insert(tx_hash, receipt.clone());
+    Ok(receipt)
+}
+
+/// Calls are decoded from transaction input alone, so the receipt tells whether
+/// the transaction actually succeeded and its call-trace holds the return values
+fn complete_call(
+    rpc: &mut RpcAgent,
+    receipts: &mut HashMap<H256, EthereumTransactionReceipt>,
+    call: &mut EthereumFilteredCall,
+) -> Result<bool, RPCError> {
+    let tx_hash = call.call.transaction.hash;
+    let receipt = fetch_receipt(rpc, receipts, tx_hash)?;
+    // Pre-byzantium receipts have no status, those transactions are kept
+    let succeeded = receipt.status != Some(U64::zero());
+
+    if !succeeded || call.function.outputs.is_empty() {
+        return Ok(succeeded);
+    }
+
+    let output = rpc.get_call_output(tx_hash)?;
+    let tokens = call
+        .function
+        .decode_output(&output)
+        .map_err(|_| RPCError::DataDecodingFail)?;
+    call.call.outputs = call
+        .function
+        .outputs
+        .iter()
+        .zip(tokens)
+        .map(|(param, value)| LogParam {
+            name: param.name.clone(),
+            value,
+        })
+        .collect();
+    Ok(true)
+}
+
+/// Find the wasm-instance handling a trigger emitted from/to `address`
+fn find_source<'a>(
+    sources: &'a mut HashMap<(String, Option<String>), DatasourceWasmInstance>,
+    manifest: &ManifestAgent,
+    ds_name: &str,
+    address: &str,
+) -> Option<&'a mut DatasourceWasmInstance> {
+    let key = (ds_name.to_owned(), Some(address.to_owned()));
+
+    if sources.contains_key(&key) {
+        return sources.get_mut(&key);
+    }
+
+    let source = sources.get_mut(&(ds_name.to_owned(), None))?;
+
+    if !manifest.should_process_address(ds_name, address) {
+        // NOTE: This datasource is based from a template,
+        // and this address is not relevant to process
+        return None;
+    }
+
+    if let Some(instance) = manifest.get_template_instance(ds_name, address) {
+        source.set_datasource_instance(instance.source.address.clone(), instance.context_entity());
+    }
+
+    // NOTE: This datasource is either based from a template or a no-address datasource,
+    // this address might be relevant if the datasource is template, or
+    // directly relevant to the no-address datasource
+    Some(source)
 }
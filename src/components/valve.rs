@@ -53,6 +53,14 @@ impl Valve {
         Valve(Rc::new(RefCell::new(this)))
     }
 
+    pub fn downloaded(&self) -> u64 {
+        self.0.borrow().downloaded
+    }
+
+    pub fn finished(&self) -> u64 {
+        self.0.borrow().finished
+    }
+
     pub async fn temporarily_close(&self) {
         loop {
             let this = self.0.borrow();
@@ -4,6 +4,7 @@ use figment::providers::Format;
 use figment::providers::Toml;
 use figment::Figment;
 use serde::Deserialize;
+use std::time::Duration;
 
 #[cfg(feature = "deltalake")]
 #[derive(Clone, Debug, Deserialize)]
@@ -13,6 +14,48 @@ pub struct DeltaConfig {
     pub version: Option<u64>,
 }
 
+#[cfg(feature = "file")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    /// Length-delimited `proto::ethereum::Block` messages
+    Protobuf,
+    /// One JSON-encoded `proto::ethereum::Block` per line
+    Jsonl,
+}
+
+#[cfg(feature = "file")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct FileSourceConfig {
+    pub dir: String,
+    pub format: FileFormat,
+    pub compression: bool,
+    pub query_step: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RpcSourceConfig {
+    /// Falls back to `rpc_endpoint` when not set
+    pub endpoint: Option<String>,
+    pub query_step: u64,
+    pub confirmations: u64,
+    pub poll_interval_ms: u64,
+    /// Blocks of a batch downloaded at the same time
+    #[serde(default = "default_rpc_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Attempts made after a failed request, backing off between them, before the source fails
+    #[serde(default = "default_rpc_max_retries")]
+    pub max_retries: usize,
+}
+
+fn default_rpc_max_concurrent_requests() -> usize {
+    20
+}
+
+fn default_rpc_max_retries() -> usize {
+    10
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum SourceTypes {
@@ -20,15 +63,166 @@ pub enum SourceTypes {
     Delta(DeltaConfig),
     #[cfg(feature = "pubsub")]
     PubSub { sub_id: String, compression: bool },
+    Rpc(RpcSourceConfig),
+    #[cfg(feature = "file")]
+    File(FileSourceConfig),
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DatabaseConfig {
     #[cfg(feature = "scylla")]
     Scylla { uri: String, keyspace: String },
+    /// Blocks are committed in transactions on a replica set (a single node is enough)
     #[cfg(feature = "mongo")]
     Mongo { uri: String, database: String },
+    #[cfg(feature = "postgres")]
+    Postgres { uri: String, namespace: String },
+    #[cfg(feature = "sqlite")]
+    Sqlite { path: String },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum IpfsResolverConfig {
+    /// HTTP gateway serving `/ipfs/<cid>`
+    Gateway { url: String },
+    /// Local directory holding files named by their `<cid>`
+    Local { dir: String },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IpfsConfig {
+    pub resolver: IpfsResolverConfig,
+    pub timeout_ms: u64,
+    pub max_file_size: usize,
+    /// Fetched files are kept here and never requested again
+    pub cache_dir: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WasmHostConfig {
+    /// Instantiate mappings that import unsupported host functions,
+    /// failing only when one of them is actually called
+    #[serde(default)]
+    pub stub_missing_imports: bool,
+    /// Gas available to a single handler call, every wasm instruction costs 1
+    #[serde(default = "default_gas_limit")]
+    pub gas_limit: u64,
+    /// Upper bound of the gas a handler burns per millisecond. Past its deadline, a handler
+    /// that never calls the host is stopped once it used the gas of the time it had left.
+    /// On a slower machine it is stopped later than its deadline, never earlier
+    #[serde(default = "default_gas_per_ms")]
+    pub gas_per_ms: u64,
+}
+
+fn default_gas_limit() -> u64 {
+    10_000_000_000
+}
+
+fn default_gas_per_ms() -> u64 {
+    1_000_000
+}
+
+impl Default for WasmHostConfig {
+    fn default() -> Self {
+        Self {
+            stub_missing_imports: false,
+            gas_limit: default_gas_limit(),
+            gas_per_ms: default_gas_per_ms(),
+        }
+    }
+}
+
+/// What to do with a block whose handlers ran out of time
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeoutPolicy {
+    /// Stop the subgraph at that block
+    #[default]
+    Fail,
+    /// Discard the changes made by the block and process it again
+    Retry,
+    /// Discard the changes made by the block and move on to the next one
+    Skip,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TimeoutConfig {
+    /// Wall-clock limit of a single handler call
+    pub handler_ms: Option<u64>,
+    /// Wall-clock limit of all the handlers of a block
+    pub block_ms: Option<u64>,
+    /// Limit of a single rpc request, eg: `ethereum.call`
+    pub rpc_ms: Option<u64>,
+    #[serde(default)]
+    pub policy: TimeoutPolicy,
+    /// Attempts made with the `retry` policy before failing
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            handler_ms: None,
+            block_ms: None,
+            rpc_ms: None,
+            policy: TimeoutPolicy::default(),
+            max_retries: default_max_retries(),
+        }
+    }
+}
+
+/// Backoff of the main flow when it fails for a transient reason, eg: a database timeout
+#[derive(Deserialize, Clone, Debug)]
+pub struct RetryConfig {
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// Consecutive failures before giving up, retry forever when unset
+    pub max_attempts: Option<u32>,
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    60_000
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: default_retry_base_delay_ms(),
+            max_delay_ms: default_retry_max_delay_ms(),
+            max_attempts: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Doubles with every failed attempt, up to `max_delay_ms`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self
+            .base_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt))
+            .min(self.max_delay_ms);
+        Duration::from_millis(delay_ms)
+    }
+
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        match self.max_attempts {
+            Some(max_attempts) => attempt < max_attempts,
+            None => true,
+        }
+    }
 }
 
 #[derive(Deserialize, Default, Clone, Debug)]
@@ -38,6 +232,19 @@ pub struct ValveConfig {
     pub wait_time: u64,
 }
 
+/// A subgraph hosted by the runtime, every subgraph has its own database.
+/// Commit cadence & entity cache size default to the ones of the main subgraph
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SubgraphConfig {
+    pub name: String,
+    pub dir: String,
+    pub database: DatabaseConfig,
+    pub commit_every_blocks: Option<u64>,
+    pub commit_every_ms: Option<u64>,
+    pub max_cache_entities: Option<usize>,
+    pub entity_cache_size: Option<usize>,
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct Config {
     pub chain: Chain,
@@ -47,19 +254,37 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub reorg_threshold: u16,
     pub metric_port: Option<u16>,
+    /// Bearer token required by the admin routes that pause, resume & revert,
+    /// without it they only accept requests from localhost
+    pub admin_token: Option<String>,
+    pub graphql_port: Option<u16>,
     pub rpc_endpoint: String,
     pub valve: ValveConfig,
     pub block_data_retention: Option<u64>,
+    /// Commit after this many processed blocks instead of once per source batch
+    pub commit_every_blocks: Option<u64>,
+    /// Commit when this long has passed since the last commit
+    pub commit_every_ms: Option<u64>,
+    /// Commit when this many entities were changed or loaded since the last commit
+    pub max_cache_entities: Option<usize>,
+    /// Latest committed entities kept in memory across commits, 100k by default
+    pub entity_cache_size: Option<usize>,
+    pub ipfs: Option<IpfsConfig>,
+    #[serde(default)]
+    pub wasm_host: WasmHostConfig,
+    #[serde(default)]
+    pub timeout: TimeoutConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Extra subgraphs sharing the block source of the main one,
+    /// they can be added & removed while running by editing the config file
+    #[serde(default)]
+    pub subgraphs: Vec<SubgraphConfig>,
 }
 
 impl Config {
     pub fn load() -> Self {
-        let config_file_path = std::env::var("CONFIG").unwrap_or("config.toml".to_string());
-        let cfg: Config = Figment::new()
-            .merge(Toml::file(config_file_path))
-            .merge(Env::prefixed("DFR_"))
-            .extract()
-            .expect("Load config failed");
+        let cfg = Config::try_load().expect("Load config failed");
 
         if let Some(size) = cfg.block_data_retention {
             assert!(
@@ -70,12 +295,46 @@ impl Config {
 
         cfg
     }
+
+    pub fn try_load() -> Result<Self, figment::Error> {
+        let config_file_path = std::env::var("CONFIG").unwrap_or("config.toml".to_string());
+        Figment::new()
+            .merge(Toml::file(config_file_path))
+            .merge(Env::prefixed("DFR_"))
+            .extract()
+    }
+
+    /// The main subgraph first, then the extra ones
+    pub fn hosted_subgraphs(&self) -> Vec<SubgraphConfig> {
+        let main_subgraph = SubgraphConfig {
+            name: self.subgraph_name.clone(),
+            dir: self.subgraph_dir.clone(),
+            database: self.database.clone(),
+            commit_every_blocks: self.commit_every_blocks,
+            commit_every_ms: self.commit_every_ms,
+            max_cache_entities: self.max_cache_entities,
+            entity_cache_size: self.entity_cache_size,
+        };
+        let extra_subgraphs = self.subgraphs.iter().map(|cfg| SubgraphConfig {
+            commit_every_blocks: cfg.commit_every_blocks.or(self.commit_every_blocks),
+            commit_every_ms: cfg.commit_every_ms.or(self.commit_every_ms),
+            max_cache_entities: cfg.max_cache_entities.or(self.max_cache_entities),
+            entity_cache_size: cfg.entity_cache_size.or(self.entity_cache_size),
+            ..cfg.clone()
+        });
+        std::iter::once(main_subgraph)
+            .chain(extra_subgraphs)
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::Config;
+    use super::RetryConfig;
     use df_logger::loggers::init_logger;
+    use std::time::Duration;
+
     #[test]
     fn test_config() {
         init_logger();
@@ -83,4 +342,21 @@ mod test {
         let config = Config::load();
         df_logger::log::info!("Config = {:?}", config);
     }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = RetryConfig {
+            base_delay_ms: 500,
+            max_delay_ms: 5_000,
+            max_attempts: Some(5),
+        };
+        assert_eq!(retry.delay(0), Duration::from_millis(500));
+        assert_eq!(retry.delay(1), Duration::from_millis(1_000));
+        assert_eq!(retry.delay(3), Duration::from_millis(4_000));
+        assert_eq!(retry.delay(4), Duration::from_millis(5_000));
+        assert_eq!(retry.delay(100), Duration::from_millis(5_000));
+        assert!(retry.should_retry(4));
+        assert!(!retry.should_retry(5));
+        assert!(RetryConfig::default().should_retry(u32::MAX));
+    }
 }
@@ -0,0 +1,152 @@
+use crate::common::EntityID;
+use crate::common::EntityType;
+use crate::common::RawEntity;
+use crate::common::Schemas;
+use crate::runtime::asc::native_types::store::Value;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+pub const DEFAULT_ENTITY_CACHE_SIZE: usize = 100_000;
+
+struct CachedEntity {
+    /// None when the entity does not exist or was deleted
+    entity: Option<RawEntity>,
+    used_at: u64,
+}
+
+/// Latest committed version of entities, kept across commits so hot entities
+/// are not reloaded from the database. Once full, the least recently used goes first
+pub struct EntityCache {
+    capacity: usize,
+    /// Readonly entity types are written by another subgraph, so they are never cached
+    cacheable: HashSet<EntityType>,
+    entries: HashMap<(EntityType, EntityID), CachedEntity>,
+    recency: BTreeMap<u64, (EntityType, EntityID)>,
+    clock: u64,
+}
+
+impl EntityCache {
+    pub fn new(capacity: usize, schema: &Schemas) -> Self {
+        let cacheable = schema
+            .get_entity_names()
+            .into_iter()
+            .filter(|entity_type| {
+                schema
+                    .get_config(entity_type)
+                    .unwrap_or_default()
+                    .writeable()
+            })
+            .collect();
+        Self {
+            capacity,
+            cacheable,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    pub fn get(&mut self, entity_type: &str, entity_id: &str) -> Option<Option<RawEntity>> {
+        let key = (entity_type.to_owned(), entity_id.to_owned());
+        let used_at = self.tick();
+        let cached = self.entries.get_mut(&key)?;
+        self.recency.remove(&cached.used_at);
+        cached.used_at = used_at;
+        let entity = cached.entity.clone();
+        self.recency.insert(used_at, key);
+        Some(entity)
+    }
+
+    pub fn insert(&mut self, entity_type: &str, entity_id: &str, entity: Option<RawEntity>) {
+        if self.capacity == 0 || !self.cacheable.contains(entity_type) {
+            return;
+        }
+
+        let key = (entity_type.to_owned(), entity_id.to_owned());
+        let used_at = self.tick();
+        let replaced = self
+            .entries
+            .insert(key.clone(), CachedEntity { entity, used_at });
+        if let Some(replaced) = replaced {
+            self.recency.remove(&replaced.used_at);
+        }
+        self.recency.insert(used_at, key);
+
+        while self.entries.len() > self.capacity {
+            let (_, key) = self.recency.pop_first().unwrap();
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Keep the version of an entity that is being committed
+    pub fn insert_latest(&mut self, entity_type: &str, entity: &RawEntity) {
+        let Some(Value::String(entity_id)) = entity.get("id") else {
+            return;
+        };
+        let entity_id = entity_id.clone();
+        match entity.get("__is_deleted__") {
+            Some(Value::Bool(true)) => self.insert(entity_type, &entity_id, None),
+            _ => self.insert(entity_type, &entity_id, Some(entity.clone())),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(id: &str) -> RawEntity {
+        RawEntity::from([
+            ("id".to_string(), Value::String(id.to_string())),
+            ("__is_deleted__".to_string(), Value::Bool(false)),
+        ])
+    }
+
+    #[test]
+    fn test_entity_cache_eviction() {
+        let schema = Schemas::new_from_graphql_schema(
+            r#"
+            type Token @entity {
+                id: ID!
+            }
+            type Pool @entity(mode:readonly) {
+                id: ID!
+            }
+            "#,
+        );
+        let mut cache = EntityCache::new(2, &schema);
+
+        cache.insert_latest("Token", &entity("1"));
+        cache.insert_latest("Token", &entity("2"));
+        assert!(cache.get("Token", "1").unwrap().is_some());
+
+        // Token 2 is the least recently used
+        cache.insert("Token", "3", None);
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get("Token", "2").is_none());
+        assert_eq!(cache.get("Token", "3"), Some(None));
+
+        let mut deleted = entity("1");
+        deleted.insert("__is_deleted__".to_string(), Value::Bool(true));
+        cache.insert_latest("Token", &deleted);
+        assert_eq!(cache.get("Token", "1"), Some(None));
+
+        cache.insert_latest("Pool", &entity("1"));
+        assert!(cache.get("Pool", "1").is_none());
+
+        cache.clear();
+        assert_eq!(cache.entries.len(), 0);
+        assert!(cache.get("Token", "3").is_none());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::common::RawEntity;
+use crate::runtime::asc::native_types::store::Value;
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Equal,
+    Not,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    NotIn,
+    Contains,
+    NotContains,
+    StartsWith,
+    NotStartsWith,
+    EndsWith,
+    NotEndsWith,
+}
+
+#[derive(Clone, Debug)]
+pub enum EntityFilter {
+    And(Vec<EntityFilter>),
+    Or(Vec<EntityFilter>),
+    Field {
+        field: String,
+        op: Operator,
+        value: Value,
+    },
+}
+
+impl EntityFilter {
+    pub fn matches(&self, entity: &RawEntity) -> bool {
+        match self {
+            EntityFilter::And(filters) => filters.iter().all(|f| f.matches(entity)),
+            EntityFilter::Or(filters) => filters.iter().any(|f| f.matches(entity)),
+            EntityFilter::Field { field, op, value } => {
+                let actual = entity.get(field).unwrap_or(&Value::Null);
+                let ordering = compare_values(actual, value);
+                match op {
+                    Operator::Equal => actual == value,
+                    Operator::Not => actual != value,
+                    Operator::Gt => ordering == Some(Ordering::Greater),
+                    Operator::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    Operator::Lt => ordering == Some(Ordering::Less),
+                    Operator::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Operator::In => list_contains(value, actual),
+                    Operator::NotIn => !list_contains(value, actual),
+                    Operator::Contains => contains(actual, value),
+                    Operator::NotContains => !contains(actual, value),
+                    Operator::StartsWith => starts_with(actual, value),
+                    Operator::NotStartsWith => !starts_with(actual, value),
+                    Operator::EndsWith => ends_with(actual, value),
+                    Operator::NotEndsWith => !ends_with(actual, value),
+                }
+            }
+        }
+    }
+
+    /// Top-level conditions that must all hold, so each of them can be pushed down on its own
+    pub fn conjuncts(&self) -> Vec<&EntityFilter> {
+        match self {
+            EntityFilter::And(filters) => filters.iter().flat_map(|f| f.conjuncts()).collect(),
+            filter => vec![filter],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EntityOrder {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl Default for EntityOrder {
+    fn default() -> Self {
+        EntityOrder {
+            field: "id".to_owned(),
+            descending: false,
+        }
+    }
+}
+
+impl EntityOrder {
+    /// Null sorts before any value, ties are broken by id
+    pub fn sort(&self, entities: &mut [RawEntity]) {
+        entities.sort_by(|a, b| {
+            let by_field = compare_for_sort(
+                a.get(&self.field).unwrap_or(&Value::Null),
+                b.get(&self.field).unwrap_or(&Value::Null),
+            );
+            let by_id = compare_for_sort(
+                a.get("id").unwrap_or(&Value::Null),
+                b.get("id").unwrap_or(&Value::Null),
+            );
+            let ordering = by_field.then(by_id);
+
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// Filtering, ordering & pagination of the latest snapshots of an entity type.
+/// Backends evaluate what they can natively, `apply` is the reference for the rest
+#[derive(Clone, Debug, Default)]
+pub struct EntityQuery {
+    pub filter: Option<EntityFilter>,
+    pub order: EntityOrder,
+    pub first: Option<usize>,
+    pub skip: usize,
+}
+
+impl EntityQuery {
+    pub fn apply(&self, mut entities: Vec<RawEntity>) -> Vec<RawEntity> {
+        if let Some(filter) = &self.filter {
+            entities.retain(|entity| filter.matches(entity));
+        }
+        self.order.sort(&mut entities);
+        entities
+            .into_iter()
+            .skip(self.skip)
+            .take(self.first.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    pub fn conjuncts(&self) -> Vec<&EntityFilter> {
+        self.filter
+            .as_ref()
+            .map(|filter| filter.conjuncts())
+            .unwrap_or_default()
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Int8(a), Value::Int8(b)) => Some(a.cmp(b)),
+        (Value::Int(a), Value::Int8(b)) => Some((*a as i64).cmp(b)),
+        (Value::Int8(a), Value::Int(b)) => Some(a.cmp(&(*b as i64))),
+        (Value::BigInt(a), Value::BigInt(b)) => Some(a.cmp(b)),
+        (Value::BigDecimal(a), Value::BigDecimal(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Bytes(a), Value::Bytes(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn compare_for_sort(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (a, b) => compare_values(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+fn list_contains(list: &Value, item: &Value) -> bool {
+    match list {
+        Value::List(items) => items.contains(item),
+        _ => false,
+    }
+}
+
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    match (haystack, needle) {
+        (Value::String(s), Value::String(sub)) => s.contains(sub.as_str()),
+        (Value::Bytes(b), Value::Bytes(sub)) => {
+            sub.is_empty() || b.windows(sub.len()).any(|w| w == sub.as_slice())
+        }
+        (Value::List(items), Value::List(subset)) => subset.iter().all(|i| items.contains(i)),
+        _ => false,
+    }
+}
+
+fn starts_with(value: &Value, prefix: &Value) -> bool {
+    match (value, prefix) {
+        (Value::String(s), Value::String(prefix)) => s.starts_with(prefix.as_str()),
+        (Value::Bytes(b), Value::Bytes(prefix)) => b.starts_with(prefix.as_slice()),
+        _ => false,
+    }
+}
+
+fn ends_with(value: &Value, suffix: &Value) -> bool {
+    match (value, suffix) {
+        (Value::String(s), Value::String(suffix)) => s.ends_with(suffix.as_str()),
+        (Value::Bytes(b), Value::Bytes(suffix)) => b.ends_with(suffix.as_slice()),
+        _ => false,
+    }
+}
@@ -8,13 +8,25 @@ mod mongo;
 #[cfg(feature = "mongo")]
 use mongo::*;
 
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+use postgres::*;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+use sqlite::*;
+
 use crate::common::BlockPtr;
 use crate::common::Datasource;
 use crate::common::EntityID;
 use crate::common::EntityType;
 use crate::common::RawEntity;
 use crate::common::Schemas;
+use crate::common::SubgraphErrorRecord;
 use crate::config::DatabaseConfig;
+use crate::database::EntityQuery;
 use crate::errors::DatabaseError;
 use async_trait::async_trait;
 
@@ -24,6 +36,10 @@ pub enum ExternDB {
     Scylla(Scylladb),
     #[cfg(feature = "mongo")]
     Mongo(MongoDB),
+    #[cfg(feature = "postgres")]
+    Postgres(Postgres),
+    #[cfg(feature = "sqlite")]
+    Sqlite(Sqlite),
     #[default]
     None,
 }
@@ -39,6 +55,30 @@ impl ExternDB {
             DatabaseConfig::Mongo { uri, database } => {
                 ExternDB::Mongo(MongoDB::new(uri, database, schemas).await?)
             }
+            #[cfg(feature = "postgres")]
+            DatabaseConfig::Postgres { uri, namespace } => {
+                ExternDB::Postgres(Postgres::new(uri, namespace, schemas).await?)
+            }
+            #[cfg(feature = "sqlite")]
+            DatabaseConfig::Sqlite { path } => ExternDB::Sqlite(Sqlite::new(path, schemas)?),
+        };
+
+        Ok(db)
+    }
+
+    /// Read another namespace through the connection of this one,
+    /// without creating any table or index there
+    pub fn read_namespace(&self, namespace: &str, schemas: Schemas) -> Result<Self, DatabaseError> {
+        let db = match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => ExternDB::Scylla(db.read_namespace(namespace, schemas)),
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => ExternDB::Mongo(db.read_namespace(namespace, schemas)),
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => ExternDB::Postgres(db.read_namespace(namespace, schemas)),
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(_) => ExternDB::Sqlite(Sqlite::open_read_only(namespace, schemas)?),
+            ExternDB::None => ExternDB::None,
         };
 
         Ok(db)
@@ -53,6 +93,10 @@ pub trait ExternDBTrait: Sized {
 
     async fn create_datasource_table(&self) -> Result<(), DatabaseError>;
 
+    async fn create_subgraph_error_table(&self) -> Result<(), DatabaseError>;
+
+    async fn create_schema_table(&self) -> Result<(), DatabaseError>;
+
     async fn load_entity(
         &self,
         entity_type: &str,
@@ -65,6 +109,41 @@ pub trait ExternDBTrait: Sized {
         ids: Vec<String>,
     ) -> Result<Vec<RawEntity>, DatabaseError>;
 
+    async fn load_entity_at_block(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        block_number: u64,
+    ) -> Result<Option<RawEntity>, DatabaseError>;
+
+    /// Latest non-deleted snapshot of every entity of a type,
+    /// optionally as it was at `block_number`
+    async fn scan_entities(
+        &self,
+        entity_type: &str,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError>;
+
+    /// Latest snapshot, deleted ones included, of the first `limit` entities keyed after
+    /// `after_id` as they were at `block_number`. Pages follow the key order of the backend,
+    /// so the last id of a page is where the next one starts
+    async fn scan_entities_page(
+        &self,
+        entity_type: &str,
+        block_number: u64,
+        after_id: Option<EntityID>,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError>;
+
+    /// Latest non-deleted snapshots matching `query`, optionally as they were at `block_number`.
+    /// Whatever the backend cannot evaluate natively is applied in memory
+    async fn query_entities(
+        &self,
+        entity_type: &str,
+        query: &EntityQuery,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError>;
+
     async fn create_entity(
         &self,
         block_ptr: BlockPtr,
@@ -81,16 +160,39 @@ pub trait ExternDBTrait: Sized {
 
     async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError>;
 
-    async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError>;
+    async fn load_block_ptr(&self, block_number: u64) -> Result<Option<BlockPtr>, DatabaseError>;
 
     async fn load_datasources(&self) -> Result<Option<Vec<Datasource>>, DatabaseError>;
 
+    async fn save_subgraph_errors(
+        &self,
+        errors: Vec<SubgraphErrorRecord>,
+    ) -> Result<(), DatabaseError>;
+
+    async fn load_subgraph_errors(&self) -> Result<Vec<SubgraphErrorRecord>, DatabaseError>;
+
+    /// The GraphQL schema the data is indexed with, checked by subgraphs grafting onto it
+    async fn save_schema(&self, schema: &str) -> Result<(), DatabaseError>;
+
+    async fn load_schema(&self) -> Result<Option<String>, DatabaseError>;
+
     async fn batch_insert_entities(
         &self,
         block_ptr: BlockPtr,
         values: Vec<(EntityType, RawEntity)>,
     ) -> Result<(), DatabaseError>;
 
+    /// Entity snapshots, subgraph errors, created datasources & the block pointer of a batch
+    /// in a single transaction, so that no row is ever written above the last saved block pointer.
+    /// A backend running without transactions writes the block pointer last instead
+    async fn commit_block(
+        &self,
+        block_ptr: BlockPtr,
+        values: Vec<(EntityType, RawEntity)>,
+        errors: Vec<SubgraphErrorRecord>,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError>;
+
     async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError>;
 
     async fn remove_snapshots(
@@ -112,6 +214,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_entity_tables().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_entity_tables().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_entity_tables().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_entity_tables().await,
             ExternDB::None => Ok(()),
         }
     }
@@ -122,6 +228,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_block_ptr_table().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_block_ptr_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_block_ptr_table().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_block_ptr_table().await,
             ExternDB::None => Ok(()),
         }
     }
@@ -132,6 +242,38 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_datasource_table().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_datasource_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_datasource_table().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_datasource_table().await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn create_subgraph_error_table(&self) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.create_subgraph_error_table().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.create_subgraph_error_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_subgraph_error_table().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_subgraph_error_table().await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn create_schema_table(&self) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.create_schema_table().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.create_schema_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_schema_table().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_schema_table().await,
             ExternDB::None => Ok(()),
         }
     }
@@ -146,6 +288,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_entity(entity_type, entity_id).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_entity(entity_type, entity_id).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entity(entity_type, entity_id).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_entity(entity_type, entity_id).await,
             ExternDB::None => Ok(None),
         }
     }
@@ -160,6 +306,110 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_entities(entity_type, ids).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_entities(entity_type, ids).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entities(entity_type, ids).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_entities(entity_type, ids).await,
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_entity_at_block(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        block_number: u64,
+    ) -> Result<Option<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => {
+                db.load_entity_at_block(entity_type, entity_id, block_number)
+                    .await
+            }
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => {
+                db.load_entity_at_block(entity_type, entity_id, block_number)
+                    .await
+            }
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => {
+                db.load_entity_at_block(entity_type, entity_id, block_number)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => {
+                db.load_entity_at_block(entity_type, entity_id, block_number)
+                    .await
+            }
+            ExternDB::None => Ok(None),
+        }
+    }
+
+    async fn scan_entities(
+        &self,
+        entity_type: &str,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.scan_entities(entity_type, block_number).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.scan_entities(entity_type, block_number).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.scan_entities(entity_type, block_number).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.scan_entities(entity_type, block_number).await,
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn scan_entities_page(
+        &self,
+        entity_type: &str,
+        block_number: u64,
+        after_id: Option<EntityID>,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => {
+                db.scan_entities_page(entity_type, block_number, after_id, limit)
+                    .await
+            }
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => {
+                db.scan_entities_page(entity_type, block_number, after_id, limit)
+                    .await
+            }
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => {
+                db.scan_entities_page(entity_type, block_number, after_id, limit)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => {
+                db.scan_entities_page(entity_type, block_number, after_id, limit)
+                    .await
+            }
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn query_entities(
+        &self,
+        entity_type: &str,
+        query: &EntityQuery,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.query_entities(entity_type, query, block_number).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.query_entities(entity_type, query, block_number).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.query_entities(entity_type, query, block_number).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.query_entities(entity_type, query, block_number).await,
             ExternDB::None => Ok(vec![]),
         }
     }
@@ -175,6 +425,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_entity(block_ptr, entity_type, data).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_entity(block_ptr, entity_type, data).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_entity(block_ptr, entity_type, data).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.create_entity(block_ptr, entity_type, data).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -185,6 +439,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.save_block_ptr(block_ptr).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.save_block_ptr(block_ptr).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_block_ptr(block_ptr).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.save_block_ptr(block_ptr).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -198,6 +456,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_recent_block_ptrs(number_of_blocks).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_recent_block_ptrs(number_of_blocks).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_recent_block_ptrs(number_of_blocks).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_recent_block_ptrs(number_of_blocks).await,
             ExternDB::None => Ok(vec![]),
         }
     }
@@ -208,17 +470,25 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.get_earliest_block_ptr().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.get_earliest_block_ptr().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.get_earliest_block_ptr().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.get_earliest_block_ptr().await,
             ExternDB::None => Ok(None),
         }
     }
 
-    async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError> {
+    async fn load_block_ptr(&self, block_number: u64) -> Result<Option<BlockPtr>, DatabaseError> {
         match self {
             #[cfg(feature = "scylla")]
-            ExternDB::Scylla(db) => db.save_datasources(datasources).await,
+            ExternDB::Scylla(db) => db.load_block_ptr(block_number).await,
             #[cfg(feature = "mongo")]
-            ExternDB::Mongo(db) => db.save_datasources(datasources).await,
-            ExternDB::None => Ok(()),
+            ExternDB::Mongo(db) => db.load_block_ptr(block_number).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_block_ptr(block_number).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_block_ptr(block_number).await,
+            ExternDB::None => Ok(None),
         }
     }
 
@@ -228,6 +498,69 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_datasources().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_datasources().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_datasources().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_datasources().await,
+            ExternDB::None => Ok(None),
+        }
+    }
+
+    async fn save_subgraph_errors(
+        &self,
+        errors: Vec<SubgraphErrorRecord>,
+    ) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.save_subgraph_errors(errors).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.save_subgraph_errors(errors).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_subgraph_errors(errors).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.save_subgraph_errors(errors).await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn load_subgraph_errors(&self) -> Result<Vec<SubgraphErrorRecord>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_subgraph_errors().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_subgraph_errors().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_subgraph_errors().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_subgraph_errors().await,
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn save_schema(&self, schema: &str) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.save_schema(schema).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.save_schema(schema).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_schema(schema).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.save_schema(schema).await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn load_schema(&self) -> Result<Option<String>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_schema().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_schema().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_schema().await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.load_schema().await,
             ExternDB::None => Ok(None),
         }
     }
@@ -242,6 +575,42 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.batch_insert_entities(block_ptr, values).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.batch_insert_entities(block_ptr, values).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.batch_insert_entities(block_ptr, values).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.batch_insert_entities(block_ptr, values).await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn commit_block(
+        &self,
+        block_ptr: BlockPtr,
+        values: Vec<(EntityType, RawEntity)>,
+        errors: Vec<SubgraphErrorRecord>,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => {
+                db.commit_block(block_ptr, values, errors, datasources)
+                    .await
+            }
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => {
+                db.commit_block(block_ptr, values, errors, datasources)
+                    .await
+            }
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => {
+                db.commit_block(block_ptr, values, errors, datasources)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => {
+                db.commit_block(block_ptr, values, errors, datasources)
+                    .await
+            }
             ExternDB::None => Ok(()),
         }
     }
@@ -252,6 +621,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.revert_from_block(from_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.revert_from_block(from_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.revert_from_block(from_block).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.revert_from_block(from_block).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -266,6 +639,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.remove_snapshots(entities, to_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.remove_snapshots(entities, to_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.remove_snapshots(entities, to_block).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.remove_snapshots(entities, to_block).await,
             ExternDB::None => Ok(0),
         }
     }
@@ -276,6 +653,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.clean_data_history(to_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.clean_data_history(to_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.clean_data_history(to_block).await,
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.clean_data_history(to_block).await,
             ExternDB::None => Ok(1),
         }
     }
@@ -286,6 +667,10 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.get_schema(),
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.get_schema(),
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.get_schema(),
+            #[cfg(feature = "sqlite")]
+            ExternDB::Sqlite(db) => db.get_schema(),
             ExternDB::None => Schemas::default(),
         }
     }
@@ -6,6 +6,8 @@ use crate::common::EntityType;
 use crate::common::FieldKind;
 use crate::common::RawEntity;
 use crate::common::Schemas;
+use crate::common::SubgraphErrorRecord;
+use crate::database::EntityQuery;
 use crate::debug;
 use crate::error;
 use crate::errors::DatabaseError;
@@ -19,6 +21,7 @@ use async_trait::async_trait;
 use futures_util::future::try_join_all;
 use scylla::_macro_internal::CqlValue;
 use scylla::batch::Batch;
+use scylla::batch::BatchType;
 use scylla::transport::session::Session;
 use scylla::QueryResult;
 use scylla::SessionBuilder;
@@ -50,7 +53,7 @@ pub enum BlockPtrFilter {
     // Gt(u64),
     Gte(u64),
     Lt(u64),
-    // Lte(u64),
+    Lte(u64),
 }
 
 impl Display for BlockPtrFilter {
@@ -58,10 +61,47 @@ impl Display for BlockPtrFilter {
         match self {
             Self::Gte(block) => write!(f, "__block_ptr__ >= {block}"),
             Self::Lt(block) => write!(f, "__block_ptr__ < {block}"),
+            Self::Lte(block) => write!(f, "__block_ptr__ <= {block}"),
         }
     }
 }
 
+/// Target size of a single logged batch, well under the 50KB default of `batch_size_fail_threshold_in_kb`
+const BATCH_SIZE_LIMIT: usize = 32 * 1024;
+
+fn estimated_size(value: &CqlValue) -> usize {
+    match value {
+        CqlValue::Text(text) => text.len(),
+        CqlValue::Blob(blob) => blob.len(),
+        CqlValue::List(list) => list.iter().map(estimated_size).sum(),
+        _ => 16,
+    }
+}
+
+/// Split statements into batches of roughly `size_limit` bytes, keeping their order.
+/// A statement larger than the limit still gets a batch of its own
+fn chunk_statements(
+    statements: Vec<(String, Vec<CqlValue>)>,
+    size_limit: usize,
+) -> Vec<Vec<(String, Vec<CqlValue>)>> {
+    let mut chunks = vec![];
+    let mut chunk = vec![];
+    let mut chunk_size = 0;
+    for (query, values) in statements {
+        let size = query.len() + values.iter().map(estimated_size).sum::<usize>();
+        if !chunk.is_empty() && chunk_size + size > size_limit {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_size = 0;
+        }
+        chunk_size += size;
+        chunk.push((query, values));
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
 pub struct Scylladb {
     session: Arc<Session>,
     keyspace: String,
@@ -84,9 +124,23 @@ impl Scylladb {
         info!(ExternDB, "Entities table created OK"; entities => format!("{:?}", entities));
         this.create_block_ptr_table().await?;
         info!(ExternDB, "Block_Ptr table created OK");
+        this.create_datasource_table().await?;
+        info!(ExternDB, "Datasources table created OK");
+        this.create_subgraph_error_table().await?;
+        info!(ExternDB, "Subgraph-errors table created OK");
+        this.create_schema_table().await?;
+        info!(ExternDB, "Schema table created OK");
         Ok(this)
     }
 
+    pub fn read_namespace(&self, keyspace: &str, schemas: Schemas) -> Self {
+        Self {
+            session: self.session.clone(),
+            keyspace: keyspace.to_owned(),
+            schemas,
+        }
+    }
+
     async fn create_keyspace(&self) -> Result<(), DatabaseError> {
         let q = format!(
             r#"
@@ -378,7 +432,52 @@ impl ExternDBTrait for Scylladb {
     }
 
     async fn create_datasource_table(&self) -> Result<(), DatabaseError> {
-        todo!()
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {}.datasources (
+                sgd text,
+                created_at_block bigint,
+                name text,
+                address text,
+                datasource text,
+                PRIMARY KEY (sgd, created_at_block, name, address)
+            ) WITH CLUSTERING ORDER BY (created_at_block ASC)
+            "#,
+            self.keyspace
+        );
+        self.session.query(query, ()).await?;
+        Ok(())
+    }
+
+    async fn create_subgraph_error_table(&self) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {}.subgraph_errors (
+                sgd text,
+                block_number bigint,
+                handler text,
+                error text,
+                PRIMARY KEY (sgd, block_number, handler)
+            ) WITH CLUSTERING ORDER BY (block_number ASC)
+            "#,
+            self.keyspace
+        );
+        self.session.query(query, ()).await?;
+        Ok(())
+    }
+
+    async fn create_schema_table(&self) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {}.subgraph_schema (
+                sgd text PRIMARY KEY,
+                schema text
+            )
+            "#,
+            self.keyspace
+        );
+        self.session.query(query, ()).await?;
+        Ok(())
     }
 
     async fn load_entity(
@@ -439,6 +538,96 @@ impl ExternDBTrait for Scylladb {
         Ok(self.handle_entity_query_result(entity_type, entity_query_result, false))
     }
 
+    async fn load_entity_at_block(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        block_number: u64,
+    ) -> Result<Option<RawEntity>, DatabaseError> {
+        let query = format!(
+            r#"
+            SELECT * from {}."{}"
+            WHERE id = ? AND {}
+            ORDER BY __block_ptr__ DESC
+            LIMIT 1
+            "#,
+            self.keyspace,
+            entity_type,
+            BlockPtrFilter::Lte(block_number)
+        );
+        let result = self.session.query(query, (entity_id,)).await?;
+        let entity = self
+            .handle_entity_query_result(entity_type, result, false)
+            .first()
+            .cloned();
+        Ok(entity)
+    }
+
+    async fn scan_entities(
+        &self,
+        entity_type: &str,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let block_filter = block_number
+            .map(|number| format!("WHERE {}", BlockPtrFilter::Lte(number)))
+            .unwrap_or_default();
+        // Rows are clustered by `__block_ptr__ DESC`, so the first row of each
+        // partition is the latest snapshot of that entity
+        let query = format!(
+            r#"
+            SELECT * from {}."{}"
+            {block_filter}
+            PER PARTITION LIMIT 1
+            ALLOW FILTERING
+            "#,
+            self.keyspace, entity_type
+        );
+        let result = self.session.query(query, ()).await?;
+        Ok(self.handle_entity_query_result(entity_type, result, false))
+    }
+
+    /// Partitions are walked in token order, the id of the last one resumes the next page
+    async fn scan_entities_page(
+        &self,
+        entity_type: &str,
+        block_number: u64,
+        after_id: Option<EntityID>,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let token_filter = after_id
+            .as_ref()
+            .map(|_| "token(id) > token(?) AND")
+            .unwrap_or_default();
+        let query = format!(
+            r#"
+            SELECT * from {}."{}"
+            WHERE {token_filter} {}
+            PER PARTITION LIMIT 1
+            LIMIT {limit}
+            ALLOW FILTERING
+            "#,
+            self.keyspace,
+            entity_type,
+            BlockPtrFilter::Lte(block_number)
+        );
+        let result = match after_id {
+            Some(after_id) => self.session.query(query, (after_id,)).await?,
+            None => self.session.query(query, ()).await?,
+        };
+        Ok(self.handle_entity_query_result(entity_type, result, true))
+    }
+
+    /// Scylla only filters on key columns, so the query runs in memory over the latest snapshots
+    async fn query_entities(
+        &self,
+        entity_type: &str,
+        query: &EntityQuery,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let entities = self.scan_entities(entity_type, block_number).await?;
+        Ok(query.apply(entities))
+    }
+
     async fn create_entity(
         &self,
         block_ptr: BlockPtr,
@@ -533,12 +722,111 @@ WHERE sgd = ? AND block_number = {}"#,
         return Ok(serde_json::from_str(&text).ok());
     }
 
-    async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError> {
-        todo!()
+    async fn load_block_ptr(&self, block_number: u64) -> Result<Option<BlockPtr>, DatabaseError> {
+        let query = format!(
+            r#"
+SELECT JSON block_number as number, block_hash as hash, parent_hash
+FROM {}.block_ptr
+WHERE sgd = ? AND block_number = ?"#,
+            self.keyspace
+        );
+        let result = self
+            .session
+            .query(query, ("dfr".to_string(), block_number as i64))
+            .await?;
+        let block_ptr = result.first_row().ok().and_then(|row| {
+            let text = row.columns.first().cloned()??.into_string()?;
+            serde_json::from_str::<BlockPtr>(&text).ok()
+        });
+        Ok(block_ptr)
     }
 
     async fn load_datasources(&self) -> Result<Option<Vec<Datasource>>, DatabaseError> {
-        todo!()
+        let query = format!(
+            "SELECT datasource FROM {}.datasources WHERE sgd = ?",
+            self.keyspace
+        );
+        let result = self.session.query(query, vec!["dfr".to_string()]).await?;
+        let datasources = result
+            .rows()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|r| {
+                let text = r.columns.first().cloned()??.into_string()?;
+                serde_json::from_str::<Datasource>(&text).ok()
+            })
+            .collect::<Vec<_>>();
+
+        if datasources.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(datasources))
+    }
+
+    async fn save_subgraph_errors(
+        &self,
+        errors: Vec<SubgraphErrorRecord>,
+    ) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            INSERT INTO {}.subgraph_errors (sgd, block_number, handler, error) VALUES ('dfr', ?, ?, ?)"#,
+            self.keyspace
+        );
+
+        for error in errors {
+            let record = serde_json::to_string(&error)
+                .map_err(|e| DatabaseError::Plain(format!("Invalid subgraph error: {:?}", e)))?;
+            self.session
+                .query(
+                    query.clone(),
+                    (error.block_ptr.number as i64, error.handler, record),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn load_subgraph_errors(&self) -> Result<Vec<SubgraphErrorRecord>, DatabaseError> {
+        let query = format!(
+            "SELECT error FROM {}.subgraph_errors WHERE sgd = ?",
+            self.keyspace
+        );
+        let result = self.session.query(query, vec!["dfr".to_string()]).await?;
+        let errors = result
+            .rows()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|r| {
+                let text = r.columns.first().cloned()??.into_string()?;
+                serde_json::from_str::<SubgraphErrorRecord>(&text).ok()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(errors)
+    }
+
+    async fn save_schema(&self, schema: &str) -> Result<(), DatabaseError> {
+        let query = format!(
+            "INSERT INTO {}.subgraph_schema (sgd, schema) VALUES ('dfr', ?)",
+            self.keyspace
+        );
+        self.session.query(query, (schema,)).await?;
+        Ok(())
+    }
+
+    async fn load_schema(&self) -> Result<Option<String>, DatabaseError> {
+        let query = format!(
+            "SELECT schema FROM {}.subgraph_schema WHERE sgd = ?",
+            self.keyspace
+        );
+        let result = self.session.query(query, vec!["dfr".to_string()]).await?;
+        let schema = result
+            .first_row()
+            .ok()
+            .and_then(|row| row.columns.first().cloned()??.into_string());
+        Ok(schema)
     }
 
     async fn batch_insert_entities(
@@ -597,6 +885,104 @@ WHERE sgd = ? AND block_number = {}"#,
         Ok(())
     }
 
+    /// Statements are split into logged batches that stay under the server's
+    /// `batch_size_fail_threshold_in_kb`, the block pointer goes into the last one.
+    /// A block interrupted halfway has no block pointer, so its rows are reverted as orphans on restart
+    async fn commit_block(
+        &self,
+        block_ptr: BlockPtr,
+        values: Vec<(String, RawEntity)>,
+        errors: Vec<SubgraphErrorRecord>,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError> {
+        let mut statements: Vec<(String, Vec<CqlValue>)> = vec![];
+        let count = values.len();
+
+        for (entity_type, data) in values {
+            if data.get("__is_deleted__").is_none() {
+                error!(ExternDB,
+                       "Missing is_deleted field";
+                       entity_type => entity_type,
+                       entity_data => format!("{:?}", data),
+                       __block_ptr__ => block_ptr.number,
+                       block_ptr_hash => block_ptr.hash
+                );
+                return Err(DatabaseError::MissingField("__is_deleted__".to_string()));
+            }
+
+            statements.push(self.generate_insert_query(&entity_type, data, block_ptr.clone()));
+        }
+
+        let query = format!(
+            r#"
+            INSERT INTO {}.subgraph_errors (sgd, block_number, handler, error) VALUES ('dfr', ?, ?, ?)"#,
+            self.keyspace
+        );
+        for error in errors {
+            let record = serde_json::to_string(&error)
+                .map_err(|e| DatabaseError::Plain(format!("Invalid subgraph error: {:?}", e)))?;
+            statements.push((
+                query.clone(),
+                vec![
+                    CqlValue::BigInt(error.block_ptr.number as i64),
+                    CqlValue::Text(error.handler),
+                    CqlValue::Text(record),
+                ],
+            ));
+        }
+
+        let query = format!(
+            r#"
+            INSERT INTO {}.datasources (sgd, created_at_block, name, address, datasource) VALUES ('dfr', ?, ?, ?, ?)"#,
+            self.keyspace
+        );
+        for ds in datasources {
+            let datasource = serde_json::to_string(&ds)
+                .map_err(|e| DatabaseError::Plain(format!("Invalid datasource: {:?}", e)))?;
+            statements.push((
+                query.clone(),
+                vec![
+                    CqlValue::BigInt(ds.source.startBlock.unwrap_or(0) as i64),
+                    CqlValue::Text(ds.name),
+                    CqlValue::Text(ds.source.address.unwrap_or_default()),
+                    CqlValue::Text(datasource),
+                ],
+            ));
+        }
+
+        let query = format!(
+            r#"
+            INSERT INTO {}.block_ptr (sgd, block_number, block_hash, parent_hash) VALUES ('dfr', ?, ?, ?)"#,
+            self.keyspace
+        );
+        statements.push((
+            query,
+            vec![
+                CqlValue::BigInt(block_ptr.number as i64),
+                CqlValue::Text(block_ptr.hash.clone()),
+                CqlValue::Text(block_ptr.parent_hash.clone()),
+            ],
+        ));
+
+        for chunk in chunk_statements(statements, BATCH_SIZE_LIMIT) {
+            let mut batch_queries = Batch::new(BatchType::Logged);
+            let mut batch_values: Vec<Vec<CqlValue>> = vec![];
+            for (query, values) in chunk {
+                batch_queries.append_statement(query.as_str());
+                batch_values.push(values);
+            }
+            let st = self.session.prepare_batch(&batch_queries).await?;
+            self.session.batch(&st, batch_values).await?;
+        }
+        info!(
+            Scylladb,
+            "Commit result";
+            statements => format!("{:?} statements", count),
+            block => block_ptr.number
+        );
+        Ok(())
+    }
+
     async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError> {
         let entity_names = self.schemas.get_entity_names();
         let mut batch_queries: Batch = Batch::default();
@@ -618,6 +1004,22 @@ WHERE sgd = ? AND block_number = {}"#,
         }
         let st_batch = self.session.prepare_batch(&batch_queries).await?;
         self.session.batch(&st_batch, batch_values).await?;
+
+        let query = format!(
+            "DELETE FROM {}.datasources WHERE sgd = ? AND created_at_block >= ?",
+            self.keyspace
+        );
+        self.session
+            .query(query, ("dfr".to_string(), from_block as i64))
+            .await?;
+
+        let query = format!(
+            "DELETE FROM {}.subgraph_errors WHERE sgd = ? AND block_number >= ?",
+            self.keyspace
+        );
+        self.session
+            .query(query, ("dfr".to_string(), from_block as i64))
+            .await?;
         Ok(())
     }
 
@@ -681,3 +1083,28 @@ WHERE sgd = ? AND block_number = {}"#,
         self.schemas.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_statements() {
+        let statement = |text: &str| ("INSERT".to_string(), vec![CqlValue::Text(text.to_string())]);
+        let statements = vec![
+            statement(&"a".repeat(40)),
+            statement(&"b".repeat(40)),
+            statement(&"c".repeat(200)),
+            statement("block_ptr"),
+        ];
+
+        let chunks = chunk_statements(statements, 100);
+        let sizes = chunks.iter().map(|c| c.len()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![2, 1, 1]);
+        assert_eq!(
+            chunks.last().unwrap()[0].1,
+            vec![CqlValue::Text("block_ptr".to_string())]
+        );
+        assert!(chunk_statements(vec![], 100).is_empty());
+    }
+}
@@ -0,0 +1,1416 @@
+use super::ExternDBTrait;
+use crate::common::BlockPtr;
+use crate::common::Datasource;
+use crate::common::EntityID;
+use crate::common::EntityType;
+use crate::common::FieldKind;
+use crate::common::RawEntity;
+use crate::common::Schemas;
+use crate::common::SubgraphErrorRecord;
+use crate::database::EntityFilter;
+use crate::database::EntityQuery;
+use crate::database::Operator;
+use crate::error;
+use crate::errors::DatabaseError;
+use crate::info;
+use crate::runtime::asc::native_types::store::Bytes;
+use crate::runtime::asc::native_types::store::StoreValueKind;
+use crate::runtime::asc::native_types::store::Value;
+use crate::runtime::bignumber::bigdecimal::BigDecimal;
+use crate::runtime::bignumber::bigint::BigInt;
+use async_trait::async_trait;
+use rusqlite::params;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use rusqlite::OptionalExtension;
+use rusqlite::Params;
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// Lists have no native column type in SQLite, so they are kept as JSON text
+fn store_value_to_json(value: Value) -> JsonValue {
+    match value {
+        Value::String(s) => JsonValue::from(s),
+        Value::Int(n) => JsonValue::from(n),
+        Value::Int8(n) => JsonValue::from(n),
+        Value::BigInt(n) => JsonValue::from(n.to_string()),
+        Value::BigDecimal(n) => JsonValue::from(n.to_string()),
+        Value::Bool(b) => JsonValue::from(b),
+        Value::Bytes(bytes) => JsonValue::from(hex::encode(bytes.as_slice())),
+        Value::List(list) => JsonValue::Array(list.into_iter().map(store_value_to_json).collect()),
+        Value::Null => JsonValue::Null,
+    }
+}
+
+fn json_to_store_value(
+    field: &str,
+    kind: StoreValueKind,
+    value: JsonValue,
+) -> Result<Value, DatabaseError> {
+    let invalid = || DatabaseError::InvalidValue(field.to_owned());
+    let value = match (kind, value) {
+        (_, JsonValue::Null) => Value::Null,
+        (StoreValueKind::String, JsonValue::String(s)) => Value::String(s),
+        (StoreValueKind::Int, JsonValue::Number(n)) => {
+            let n = n.as_i64().and_then(|n| i32::try_from(n).ok());
+            Value::Int(n.ok_or_else(invalid)?)
+        }
+        (StoreValueKind::Int8, JsonValue::Number(n)) => {
+            Value::Int8(n.as_i64().ok_or_else(invalid)?)
+        }
+        (StoreValueKind::BigInt, JsonValue::String(s)) => {
+            Value::BigInt(BigInt::from_str(&s).map_err(|_| invalid())?)
+        }
+        (StoreValueKind::BigDecimal, JsonValue::String(s)) => {
+            Value::BigDecimal(BigDecimal::from_str(&s).map_err(|_| invalid())?)
+        }
+        (StoreValueKind::Bool, JsonValue::Bool(b)) => Value::Bool(b),
+        (StoreValueKind::Bytes, JsonValue::String(s)) => {
+            Value::Bytes(Bytes::from(hex::decode(s).map_err(|_| invalid())?))
+        }
+        // Includes nested lists, whose inner kind is not part of the schema
+        _ => return Err(invalid()),
+    };
+    Ok(value)
+}
+
+fn store_value_to_sql(value: Value) -> SqlValue {
+    match value {
+        Value::String(s) => SqlValue::Text(s),
+        Value::Int(n) => SqlValue::Integer(n as i64),
+        Value::Int8(n) => SqlValue::Integer(n),
+        Value::BigInt(n) => SqlValue::Text(n.to_string()),
+        Value::BigDecimal(n) => SqlValue::Text(n.to_string()),
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Bytes(bytes) => SqlValue::Blob(bytes.as_slice().to_vec()),
+        Value::List(list) => SqlValue::Text(store_value_to_json(Value::List(list)).to_string()),
+        Value::Null => SqlValue::Null,
+    }
+}
+
+/// Columns that do not hold what the schema says, eg: written by another version
+/// of the subgraph, fail the read instead of the runtime
+fn sql_to_store_value(
+    field: &str,
+    field_kind: &FieldKind,
+    value: ValueRef,
+) -> Result<Value, DatabaseError> {
+    if let ValueRef::Null = value {
+        return Ok(match field_kind.kind {
+            StoreValueKind::Array => Value::List(vec![]),
+            _ => Value::Null,
+        });
+    }
+
+    let invalid = || DatabaseError::InvalidValue(field.to_owned());
+    let as_str = || value.as_str().map_err(|_| invalid());
+    let value = match field_kind.kind {
+        StoreValueKind::Int => {
+            let n = value.as_i64().map_err(|_| invalid())?;
+            Value::Int(i32::try_from(n).map_err(|_| invalid())?)
+        }
+        StoreValueKind::Int8 => Value::Int8(value.as_i64().map_err(|_| invalid())?),
+        StoreValueKind::Bool => Value::Bool(value.as_i64().map_err(|_| invalid())? != 0),
+        StoreValueKind::String => Value::String(as_str()?.to_owned()),
+        StoreValueKind::BigInt => {
+            Value::BigInt(BigInt::from_str(as_str()?).map_err(|_| invalid())?)
+        }
+        StoreValueKind::BigDecimal => {
+            Value::BigDecimal(BigDecimal::from_str(as_str()?).map_err(|_| invalid())?)
+        }
+        StoreValueKind::Bytes => Value::Bytes(Bytes::from(
+            value.as_blob().map_err(|_| invalid())?.to_vec(),
+        )),
+        StoreValueKind::Array => {
+            let inner_kind = field_kind.list_inner_kind.ok_or_else(invalid)?;
+            let values =
+                serde_json::from_str::<Vec<JsonValue>>(as_str()?).map_err(|_| invalid())?;
+            Value::List(
+                values
+                    .into_iter()
+                    .map(|inner_val| json_to_store_value(field, inner_kind, inner_val))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        }
+        StoreValueKind::Null => Value::Null,
+    };
+    Ok(value)
+}
+
+/// Embedded, single-file database. Use `:memory:` as path for a throw-away store.
+/// rusqlite is synchronous, so queries run on tokio's blocking pool
+#[derive(Clone)]
+pub struct Sqlite {
+    conn: Arc<Mutex<Connection>>,
+    schemas: Arc<Schemas>,
+}
+
+impl Sqlite {
+    pub fn new(path: &str, schemas: Schemas) -> Result<Self, DatabaseError> {
+        info!(ExternDB, "Open sqlite database"; path => path);
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+
+        let entities = schemas.get_entity_names();
+        let this = Self {
+            conn: Arc::new(Mutex::new(conn)),
+            schemas: Arc::new(schemas),
+        };
+        this.create_tables()?;
+        info!(ExternDB, "Entities table created OK"; entities => format!("{:?}", entities));
+        Ok(this)
+    }
+
+    /// Grafting reads its base from another file, which must not be created nor altered
+    pub fn open_read_only(path: &str, schemas: Schemas) -> Result<Self, DatabaseError> {
+        info!(ExternDB, "Open sqlite database read-only"; path => path);
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            schemas: Arc::new(schemas),
+        })
+    }
+
+    fn conn(&self) -> Result<MutexGuard<Connection>, DatabaseError> {
+        self.conn.lock().map_err(|_| DatabaseError::MutexLockFailed)
+    }
+
+    /// Run `query` off the async workers, which a slow write would otherwise stall
+    async fn blocking<T, F>(&self, query: F) -> Result<T, DatabaseError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Sqlite) -> Result<T, DatabaseError> + Send + 'static,
+    {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || query(&this))
+            .await
+            .map_err(|e| DatabaseError::Plain(format!("Sqlite query task failed: {:?}", e)))?
+    }
+
+    fn create_tables(&self) -> Result<(), DatabaseError> {
+        let conn = self.conn()?;
+
+        for entity_type in self.schemas.get_entity_names() {
+            let schema = self.schemas.get_schema(&entity_type);
+            let mut column_definitions = vec![];
+            for (column_name, field_kind) in schema.iter() {
+                let column_type = Sqlite::store_kind_to_db_type(column_name, field_kind)?;
+                column_definitions.push(format!("\"{column_name}\" {column_type}"));
+            }
+            column_definitions.push("PRIMARY KEY (id, __block_ptr__)".to_string());
+
+            let query = format!(
+                "CREATE TABLE IF NOT EXISTS \"{}\" (\n{}\n)",
+                entity_type,
+                column_definitions.join(",\n")
+            );
+            conn.execute_batch(&query)?;
+        }
+
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS block_ptr (
+                block_number INTEGER PRIMARY KEY,
+                block_hash TEXT NOT NULL,
+                parent_hash TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS datasources (
+                name TEXT NOT NULL,
+                address TEXT,
+                created_at_block INTEGER,
+                datasource TEXT NOT NULL
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS datasources_unique_idx ON datasources
+                (name, IFNULL(address, ''), IFNULL(created_at_block, -1));
+            CREATE TABLE IF NOT EXISTS subgraph_errors (
+                block_number INTEGER NOT NULL,
+                block_hash TEXT NOT NULL,
+                parent_hash TEXT NOT NULL,
+                handler TEXT NOT NULL,
+                message TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS subgraph_schema (
+                id INTEGER PRIMARY KEY CHECK (id = 0),
+                schema TEXT NOT NULL
+            );
+            "#,
+        )?;
+        Ok(())
+    }
+
+    /// Fields without a type are rejected when the tables are created
+    fn store_kind_to_db_type(
+        column_name: &str,
+        field_kind: &FieldKind,
+    ) -> Result<&'static str, DatabaseError> {
+        let column_type = match field_kind.kind {
+            StoreValueKind::Int | StoreValueKind::Int8 | StoreValueKind::Bool => "INTEGER",
+            StoreValueKind::String
+            | StoreValueKind::BigInt
+            | StoreValueKind::BigDecimal
+            | StoreValueKind::Array => "TEXT",
+            StoreValueKind::Bytes => "BLOB",
+            StoreValueKind::Null => {
+                return Err(DatabaseError::InvalidValue(column_name.to_owned()))
+            }
+        };
+        Ok(column_type)
+    }
+
+    fn select_entities<P: Params>(
+        &self,
+        conn: &Connection,
+        entity_type: &str,
+        query: &str,
+        params: P,
+        include_deleted: bool,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let mut stmt = conn.prepare(query)?;
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(|name| (name.to_owned(), self.schemas.get_field(entity_type, name)))
+            .collect::<Vec<_>>();
+
+        let mut rows = stmt.query(params)?;
+        let mut entities = vec![];
+        while let Some(row) = rows.next()? {
+            let mut entity = RawEntity::new();
+            for (idx, (name, field_kind)) in columns.iter().enumerate() {
+                let value = sql_to_store_value(name, field_kind, row.get_ref(idx)?)?;
+                entity.insert(name.to_owned(), value);
+            }
+            if include_deleted || entity.get("__is_deleted__") != Some(&Value::Bool(true)) {
+                entities.push(entity);
+            }
+        }
+
+        Ok(entities)
+    }
+
+    /// Selects the latest snapshot of each entity at or before `?1`
+    fn latest_snapshot_query(entity_type: &str, id_filter: &str) -> String {
+        format!(
+            r#"
+            SELECT * FROM "{entity_type}" AS snapshot
+            WHERE {id_filter} snapshot.__block_ptr__ = (
+                SELECT MAX(latest.__block_ptr__) FROM "{entity_type}" AS latest
+                WHERE latest.id = snapshot.id AND latest.__block_ptr__ <= ?1
+            )
+            ORDER BY snapshot.id"#
+        )
+    }
+
+    /// Columns SQLite compares the same way as `EntityFilter::matches`. Big numbers are
+    /// kept as text so only BigInt equality is exact, lists are JSON text
+    fn native_kinds(&self, entity_type: &str, field: &str) -> (bool, bool) {
+        let kind = self.schemas.get_field(entity_type, field).kind;
+        let ordered = matches!(
+            kind,
+            StoreValueKind::String
+                | StoreValueKind::Int
+                | StoreValueKind::Int8
+                | StoreValueKind::Bool
+                | StoreValueKind::Bytes
+        );
+        (ordered || kind == StoreValueKind::BigInt, ordered)
+    }
+
+    /// Translates a filter into a condition on the latest snapshots. On `None`,
+    /// params pushed for the failed filter must be dropped by the caller
+    fn filter_to_sql(
+        &self,
+        entity_type: &str,
+        filter: &EntityFilter,
+        params: &mut Vec<SqlValue>,
+    ) -> Option<String> {
+        let (field, op, value) = match filter {
+            EntityFilter::And(filters) | EntityFilter::Or(filters) => {
+                let is_and = matches!(filter, EntityFilter::And(_));
+                if filters.is_empty() {
+                    return Some(if is_and { "1" } else { "0" }.to_owned());
+                }
+                let conditions = filters
+                    .iter()
+                    .map(|f| self.filter_to_sql(entity_type, f, params))
+                    .collect::<Option<Vec<_>>>()?;
+                let separator = if is_and { " AND " } else { " OR " };
+                return Some(format!("({})", conditions.join(separator)));
+            }
+            EntityFilter::Field { field, op, value } => (field, op, value),
+        };
+
+        let (equal, ordered) = self.native_kinds(entity_type, field);
+        let mut param = |value: &Value| {
+            params.push(store_value_to_sql(value.clone()));
+            format!("?{}", params.len())
+        };
+        let condition = match (op, value) {
+            (Operator::Equal, value) if equal => format!(r#""{field}" IS {}"#, param(value)),
+            (Operator::Not, value) if equal => format!(r#""{field}" IS NOT {}"#, param(value)),
+            (Operator::In | Operator::NotIn, Value::List(items)) if equal => {
+                let has_null = items.contains(&Value::Null);
+                let placeholders = items
+                    .iter()
+                    .filter(|item| **item != Value::Null)
+                    .map(&mut param)
+                    .collect::<Vec<_>>();
+                let listed = if placeholders.is_empty() {
+                    "0".to_owned()
+                } else {
+                    format!(r#""{field}" IN ({})"#, placeholders.join(","))
+                };
+                match (op, has_null) {
+                    (Operator::In, true) => format!(r#"({listed} OR "{field}" IS NULL)"#),
+                    (Operator::In, false) => listed,
+                    (_, true) => format!(r#"("{field}" IS NOT NULL AND NOT {listed})"#),
+                    (_, false) => format!(r#"("{field}" IS NULL OR NOT {listed})"#),
+                }
+            }
+            (Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte, value)
+                if ordered && *value != Value::Null =>
+            {
+                let operator = match op {
+                    Operator::Gt => ">",
+                    Operator::Gte => ">=",
+                    Operator::Lt => "<",
+                    _ => "<=",
+                };
+                format!(r#""{field}" {operator} {}"#, param(value))
+            }
+            _ => return None,
+        };
+        Some(condition)
+    }
+
+    fn generate_insert_query(
+        &self,
+        entity_type: &str,
+        mut data: RawEntity,
+        block_ptr: &BlockPtr,
+    ) -> (String, Vec<SqlValue>) {
+        let schema = self.schemas.get_schema(entity_type);
+        data.insert(
+            "__block_ptr__".to_string(),
+            Value::Int8(block_ptr.number as i64),
+        );
+
+        let mut fields = vec![];
+        let mut values = vec![];
+
+        for (field_name, field_kind) in schema.iter() {
+            let value = match data.remove(field_name) {
+                Some(value) => store_value_to_sql(value),
+                None if field_kind.kind == StoreValueKind::Array => {
+                    SqlValue::Text("[]".to_string())
+                }
+                None => SqlValue::Null,
+            };
+            values.push(value);
+            fields.push(format!("\"{}\"", field_name));
+        }
+
+        let query = format!(
+            "INSERT INTO \"{}\" ({}) VALUES ({})",
+            entity_type,
+            fields.join(","),
+            vec!["?"; fields.len()].join(",")
+        );
+
+        (query, values)
+    }
+
+    fn insert_entities(
+        &self,
+        conn: &Connection,
+        block_ptr: &BlockPtr,
+        values: Vec<(EntityType, RawEntity)>,
+    ) -> Result<(), DatabaseError> {
+        for (entity_type, data) in values {
+            if data.get("__is_deleted__").is_none() {
+                error!(ExternDB,
+                       "Missing is_deleted field";
+                       entity_type => entity_type,
+                       entity_data => format!("{:?}", data),
+                       __block_ptr__ => block_ptr.number,
+                       block_ptr_hash => block_ptr.hash
+                );
+                return Err(DatabaseError::MissingField("__is_deleted__".to_string()));
+            }
+
+            let (query, values) = self.generate_insert_query(&entity_type, data, block_ptr);
+            conn.prepare_cached(&query)?
+                .execute(params_from_iter(values))?;
+        }
+        Ok(())
+    }
+
+    fn insert_subgraph_errors(
+        conn: &Connection,
+        errors: Vec<SubgraphErrorRecord>,
+    ) -> Result<(), DatabaseError> {
+        for error in errors {
+            conn.execute(
+                r#"
+                INSERT INTO subgraph_errors (block_number, block_hash, parent_hash, handler, message)
+                VALUES (?1, ?2, ?3, ?4, ?5)"#,
+                params![
+                    error.block_ptr.number as i64,
+                    error.block_ptr.hash,
+                    error.block_ptr.parent_hash,
+                    error.handler,
+                    error.message
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn insert_datasources(
+        conn: &Connection,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError> {
+        for ds in datasources {
+            let datasource = serde_json::to_string(&ds)
+                .map_err(|e| DatabaseError::Plain(format!("Invalid datasource: {:?}", e)))?;
+            conn.execute(
+                r#"
+                INSERT OR IGNORE INTO datasources (name, address, created_at_block, datasource)
+                VALUES (?1, ?2, ?3, ?4)"#,
+                params![
+                    ds.name,
+                    ds.source.address,
+                    ds.source.startBlock.map(|n| n as i64),
+                    datasource
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn insert_block_ptr(conn: &Connection, block_ptr: &BlockPtr) -> Result<(), DatabaseError> {
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO block_ptr (block_number, block_hash, parent_hash)
+            VALUES (?1, ?2, ?3)"#,
+            params![
+                block_ptr.number as i64,
+                block_ptr.hash,
+                block_ptr.parent_hash
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn read_block_ptr(row: &rusqlite::Row) -> rusqlite::Result<BlockPtr> {
+        Ok(BlockPtr {
+            number: row.get::<_, i64>(0)? as u64,
+            hash: row.get(1)?,
+            parent_hash: row.get(2)?,
+        })
+    }
+}
+
+#[async_trait]
+impl ExternDBTrait for Sqlite {
+    async fn create_entity_tables(&self) -> Result<(), DatabaseError> {
+        self.blocking(|db| db.create_tables()).await
+    }
+
+    async fn create_block_ptr_table(&self) -> Result<(), DatabaseError> {
+        self.blocking(|db| db.create_tables()).await
+    }
+
+    async fn create_datasource_table(&self) -> Result<(), DatabaseError> {
+        self.blocking(|db| db.create_tables()).await
+    }
+
+    async fn create_subgraph_error_table(&self) -> Result<(), DatabaseError> {
+        self.blocking(|db| db.create_tables()).await
+    }
+
+    async fn create_schema_table(&self) -> Result<(), DatabaseError> {
+        self.blocking(|db| db.create_tables()).await
+    }
+
+    async fn load_entity(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<Option<RawEntity>, DatabaseError> {
+        self.load_entity_at_block(entity_type, entity_id, i64::MAX as u64)
+            .await
+    }
+
+    async fn load_entities(
+        &self,
+        entity_type: &str,
+        ids: Vec<String>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // `?1` is taken by the block number, ids are bound from `?2` onward
+        let placeholders = (2..ids.len() + 2)
+            .map(|idx| format!("?{idx}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        let query = Sqlite::latest_snapshot_query(
+            entity_type,
+            &format!("snapshot.id IN ({placeholders}) AND"),
+        );
+        let params = std::iter::once(SqlValue::Integer(i64::MAX))
+            .chain(ids.into_iter().map(SqlValue::Text))
+            .collect::<Vec<_>>();
+        let entity_type = entity_type.to_owned();
+        self.blocking(move |db| {
+            let conn = db.conn()?;
+            db.select_entities(&conn, &entity_type, &query, params_from_iter(params), false)
+        })
+        .await
+    }
+
+    async fn load_entity_at_block(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        block_number: u64,
+    ) -> Result<Option<RawEntity>, DatabaseError> {
+        let query = format!(
+            r#"
+            SELECT * FROM "{}"
+            WHERE id = ?1 AND __block_ptr__ <= ?2
+            ORDER BY __block_ptr__ DESC
+            LIMIT 1"#,
+            entity_type
+        );
+        let entity_type = entity_type.to_owned();
+        let entity_id = entity_id.to_owned();
+        self.blocking(move |db| {
+            let conn = db.conn()?;
+            let entity = db
+                .select_entities(
+                    &conn,
+                    &entity_type,
+                    &query,
+                    params![entity_id, block_number as i64],
+                    false,
+                )?
+                .first()
+                .cloned();
+            Ok(entity)
+        })
+        .await
+    }
+
+    async fn scan_entities(
+        &self,
+        entity_type: &str,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let block_number = block_number.map(|n| n as i64).unwrap_or(i64::MAX);
+        let query = Sqlite::latest_snapshot_query(entity_type, "");
+        let entity_type = entity_type.to_owned();
+        self.blocking(move |db| {
+            let conn = db.conn()?;
+            db.select_entities(&conn, &entity_type, &query, params![block_number], false)
+        })
+        .await
+    }
+
+    async fn scan_entities_page(
+        &self,
+        entity_type: &str,
+        block_number: u64,
+        after_id: Option<EntityID>,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let query = format!(
+            "{} LIMIT ?3",
+            Sqlite::latest_snapshot_query(entity_type, "(?2 IS NULL OR snapshot.id > ?2) AND")
+        );
+        let entity_type = entity_type.to_owned();
+        self.blocking(move |db| {
+            let conn = db.conn()?;
+            let params = params![block_number as i64, after_id, limit as i64];
+            db.select_entities(&conn, &entity_type, &query, params, true)
+        })
+        .await
+    }
+
+    async fn query_entities(
+        &self,
+        entity_type: &str,
+        query: &EntityQuery,
+        block_number: Option<u64>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let block_number = block_number.map(|n| n as i64).unwrap_or(i64::MAX);
+        let mut params = vec![SqlValue::Integer(block_number)];
+        let mut conditions = vec!["__is_deleted__ IS NOT 1".to_owned()];
+        let conjuncts = query.conjuncts();
+
+        for filter in conjuncts.iter() {
+            let pushed = params.len();
+            match self.filter_to_sql(entity_type, filter, &mut params) {
+                Some(condition) => conditions.push(condition),
+                None => params.truncate(pushed),
+            }
+        }
+
+        let is_complete = conditions.len() == conjuncts.len() + 1
+            && self.native_kinds(entity_type, &query.order.field).1;
+        let pagination = if is_complete {
+            let direction = if query.order.descending {
+                "DESC"
+            } else {
+                "ASC"
+            };
+            let limit = query.first.map(|first| first as i64).unwrap_or(-1);
+            format!(
+                r#"ORDER BY "{}" {direction}, id {direction} LIMIT {limit} OFFSET {}"#,
+                query.order.field, query.skip
+            )
+        } else {
+            String::new()
+        };
+
+        let sql = format!(
+            "SELECT * FROM ({}) AS latest WHERE {} {pagination}",
+            Sqlite::latest_snapshot_query(entity_type, ""),
+            conditions.join(" AND ")
+        );
+        let entity_type = entity_type.to_owned();
+        let entities = self
+            .blocking(move |db| {
+                let conn = db.conn()?;
+                db.select_entities(&conn, &entity_type, &sql, params_from_iter(params), false)
+            })
+            .await?;
+
+        if is_complete {
+            return Ok(entities);
+        }
+        Ok(query.apply(entities))
+    }
+
+    async fn create_entity(
+        &self,
+        block_ptr: BlockPtr,
+        entity_type: &str,
+        data: RawEntity,
+    ) -> Result<(), DatabaseError> {
+        let (query, values) = self.generate_insert_query(entity_type, data, &block_ptr);
+        self.blocking(move |db| {
+            db.conn()?.execute(&query, params_from_iter(values))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_block_ptr(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
+        self.blocking(move |db| Sqlite::insert_block_ptr(&db.conn()?, &block_ptr))
+            .await
+    }
+
+    async fn load_recent_block_ptrs(
+        &self,
+        number_of_blocks: u16,
+    ) -> Result<Vec<BlockPtr>, DatabaseError> {
+        self.blocking(move |db| {
+            let conn = db.conn()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT block_number, block_hash, parent_hash FROM block_ptr
+                ORDER BY block_number DESC
+                LIMIT ?1"#,
+            )?;
+            let mut block_ptrs = stmt
+                .query_map(params![number_of_blocks], Sqlite::read_block_ptr)?
+                .collect::<Result<Vec<_>, _>>()?;
+            block_ptrs.reverse();
+            Ok(block_ptrs)
+        })
+        .await
+    }
+
+    async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError> {
+        self.blocking(|db| {
+            let block_ptr = db
+                .conn()?
+                .query_row(
+                    r#"
+                    SELECT block_number, block_hash, parent_hash FROM block_ptr
+                    ORDER BY block_number ASC
+                    LIMIT 1"#,
+                    [],
+                    Sqlite::read_block_ptr,
+                )
+                .optional()?;
+            Ok(block_ptr)
+        })
+        .await
+    }
+
+    async fn load_block_ptr(&self, block_number: u64) -> Result<Option<BlockPtr>, DatabaseError> {
+        self.blocking(move |db| {
+            let block_ptr = db
+                .conn()?
+                .query_row(
+                    r#"
+                    SELECT block_number, block_hash, parent_hash FROM block_ptr
+                    WHERE block_number = ?1"#,
+                    params![block_number as i64],
+                    Sqlite::read_block_ptr,
+                )
+                .optional()?;
+            Ok(block_ptr)
+        })
+        .await
+    }
+
+    async fn load_datasources(&self) -> Result<Option<Vec<Datasource>>, DatabaseError> {
+        let datasources = self
+            .blocking(|db| {
+                let conn = db.conn()?;
+                let mut stmt = conn.prepare(
+                    "SELECT datasource FROM datasources ORDER BY IFNULL(created_at_block, -1) ASC",
+                )?;
+                let datasources = stmt
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(datasources)
+            })
+            .await?;
+        let result = datasources
+            .into_iter()
+            .map(|ds| serde_json::from_str::<Datasource>(&ds))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatabaseError::Plain(format!("Invalid datasource: {:?}", e)))?;
+
+        if result.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(result))
+    }
+
+    async fn save_subgraph_errors(
+        &self,
+        errors: Vec<SubgraphErrorRecord>,
+    ) -> Result<(), DatabaseError> {
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+            Sqlite::insert_subgraph_errors(&tx, errors)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_subgraph_errors(&self) -> Result<Vec<SubgraphErrorRecord>, DatabaseError> {
+        self.blocking(|db| {
+            let conn = db.conn()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT block_number, block_hash, parent_hash, handler, message
+                FROM subgraph_errors
+                ORDER BY block_number ASC"#,
+            )?;
+            let errors = stmt
+                .query_map([], |row| {
+                    Ok(SubgraphErrorRecord {
+                        block_ptr: Sqlite::read_block_ptr(row)?,
+                        handler: row.get(3)?,
+                        message: row.get(4)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(errors)
+        })
+        .await
+    }
+
+    async fn save_schema(&self, schema: &str) -> Result<(), DatabaseError> {
+        let schema = schema.to_owned();
+        self.blocking(move |db| {
+            db.conn()?.execute(
+                "INSERT OR REPLACE INTO subgraph_schema (id, schema) VALUES (0, ?1)",
+                params![schema],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_schema(&self) -> Result<Option<String>, DatabaseError> {
+        self.blocking(|db| {
+            let schema = db
+                .conn()?
+                .query_row(
+                    "SELECT schema FROM subgraph_schema WHERE id = 0",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(schema)
+        })
+        .await
+    }
+
+    async fn batch_insert_entities(
+        &self,
+        block_ptr: BlockPtr,
+        values: Vec<(EntityType, RawEntity)>,
+    ) -> Result<(), DatabaseError> {
+        let count = values.len();
+        let block_number = block_ptr.number;
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+            db.insert_entities(&tx, &block_ptr, values)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+        info!(
+            Sqlite,
+            "Commit result";
+            statements => format!("{:?} statements", count),
+            block => block_number
+        );
+        Ok(())
+    }
+
+    async fn commit_block(
+        &self,
+        block_ptr: BlockPtr,
+        values: Vec<(EntityType, RawEntity)>,
+        errors: Vec<SubgraphErrorRecord>,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError> {
+        let count = values.len();
+        let block_number = block_ptr.number;
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+            db.insert_entities(&tx, &block_ptr, values)?;
+            Sqlite::insert_subgraph_errors(&tx, errors)?;
+            Sqlite::insert_datasources(&tx, datasources)?;
+            Sqlite::insert_block_ptr(&tx, &block_ptr)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+        info!(
+            Sqlite,
+            "Commit result";
+            statements => format!("{:?} statements", count),
+            block => block_number
+        );
+        Ok(())
+    }
+
+    async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError> {
+        let from_block = from_block as i64;
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+
+            for entity_type in db.schemas.get_entity_names() {
+                tx.execute(
+                    &format!("DELETE FROM \"{entity_type}\" WHERE __block_ptr__ >= ?1"),
+                    params![from_block],
+                )?;
+            }
+
+            tx.execute(
+                "DELETE FROM block_ptr WHERE block_number >= ?1",
+                params![from_block],
+            )?;
+            tx.execute(
+                "DELETE FROM datasources WHERE created_at_block >= ?1",
+                params![from_block],
+            )?;
+            tx.execute(
+                "DELETE FROM subgraph_errors WHERE block_number >= ?1",
+                params![from_block],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_snapshots(
+        &self,
+        entities: Vec<(EntityType, EntityID)>,
+        to_block: u64,
+    ) -> Result<usize, DatabaseError> {
+        let count = entities.len();
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+
+            for (entity_type, entity_id) in entities {
+                tx.prepare_cached(&format!(
+                    "DELETE FROM \"{entity_type}\" WHERE id = ?1 AND __block_ptr__ < ?2"
+                ))?
+                .execute(params![entity_id, to_block as i64])?;
+            }
+
+            tx.commit()?;
+            Ok(count)
+        })
+        .await
+    }
+
+    /// Remove every snapshot older than `to_block`, except the ones
+    /// that are still the latest version of their entity at `to_block`
+    async fn clean_data_history(&self, to_block: u64) -> Result<u64, DatabaseError> {
+        let to_block = to_block as i64;
+        self.blocking(move |db| {
+            let mut conn = db.conn()?;
+            let tx = conn.transaction()?;
+            let mut removed = 0;
+
+            for entity_type in db.schemas.get_entity_names() {
+                let query = format!(
+                    r#"
+                    DELETE FROM "{entity_type}"
+                    WHERE __block_ptr__ < ?1 AND EXISTS (
+                        SELECT 1 FROM "{entity_type}" AS latest
+                        WHERE latest.id = "{entity_type}".id
+                        AND latest.__block_ptr__ > "{entity_type}".__block_ptr__
+                        AND latest.__block_ptr__ <= ?1
+                    )"#
+                );
+                removed += tx.execute(&query, params![to_block])? as u64;
+            }
+
+            tx.execute(
+                "DELETE FROM block_ptr WHERE block_number < ?1",
+                params![to_block],
+            )?;
+            tx.commit()?;
+            Ok(removed)
+        })
+        .await
+    }
+
+    fn get_schema(&self) -> Schemas {
+        self.schemas.as_ref().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Schema;
+    use crate::database::EntityOrder;
+    use crate::entity;
+    use crate::schema;
+    use df_logger::loggers::init_logger;
+
+    fn setup() -> (Sqlite, String) {
+        init_logger();
+        let mut schemas = Schemas::default();
+        let mut test_schema: Schema = schema!(
+            id => StoreValueKind::String,
+            name => StoreValueKind::String,
+            total_supply => StoreValueKind::BigInt,
+            price => StoreValueKind::BigDecimal,
+            decimals => StoreValueKind::Int,
+            data => StoreValueKind::Bytes,
+            users => StoreValueKind::Array
+        );
+        test_schema.get_mut("users").unwrap().list_inner_kind = Some(StoreValueKind::String);
+
+        let entity_type = "Token".to_string();
+        schemas.add_schema(&entity_type, test_schema, None);
+        (Sqlite::new(":memory:", schemas).unwrap(), entity_type)
+    }
+
+    fn token(id: &str, supply: u64, is_deleted: bool) -> RawEntity {
+        entity! {
+            id => Value::String(id.to_string()),
+            name => Value::String("Tether USD".to_string()),
+            total_supply => Value::BigInt(BigInt::from(supply)),
+            price => Value::BigDecimal(BigDecimal::from_str("1.0001").unwrap()),
+            decimals => Value::Int(6),
+            data => Value::Bytes(Bytes::from(vec![1, 2, 3])),
+            users => Value::List(vec![Value::String("vu".to_string())]),
+            __is_deleted__ => Value::Bool(is_deleted)
+        }
+    }
+
+    fn block_ptr(number: u64) -> BlockPtr {
+        BlockPtr {
+            number,
+            hash: format!("n={number}"),
+            parent_hash: format!("n={}", number.saturating_sub(1)),
+        }
+    }
+
+    async fn insert_blocks(db: &Sqlite, entity_type: &str, blocks: std::ops::RangeInclusive<u64>) {
+        for number in blocks {
+            let values = vec![
+                (entity_type.to_owned(), token("a", number, false)),
+                (entity_type.to_owned(), token("b", number, number == 5)),
+            ];
+            db.batch_insert_entities(block_ptr(number), values)
+                .await
+                .unwrap();
+            db.save_block_ptr(block_ptr(number)).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_entities() {
+        let (db, entity_type) = setup();
+        insert_blocks(&db, &entity_type, 1..=5).await;
+
+        let a = db.load_entity(&entity_type, "a").await.unwrap().unwrap();
+        assert_eq!(a, {
+            let mut expected = token("a", 5, false);
+            expected.insert("__block_ptr__".to_string(), Value::Int8(5));
+            expected
+        });
+        assert!(db.load_entity(&entity_type, "b").await.unwrap().is_none());
+
+        let b = db
+            .load_entity_at_block(&entity_type, "b", 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(b.get("total_supply"), Some(&Value::BigInt(BigInt::from(3))));
+
+        let loaded = db
+            .load_entities(&entity_type, vec!["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(db.scan_entities(&entity_type, None).await.unwrap().len(), 1);
+        assert_eq!(
+            db.scan_entities(&entity_type, Some(4)).await.unwrap().len(),
+            2
+        );
+
+        let duplicate = db
+            .create_entity(block_ptr(5), &entity_type, token("a", 5, false))
+            .await;
+        assert!(duplicate.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_entities_page() {
+        let (db, entity_type) = setup();
+        insert_blocks(&db, &entity_type, 1..=5).await;
+        db.create_entity(block_ptr(2), &entity_type, token("c", 2, false))
+            .await
+            .unwrap();
+
+        let ids = |page: Vec<RawEntity>| {
+            page.into_iter()
+                .map(|entity| (entity["id"].clone(), entity["__is_deleted__"].clone()))
+                .collect::<Vec<_>>()
+        };
+        let first = db
+            .scan_entities_page(&entity_type, 5, None, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(first),
+            vec![
+                (Value::String("a".to_string()), Value::Bool(false)),
+                (Value::String("b".to_string()), Value::Bool(true)),
+            ]
+        );
+        let next = db
+            .scan_entities_page(&entity_type, 5, Some("b".to_string()), 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(next),
+            vec![(Value::String("c".to_string()), Value::Bool(false))]
+        );
+        let last = db
+            .scan_entities_page(&entity_type, 5, Some("c".to_string()), 2)
+            .await
+            .unwrap();
+        assert!(last.is_empty());
+
+        let before = db
+            .scan_entities_page(&entity_type, 1, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(before.len(), 2);
+        assert_eq!(before[1].get("__is_deleted__"), Some(&Value::Bool(false)));
+    }
+
+    #[tokio::test]
+    async fn test_query_entities() {
+        let (db, entity_type) = setup();
+        let values = [("a", 300), ("b", 100), ("c", 200)]
+            .into_iter()
+            .map(|(id, supply)| (entity_type.clone(), token(id, supply, false)))
+            .collect();
+        db.commit_block(block_ptr(1), values, vec![], vec![])
+            .await
+            .unwrap();
+        insert_blocks(&db, &entity_type, 2..=2).await;
+
+        let id = |id: &str| Value::String(id.to_string());
+        let ids = |entities: Vec<RawEntity>| {
+            entities
+                .into_iter()
+                .map(|entity| entity.get("id").cloned().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let field = |field: &str, op: Operator, value: Value| EntityFilter::Field {
+            field: field.to_string(),
+            op,
+            value,
+        };
+
+        // Evaluated by SQLite entirely
+        let query = EntityQuery {
+            filter: Some(EntityFilter::And(vec![
+                field(
+                    "id",
+                    Operator::In,
+                    Value::List(vec![id("a"), id("b"), Value::Null]),
+                ),
+                field("decimals", Operator::Gte, Value::Int(6)),
+            ])),
+            order: EntityOrder {
+                field: "id".to_string(),
+                descending: true,
+            },
+            first: Some(1),
+            skip: 1,
+        };
+        let entities = db.query_entities(&entity_type, &query, None).await.unwrap();
+        assert_eq!(ids(entities), vec![id("a")]);
+
+        // Big numbers are stored as text, so they are ordered in memory
+        let query = EntityQuery {
+            filter: Some(field(
+                "total_supply",
+                Operator::Gt,
+                Value::BigInt(BigInt::from(100)),
+            )),
+            order: EntityOrder {
+                field: "total_supply".to_string(),
+                descending: false,
+            },
+            ..Default::default()
+        };
+        let entities = db.query_entities(&entity_type, &query, None).await.unwrap();
+        assert_eq!(ids(entities), vec![id("c")]);
+        let entities = db
+            .query_entities(&entity_type, &query, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(ids(entities), vec![id("c"), id("a")]);
+
+        let query = EntityQuery {
+            filter: Some(EntityFilter::And(vec![
+                field("users", Operator::Contains, Value::List(vec![id("vu")])),
+                field("id", Operator::Not, id("c")),
+            ])),
+            ..Default::default()
+        };
+        let entities = db.query_entities(&entity_type, &query, None).await.unwrap();
+        assert_eq!(ids(entities), vec![id("a"), id("b")]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_block() {
+        let (db, entity_type) = setup();
+        insert_blocks(&db, &entity_type, 1..=2).await;
+
+        let mut broken = token("c", 3, false);
+        broken.remove("__is_deleted__");
+        let values = vec![
+            (entity_type.clone(), token("a", 3, false)),
+            (entity_type.clone(), broken),
+        ];
+        assert!(db
+            .commit_block(block_ptr(3), values, vec![], vec![])
+            .await
+            .is_err());
+        let a = db.load_entity(&entity_type, "a").await.unwrap().unwrap();
+        assert_eq!(a.get("__block_ptr__"), Some(&Value::Int8(2)));
+        assert_eq!(
+            db.load_recent_block_ptrs(1).await.unwrap(),
+            vec![block_ptr(2)]
+        );
+
+        let values = vec![(entity_type.clone(), token("a", 3, false))];
+        db.commit_block(block_ptr(3), values, vec![], vec![])
+            .await
+            .unwrap();
+        let a = db.load_entity(&entity_type, "a").await.unwrap().unwrap();
+        assert_eq!(a.get("__block_ptr__"), Some(&Value::Int8(3)));
+        assert_eq!(
+            db.load_recent_block_ptrs(1).await.unwrap(),
+            vec![block_ptr(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_and_cleanup() {
+        let (db, entity_type) = setup();
+        insert_blocks(&db, &entity_type, 1..=5).await;
+
+        let recent = db.load_recent_block_ptrs(3).await.unwrap();
+        assert_eq!(recent, vec![block_ptr(3), block_ptr(4), block_ptr(5)]);
+
+        db.revert_from_block(4).await.unwrap();
+        let recent = db.load_recent_block_ptrs(10).await.unwrap();
+        assert_eq!(recent.last(), Some(&block_ptr(3)));
+        let b = db.load_entity(&entity_type, "b").await.unwrap().unwrap();
+        assert_eq!(b.get("__block_ptr__"), Some(&Value::Int8(3)));
+
+        let removed = db.clean_data_history(3).await.unwrap();
+        assert_eq!(removed, 4);
+        assert_eq!(
+            db.get_earliest_block_ptr().await.unwrap(),
+            Some(block_ptr(3))
+        );
+        assert!(db
+            .load_entity_at_block(&entity_type, "b", 2)
+            .await
+            .unwrap()
+            .is_none());
+
+        insert_blocks(&db, &entity_type, 4..=4).await;
+        let count = db
+            .remove_snapshots(vec![(entity_type.clone(), "a".to_string())], 4)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(db
+            .load_entity_at_block(&entity_type, "a", 3)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db
+            .load_entity_at_block(&entity_type, "b", 3)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_revert_datasources() {
+        let (db, _) = setup();
+        let datasource = |address: &str, created_at_block: u64| -> Datasource {
+            serde_json::from_value(serde_json::json!({
+                "kind": "ethereum/contract",
+                "name": "Pool",
+                "network": "mainnet",
+                "source": { "address": address, "abi": "Pool", "startBlock": created_at_block },
+                "mapping": {
+                    "kind": "ethereum/events",
+                    "apiVersion": "0.0.7",
+                    "entities": [],
+                    "abis": [],
+                    "file": "Pool.wasm"
+                },
+                "context": { "token0": { "type": "String", "data": "0x01" } }
+            }))
+            .unwrap()
+        };
+
+        db.commit_block(block_ptr(10), vec![], vec![], vec![datasource("0x0a", 10)])
+            .await
+            .unwrap();
+        db.commit_block(block_ptr(20), vec![], vec![], vec![datasource("0x0b", 20)])
+            .await
+            .unwrap();
+        db.revert_from_block(15).await.unwrap();
+
+        let datasources = db.load_datasources().await.unwrap().unwrap();
+        assert_eq!(datasources, vec![datasource("0x0a", 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_graft_metadata() {
+        let (db, entity_type) = setup();
+        assert_eq!(db.load_schema().await.unwrap(), None);
+
+        db.save_schema("type Token @entity { id: ID! }")
+            .await
+            .unwrap();
+        db.save_schema("type Token @entity { id: ID! name: String! }")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.load_schema().await.unwrap(),
+            Some("type Token @entity { id: ID! name: String! }".to_string())
+        );
+
+        insert_blocks(&db, &entity_type, 1..=3).await;
+        assert_eq!(db.load_block_ptr(2).await.unwrap(), Some(block_ptr(2)));
+        assert_eq!(db.load_block_ptr(4).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_errors() {
+        let (db, _) = setup();
+        let error = |number: u64| SubgraphErrorRecord {
+            block_ptr: BlockPtr {
+                number,
+                hash: format!("hash-{number}"),
+                parent_hash: format!("hash-{}", number - 1),
+            },
+            handler: "handleTransfer".to_string(),
+            message: "attempted to divide BigInt `1` by zero".to_string(),
+        };
+
+        db.save_subgraph_errors(vec![error(10), error(20)])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.load_subgraph_errors().await.unwrap(),
+            vec![error(10), error(20)]
+        );
+
+        db.revert_from_block(15).await.unwrap();
+        assert_eq!(db.load_subgraph_errors().await.unwrap(), vec![error(10)]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_values() {
+        let (db, entity_type) = setup();
+        insert_blocks(&db, &entity_type, 1..=1).await;
+        db.conn()
+            .unwrap()
+            .execute(r#"UPDATE "Token" SET total_supply = 'not a number'"#, [])
+            .unwrap();
+
+        let result = db.load_entity(&entity_type, "a").await;
+        assert!(
+            matches!(result, Err(DatabaseError::InvalidValue(field)) if field == "total_supply")
+        );
+
+        db.conn()
+            .unwrap()
+            .execute(
+                r#"UPDATE "Token" SET total_supply = '1', users = '[1]'"#,
+                [],
+            )
+            .unwrap();
+        let result = db.load_entity(&entity_type, "a").await;
+        assert!(matches!(result, Err(DatabaseError::InvalidValue(field)) if field == "users"));
+    }
+}
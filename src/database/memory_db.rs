@@ -9,7 +9,13 @@ use std::collections::HashMap;
 type EntitySnapshots = Vec<RawEntity>;
 
 #[derive(Default, Debug)]
-pub struct MemoryDb(HashMap<EntityType, HashMap<EntityID, EntitySnapshots>>);
+pub struct MemoryDb {
+    store: HashMap<EntityType, HashMap<EntityID, EntitySnapshots>>,
+    /// Entities that got a new snapshot since the last checkpoint, in order
+    journal: Vec<(EntityType, EntityID)>,
+    /// Latest snapshots of the last hand-off, whose commit may still be in flight
+    committing: HashMap<EntityType, HashMap<EntityID, RawEntity>>,
+}
 
 impl MemoryDb {
     pub fn load_entity_latest(
@@ -17,7 +23,7 @@ impl MemoryDb {
         entity_type: &str,
         entity_id: &str,
     ) -> Result<Option<RawEntity>, DatabaseError> {
-        let store = &self.0;
+        let store = &self.store;
         let table = store.get(entity_type);
 
         if table.is_none() {
@@ -55,7 +61,7 @@ impl MemoryDb {
         entity_type: &str,
         data: RawEntity,
     ) -> Result<(), DatabaseError> {
-        let store = &mut self.0;
+        let store = &mut self.store;
         if !store.contains_key(entity_type) {
             store.insert(entity_type.to_owned(), HashMap::new());
         }
@@ -72,6 +78,8 @@ impl MemoryDb {
             let mut new_data = data.clone();
             new_data.insert("__is_deleted__".to_string(), Value::Bool(false));
             snapshots.push(new_data);
+            self.journal
+                .push((entity_type.to_owned(), entity_id.to_owned()));
             Ok(())
         } else {
             error!(MemoryDb, "id is invalid";
@@ -83,7 +91,7 @@ impl MemoryDb {
     }
 
     pub fn soft_delete(&mut self, entity_type: &str, entity_id: &str) -> Result<(), DatabaseError> {
-        let store = &mut self.0;
+        let store = &mut self.store;
         let table = store.get_mut(entity_type);
 
         if table.is_none() {
@@ -105,13 +113,15 @@ impl MemoryDb {
         last.remove("__is_deleted__");
         last.insert("__is_deleted__".to_string(), Value::Bool(true));
         snapshots.push(last);
+        self.journal
+            .push((entity_type.to_owned(), entity_id.to_owned()));
 
         Ok(())
     }
 
     pub fn extract_data(&self) -> Result<Vec<(String, RawEntity)>, DatabaseError> {
         let mut result = vec![];
-        self.0.iter().for_each(|(entity_type, table)| {
+        self.store.iter().for_each(|(entity_type, table)| {
             table.iter().for_each(|(_entity_id, snapshots)| {
                 if let Some(last) = snapshots.last().cloned() {
                     result.push((entity_type.clone(), last));
@@ -122,9 +132,13 @@ impl MemoryDb {
         Ok(result)
     }
 
+    pub fn count_entities(&self) -> usize {
+        self.store.values().map(HashMap::len).sum()
+    }
+
     pub fn get_latest_entity_ids(&self) -> Vec<(EntityType, EntityID)> {
         let mut result = vec![];
-        for (entity_name, data) in self.0.iter() {
+        for (entity_name, data) in self.store.iter() {
             for entity_id in data.keys() {
                 result.push((entity_name.clone(), entity_id.to_owned()));
             }
@@ -133,8 +147,55 @@ impl MemoryDb {
         result
     }
 
+    /// Latest snapshot of an entity handed off with `take_for_commit`,
+    /// `Some(None)` when the entity was deleted
+    pub fn load_committing(&self, entity_type: &str, entity_id: &str) -> Option<Option<RawEntity>> {
+        let data = self.committing.get(entity_type)?.get(entity_id)?;
+        match data.get("__is_deleted__") {
+            Some(Value::Bool(true)) => Some(None),
+            _ => Some(Some(data.clone())),
+        }
+    }
+
+    /// Move the latest snapshot of every entity out to be committed. They remain
+    /// readable with `load_committing` until the next hand-off
+    pub fn take_for_commit(&mut self) -> Result<Vec<(EntityType, RawEntity)>, DatabaseError> {
+        let values = self.extract_data()?;
+        self.committing = std::mem::take(&mut self.store)
+            .into_iter()
+            .map(|(entity_type, table)| {
+                let latest = table
+                    .into_iter()
+                    .filter_map(|(entity_id, mut snapshots)| Some((entity_id, snapshots.pop()?)))
+                    .collect();
+                (entity_type, latest)
+            })
+            .collect();
+        self.journal.clear();
+        Ok(values)
+    }
+
     pub fn clear(&mut self) {
-        self.0 = HashMap::new();
+        self.store = HashMap::new();
+        self.journal = vec![];
+        self.committing = HashMap::new();
+    }
+
+    /// Changes made before this point are kept by the next `rollback`
+    pub fn checkpoint(&mut self) {
+        self.journal.clear();
+    }
+
+    /// Drop every snapshot created since the last checkpoint
+    pub fn rollback(&mut self) {
+        while let Some((entity_type, entity_id)) = self.journal.pop() {
+            let table = self.store.get_mut(&entity_type).unwrap();
+            let snapshots = table.get_mut(&entity_id).unwrap();
+            snapshots.pop();
+            if snapshots.is_empty() {
+                table.remove(&entity_id);
+            }
+        }
     }
 }
 
@@ -193,7 +254,7 @@ mod tests {
 
         let latest = db.load_entity_latest("test", "1").unwrap();
         assert!(latest.is_none());
-        assert_eq!(db.0.get("test").unwrap().get("1").unwrap().len(), 2);
+        assert_eq!(db.store.get("test").unwrap().get("1").unwrap().len(), 2);
     }
 
     #[test]
@@ -233,4 +294,63 @@ mod tests {
         log::info!("extract_data: {:?}", extract_data);
         assert_eq!(extract_data.len(), 3);
     }
+
+    #[test]
+    fn test_memory_04_rollback() {
+        init_logger();
+        let mut db = MemoryDb::default();
+        let mut data = HashMap::new();
+        data.insert("id".to_string(), Value::String("1".to_string()));
+        data.insert("name".to_string(), Value::String("test".to_string()));
+        db.create_entity("test", data.clone()).unwrap();
+        db.checkpoint();
+
+        data.insert("name".to_string(), Value::String("test111".to_string()));
+        db.create_entity("test", data).unwrap();
+        db.soft_delete("test", "1").unwrap();
+        let mut data = HashMap::new();
+        data.insert("id".to_string(), Value::String("2".to_string()));
+        db.create_entity("test", data).unwrap();
+
+        db.rollback();
+
+        let latest = db.load_entity_latest("test", "1").unwrap().unwrap();
+        assert_eq!(
+            latest.get("name").unwrap(),
+            &Value::String("test".to_string())
+        );
+        assert!(db.load_entity_latest("test", "2").unwrap().is_none());
+        assert_eq!(db.extract_data().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_memory_05_take_for_commit() {
+        init_logger();
+        let mut db = MemoryDb::default();
+        let mut data = HashMap::new();
+        data.insert("id".to_string(), Value::String("1".to_string()));
+        data.insert("name".to_string(), Value::String("test".to_string()));
+        db.create_entity("test", data.clone()).unwrap();
+        data.insert("id".to_string(), Value::String("2".to_string()));
+        db.create_entity("test", data).unwrap();
+        db.soft_delete("test", "2").unwrap();
+
+        assert_eq!(db.count_entities(), 2);
+        let values = db.take_for_commit().unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(db.count_entities(), 0);
+        assert!(db.load_entity_latest("test", "1").unwrap().is_none());
+        assert!(db.extract_data().unwrap().is_empty());
+
+        let committing = db.load_committing("test", "1").unwrap().unwrap();
+        assert_eq!(
+            committing.get("name").unwrap(),
+            &Value::String("test".to_string())
+        );
+        assert_eq!(db.load_committing("test", "2"), Some(None));
+        assert_eq!(db.load_committing("test", "3"), None);
+
+        db.take_for_commit().unwrap();
+        assert_eq!(db.load_committing("test", "1"), None);
+    }
 }
@@ -1,46 +1,72 @@
+mod entity_cache;
+mod entity_query;
 mod extern_db;
 mod memory_db;
 mod metrics;
 mod utils;
 
+pub use entity_query::EntityFilter;
+pub use entity_query::EntityOrder;
+pub use entity_query::EntityQuery;
+pub use entity_query::Operator;
+pub use extern_db::ExternDB;
+pub use extern_db::ExternDBTrait;
+
 use crate::common::BlockPtr;
+use crate::common::Datasource;
 use crate::common::EntityID;
 use crate::common::EntityType;
 use crate::common::FieldName;
+use crate::common::Graft;
 use crate::common::RawEntity;
 use crate::common::Schemas;
 use crate::common::StoreOperationMessage;
 use crate::common::StoreRequestResult;
+use crate::common::SubgraphErrorRecord;
 use crate::config::DatabaseConfig;
 use crate::errors::DatabaseError;
 use crate::info;
 use crate::runtime::asc::native_types::store::Value;
 use crate::warn;
-use extern_db::ExternDB;
-use extern_db::ExternDBTrait;
+use entity_cache::EntityCache;
+use entity_cache::DEFAULT_ENTITY_CACHE_SIZE;
 use memory_db::MemoryDb;
 use metrics::DatabaseMetrics;
 use prometheus::Registry;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Instant;
 
+/// Entities read & written per round trip when copying the base of a graft
+const GRAFT_CHUNK_SIZE: usize = 10_000;
+
 pub struct Database {
     pub mem: MemoryDb,
-    pub db: ExternDB,
+    cache: EntityCache,
+    /// Shared with the commits still being written
+    pub db: Arc<ExternDB>,
     pub earliest_block: u64,
     metrics: DatabaseMetrics,
     schema: Schemas,
+    /// Errors of the `nonFatalErrors` mode, persisted with the next commit
+    pending_errors: Vec<SubgraphErrorRecord>,
 }
 
 impl Database {
     pub async fn new(
         config: &DatabaseConfig,
         schema: Schemas,
+        entity_cache_size: Option<usize>,
         registry: &Registry,
     ) -> Result<Self, DatabaseError> {
         let mem = MemoryDb::default();
-        let db = ExternDB::new(config, schema.clone()).await?;
+        let cache = EntityCache::new(
+            entity_cache_size.unwrap_or(DEFAULT_ENTITY_CACHE_SIZE),
+            &schema,
+        );
+        let db = Arc::new(ExternDB::new(config, schema.clone()).await?);
         let earliest_block = db
             .get_earliest_block_ptr()
             .await?
@@ -49,10 +75,12 @@ impl Database {
         let metrics = DatabaseMetrics::new(registry);
         Ok(Database {
             mem,
+            cache,
             db,
             metrics,
             schema,
             earliest_block,
+            pending_errors: vec![],
         })
     }
 
@@ -99,11 +127,29 @@ impl Database {
         let entity = self.mem.load_entity_latest(&entity_type, &entity_id)?;
 
         if entity.is_none() {
+            // The previous batch may not have landed in the database yet
+            if let Some(committing) = self.mem.load_committing(&entity_type, &entity_id) {
+                self.metrics.database_cache_hit.inc();
+                if let Some(data) = &committing {
+                    self.mem.create_entity(&entity_type, data.clone())?;
+                }
+                return Ok(StoreRequestResult::Load(committing));
+            }
+
+            if let Some(cached) = self.cache.get(&entity_type, &entity_id) {
+                self.metrics.database_cache_hit.inc();
+                if let Some(data) = &cached {
+                    self.mem.create_entity(&entity_type, data.clone())?;
+                }
+                return Ok(StoreRequestResult::Load(cached));
+            }
+
             self.metrics.database_cache_miss.inc();
             self.metrics.extern_db_load.inc();
             let timer = self.metrics.extern_db_get_duration.start_timer();
             let entity = self.db.load_entity(&entity_type, &entity_id).await?;
             timer.stop_and_record();
+            self.cache.insert(&entity_type, &entity_id, entity.clone());
             if entity.is_none() {
                 return Ok(StoreRequestResult::Load(None));
             }
@@ -180,16 +226,33 @@ impl Database {
                 let entity = self.mem.load_entity_latest(&relation_table, &id)?;
                 if entity.is_some() {
                     related_entities.push(entity.unwrap());
-                } else {
-                    missing_ids.push(id);
+                    continue;
+                }
+
+                let cached = match self.mem.load_committing(&relation_table, &id) {
+                    Some(cached) => Some(cached),
+                    None => self.cache.get(&relation_table, &id),
+                };
+                match cached {
+                    Some(Some(entity)) => {
+                        self.metrics.database_cache_hit.inc();
+                        related_entities.push(entity.clone());
+                        self.mem.create_entity(&relation_table, entity)?;
+                    }
+                    Some(None) => self.metrics.database_cache_hit.inc(),
+                    None => missing_ids.push(id),
                 }
             }
             if !missing_ids.is_empty() {
+                self.metrics
+                    .database_cache_miss
+                    .inc_by(missing_ids.len() as u64);
                 let timer = self.metrics.extern_db_get_duration.start_timer();
                 let entities = self.db.load_entities(&relation_table, missing_ids).await?;
                 timer.stop_and_record();
 
                 for entity in entities {
+                    self.cache.insert_latest(&relation_table, &entity);
                     related_entities.push(entity.clone());
                     self.mem.create_entity(&relation_table, entity)?;
                 }
@@ -200,21 +263,11 @@ impl Database {
         }
     }
 
-    async fn migrate_from_mem_to_db(&mut self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
-        let values = self.mem.extract_data()?;
-        self.metrics.extern_db_write.inc();
-        let timer = self.metrics.extern_db_set_duration.start_timer();
-        self.db
-            .batch_insert_entities(block_ptr.clone(), values)
-            .await?;
-        timer.stop_and_record();
-        self.metrics.extern_db_write.inc();
-        self.db.save_block_ptr(block_ptr.clone()).await?;
-        Ok(())
-    }
-
     async fn revert_from_block(&mut self, block_number: u64) -> Result<(), DatabaseError> {
         self.mem.clear();
+        self.cache.clear();
+        self.pending_errors
+            .retain(|error| error.block_ptr.number < block_number);
         self.db.revert_from_block(block_number).await
     }
 }
@@ -234,9 +287,10 @@ impl DatabaseAgent {
     pub async fn new(
         config: &DatabaseConfig,
         schema: Schemas,
+        entity_cache_size: Option<usize>,
         registry: &Registry,
     ) -> Result<Self, DatabaseError> {
-        let db = Database::new(config, schema.to_owned(), registry).await?;
+        let db = Database::new(config, schema.to_owned(), entity_cache_size, registry).await?;
         Ok(Self::from(db))
     }
 
@@ -253,6 +307,21 @@ impl DatabaseAgent {
         })
     }
 
+    /// The extern database, shared with readers outside of the subgraph such as GraphQL
+    pub fn extern_db(&self) -> Arc<ExternDB> {
+        self.0.borrow().db.clone()
+    }
+
+    pub async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError> {
+        let db = self.0.borrow();
+        db.db.get_earliest_block_ptr().await
+    }
+
+    /// Blocks at or below this one have no history left to revert to
+    pub fn earliest_block(&self) -> u64 {
+        self.0.borrow().earliest_block
+    }
+
     pub async fn get_recent_block_pointers(
         &self,
         number_of_blocks: u16,
@@ -261,73 +330,282 @@ impl DatabaseAgent {
         db.db.load_recent_block_ptrs(number_of_blocks).await
     }
 
-    pub async fn commit_data(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
-        let time = Instant::now();
-        let block_number = block_ptr.number;
+    /// Rows above the last saved block pointer belong to a commit that never completed
+    pub async fn remove_orphans(&self) -> Result<(), DatabaseError> {
         let mut db = self.0.borrow_mut();
-        db.migrate_from_mem_to_db(block_ptr).await?;
-        info!(
-            Database,
-            "committed to database";
-            block_number => block_number,
-            exec_time => format!("{:?}", time.elapsed())
-        );
+        let last_saved = db
+            .db
+            .load_recent_block_ptrs(1)
+            .await?
+            .into_iter()
+            .map(|block_ptr| block_ptr.number)
+            .max();
+        let from_block = last_saved.map_or(0, |block_number| block_number + 1);
+        db.revert_from_block(from_block).await?;
+        info!(Database, "orphaned rows removed"; from_block => from_block);
         Ok(())
     }
 
-    pub async fn flush_cache(&self) -> Result<(), DatabaseError> {
-        let mut db = self.0.borrow_mut();
-        db.mem.clear();
-        info!(Database, "flushed entity cache");
-        Ok(())
+    pub async fn save_schema(&self, schema: &str) -> Result<(), DatabaseError> {
+        let db = self.0.borrow();
+        db.db.save_schema(schema).await
     }
 
-    pub async fn revert_from_block(&self, block_number: u64) -> Result<(), DatabaseError> {
-        warn!(Database, "Reverting data (probably due to reorg)"; revert_from_block_number => block_number);
+    /// Seed a fresh database with the entities & created datasources of `graft.base`
+    /// as they were at `graft.block`, so that indexing resumes from the next block.
+    /// The base is read page by page through the connection of this database, and the
+    /// block pointer is written last: an interrupted copy skips the entities already copied
+    pub async fn graft(&self, graft: &Graft) -> Result<(), DatabaseError> {
         let mut db = self.0.borrow_mut();
-        db.revert_from_block(block_number).await?;
-        warn!(Database, "Database reverted OK"; revert_from_block_number => block_number);
+        let graft_failed = |reason: String| DatabaseError::GraftFailed(graft.base.clone(), reason);
+
+        if db.db.get_earliest_block_ptr().await?.is_some() {
+            info!(Database, "subgraph already started, skip grafting"; base => graft.base);
+            return Ok(());
+        }
+
+        let base_schema = db
+            .db
+            .read_namespace(&graft.base, Schemas::default())?
+            .load_schema()
+            .await?
+            .ok_or_else(|| graft_failed("base has no schema".to_string()))?;
+        let base_schema = Schemas::new_from_graphql_schema(&base_schema);
+        db.schema
+            .check_graft_compatibility(&base_schema)
+            .map_err(graft_failed)?;
+
+        let base = db.db.read_namespace(&graft.base, base_schema.clone())?;
+        let block_ptr = base
+            .load_block_ptr(graft.block)
+            .await?
+            .ok_or_else(|| graft_failed(format!("block {} is not indexed", graft.block)))?;
+
+        let entity_types = db.schema.get_entity_names();
+        let mut number_of_entities = 0;
+        for entity_type in base_schema.get_entity_names() {
+            if !entity_types.contains(&entity_type) {
+                continue;
+            }
+            let mut after_id = None;
+            // Copied ids are a subset of the base ids, the same page of this database
+            // holds every one of them falling in the range of the base page
+            let mut resuming = true;
+            loop {
+                let page = base
+                    .scan_entities_page(
+                        &entity_type,
+                        graft.block,
+                        after_id.clone(),
+                        GRAFT_CHUNK_SIZE,
+                    )
+                    .await?;
+                let Some(Value::String(last_id)) = page.last().and_then(|e| e.get("id")).cloned()
+                else {
+                    break;
+                };
+                let copied = if resuming {
+                    db.db
+                        .scan_entities_page(&entity_type, u64::MAX, after_id, GRAFT_CHUNK_SIZE)
+                        .await?
+                        .into_iter()
+                        .filter_map(|entity| match entity.get("id") {
+                            Some(Value::String(id)) => Some(id.clone()),
+                            _ => None,
+                        })
+                        .collect::<HashSet<_>>()
+                } else {
+                    HashSet::new()
+                };
+                resuming = !copied.is_empty();
+                let live = page
+                    .into_iter()
+                    .filter(|entity| entity.get("__is_deleted__") != Some(&Value::Bool(true)))
+                    .collect::<Vec<_>>();
+                number_of_entities += live.len();
+                let values = live
+                    .into_iter()
+                    .filter(|entity| match entity.get("id") {
+                        Some(Value::String(id)) => !copied.contains(id),
+                        _ => true,
+                    })
+                    .map(|entity| (entity_type.clone(), entity))
+                    .collect::<Vec<_>>();
+                if !values.is_empty() {
+                    db.db
+                        .batch_insert_entities(block_ptr.clone(), values)
+                        .await?;
+                }
+                after_id = Some(last_id);
+            }
+        }
+
+        let datasources = base
+            .load_datasources()
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|ds| ds.source.startBlock.unwrap_or(0) <= graft.block)
+            .collect::<Vec<_>>();
+
+        db.db
+            .commit_block(block_ptr, vec![], vec![], datasources)
+            .await?;
+        db.earliest_block = graft.block;
+
+        info!(
+            Database,
+            "grafted onto base subgraph";
+            base => graft.base,
+            block => graft.block,
+            number_of_entities => number_of_entities
+        );
         Ok(())
     }
 
-    pub async fn remove_outdated_snapshots(&self, at_block: u64) -> Result<usize, DatabaseError> {
+    pub async fn load_datasources(&self) -> Result<Vec<Datasource>, DatabaseError> {
         let db = self.0.borrow();
-        let entities = db.mem.get_latest_entity_ids();
-        let count = db.db.remove_snapshots(entities, at_block).await?;
-        info!(Database, "entities' snapshot removed"; number_of_entity => count);
-        Ok(count)
+        let datasources = db.db.load_datasources().await?.unwrap_or_default();
+        Ok(datasources)
     }
 
-    pub async fn clean_data_history(&self, to_block: u64) -> Result<u64, DatabaseError> {
+    /// Hand the changes of the processed blocks over to a `PendingCommit`, so the next
+    /// batch can be processed while it is written. The previous commit must have landed
+    /// by then, as its snapshots are no longer readable from memory afterwards
+    pub fn take_commit(
+        &self,
+        block_ptr: BlockPtr,
+        datasources: Vec<Datasource>,
+        block_data_retention: Option<u64>,
+    ) -> Result<PendingCommit, DatabaseError> {
         let mut db = self.0.borrow_mut();
+        let entity_ids = db.mem.get_latest_entity_ids();
+        let values = db.mem.take_for_commit()?;
+        for (entity_type, entity) in values.iter() {
+            db.cache.insert_latest(entity_type, entity);
+        }
+        let errors = std::mem::take(&mut db.pending_errors);
 
-        if db.earliest_block < to_block {
-            let removed = db.db.clean_data_history(to_block).await?;
-            info!(
-                Database,
-                "cleaned up data history in database";
-                to_block => to_block,
-                removed => format!("{removed} records")
-            );
+        let clean_history_to = block_data_retention
+            .filter(|history_size| block_ptr.number > *history_size)
+            .map(|history_size| block_ptr.number - history_size)
+            .filter(|to_block| db.earliest_block < *to_block);
+        if let Some(to_block) = clean_history_to {
             db.earliest_block = to_block;
-            return Ok(removed);
         }
 
-        Ok(0)
+        Ok(PendingCommit {
+            db: db.db.clone(),
+            metrics: db.metrics.clone(),
+            block_ptr,
+            values,
+            errors,
+            entity_ids,
+            datasources,
+            clean_history_to,
+        })
+    }
+
+    /// Entities held in memory since the last hand-off
+    pub fn count_cached_entities(&self) -> usize {
+        let db = self.0.borrow();
+        db.mem.count_entities()
+    }
+
+    /// Mark the start of a block, so the changes it makes can be discarded
+    pub fn checkpoint(&self) {
+        let mut db = self.0.borrow_mut();
+        db.mem.checkpoint();
+    }
+
+    /// Discard the uncommitted changes made since the last checkpoint
+    pub fn rollback(&self) {
+        let mut db = self.0.borrow_mut();
+        db.mem.rollback();
+    }
+
+    pub fn record_subgraph_error(&self, error: SubgraphErrorRecord) {
+        let mut db = self.0.borrow_mut();
+        db.pending_errors.push(error);
+    }
+
+    pub async fn revert_from_block(&self, block_number: u64) -> Result<(), DatabaseError> {
+        warn!(Database, "Reverting data (probably due to reorg)"; revert_from_block_number => block_number);
+        let mut db = self.0.borrow_mut();
+        db.revert_from_block(block_number).await?;
+        warn!(Database, "Database reverted OK"; revert_from_block_number => block_number);
+        Ok(())
     }
 
     #[cfg(test)]
     pub fn empty(registry: &Registry) -> Self {
         let mem = MemoryDb::default();
-        let db = ExternDB::None;
+        let cache = EntityCache::new(DEFAULT_ENTITY_CACHE_SIZE, &Schemas::default());
+        let db = Arc::new(ExternDB::None);
         let metrics = DatabaseMetrics::new(registry);
         let database = Database {
             mem,
+            cache,
             db,
             metrics,
             schema: Schemas::default(),
             earliest_block: 0,
+            pending_errors: vec![],
         };
         DatabaseAgent::from(database)
     }
 }
+
+/// The writes of a processed batch, made while the next batch is being processed.
+/// Created datasources are committed together with the block pointer
+pub struct PendingCommit {
+    db: Arc<ExternDB>,
+    metrics: DatabaseMetrics,
+    block_ptr: BlockPtr,
+    values: Vec<(EntityType, RawEntity)>,
+    errors: Vec<SubgraphErrorRecord>,
+    entity_ids: Vec<(EntityType, EntityID)>,
+    datasources: Vec<Datasource>,
+    clean_history_to: Option<u64>,
+}
+
+impl PendingCommit {
+    pub async fn write(self) -> Result<(), DatabaseError> {
+        let time = Instant::now();
+        let block_number = self.block_ptr.number;
+
+        let number_of_datasources = self.datasources.len();
+
+        self.metrics.extern_db_write.inc();
+        let timer = self.metrics.extern_db_set_duration.start_timer();
+        self.db
+            .commit_block(self.block_ptr, self.values, self.errors, self.datasources)
+            .await?;
+        timer.stop_and_record();
+        info!(
+            Database,
+            "committed to database";
+            block_number => block_number,
+            number_of_datasources => number_of_datasources,
+            exec_time => format!("{:?}", time.elapsed())
+        );
+
+        let count = self
+            .db
+            .remove_snapshots(self.entity_ids, block_number)
+            .await?;
+        info!(Database, "entities' snapshot removed"; number_of_entity => count);
+
+        if let Some(to_block) = self.clean_history_to {
+            let removed = self.db.clean_data_history(to_block).await?;
+            info!(
+                Database,
+                "cleaned up data history in database";
+                to_block => to_block,
+                removed => format!("{removed} records")
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use kanal::SendError;
 use std::io;
 use thiserror::Error;
 use wasmer::CompileError;
+use wasmer::InstantiationError;
 use wasmer::MemoryAccessError;
 use wasmer::RuntimeError;
 
@@ -16,6 +17,12 @@ use scylla::transport::errors as ScyllaError;
 #[cfg(feature = "mongo")]
 use mongodb::error as MongoError;
 
+#[cfg(feature = "postgres")]
+use tokio_postgres::Error as PostgresError;
+
+#[cfg(feature = "sqlite")]
+use rusqlite::Error as SqliteError;
+
 #[derive(Error, Debug)]
 pub enum BigIntOutOfRangeError {
     #[error("Cannot convert negative BigInt into type")]
@@ -77,6 +84,10 @@ impl From<AscError> for RuntimeError {
 pub enum WasmHostError {
     #[error("Wasm Compiling failed: {0}")]
     Compile(#[from] CompileError),
+    #[error("Unsupported host functions: {0}")]
+    MissingImports(String),
+    #[error("Wasm instantiation failed: {0}")]
+    Instantiation(#[from] InstantiationError),
 }
 
 #[derive(Debug, Error)]
@@ -111,6 +122,52 @@ pub enum SubgraphError {
     InvalidHandlerName(String),
     #[error("Create source failed: `{0}`")]
     CreateSourceFail(String),
+    #[error("Handler `{0}` failed: {1}")]
+    HandlerFailed(String, String),
+    #[error("Handler `{0}` exceeded the gas limit")]
+    GasExhausted(String),
+    #[error("Handler `{0}` ran out of time")]
+    Timeout(String),
+    #[error("Handler `{0}` failed outside of the mapping: {1}")]
+    NonDeterministic(String, NonDeterministicError),
+}
+
+impl SubgraphError {
+    /// Only failures of the services behind host functions may go away on a retry
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SubgraphError::NonDeterministic(_, cause) => cause.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// Failure of a service backing a host function rather than of the mapping itself,
+/// so processing the same block again could succeed
+#[derive(Debug, Error)]
+pub enum NonDeterministicError {
+    #[error("database failure: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("rpc failure: {0}")]
+    Rpc(#[from] RPCError),
+    #[error("ipfs failure: {0}")]
+    Ipfs(#[from] IpfsError),
+}
+
+impl NonDeterministicError {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            NonDeterministicError::Database(e) => e.is_transient(),
+            NonDeterministicError::Rpc(e) => e.is_transient(),
+            NonDeterministicError::Ipfs(e) => e.is_transient(),
+        }
+    }
+}
+
+impl From<NonDeterministicError> for RuntimeError {
+    fn from(err: NonDeterministicError) -> Self {
+        RuntimeError::user(Box::new(err))
+    }
 }
 
 #[derive(Debug, Error)]
@@ -139,6 +196,8 @@ pub enum DatabaseError {
     WasmSendInvalidRequest,
     #[error("Schema readonly")]
     SchemaReadOnly,
+    #[error("Cannot graft onto `{0}`: {1}")]
+    GraftFailed(String, String),
 
     #[cfg(feature = "scylla")]
     #[error("Init failed")]
@@ -151,6 +210,70 @@ pub enum DatabaseError {
     #[cfg(feature = "mongo")]
     #[error("Init failed")]
     MongoDBInit(#[from] MongoError::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres error: {0}")]
+    Postgres(#[from] PostgresError),
+
+    #[cfg(feature = "sqlite")]
+    #[error("Sqlite error: {0}")]
+    Sqlite(#[from] SqliteError),
+}
+
+impl From<DatabaseError> for RuntimeError {
+    fn from(err: DatabaseError) -> Self {
+        if err.is_backend_failure() {
+            NonDeterministicError::from(err).into()
+        } else {
+            RuntimeError::new(err.to_string())
+        }
+    }
+}
+
+impl DatabaseError {
+    /// Failures of the database or its driver, as opposed to invalid entities or requests
+    pub fn is_backend_failure(&self) -> bool {
+        match self {
+            DatabaseError::MutexLockFailed => true,
+            #[cfg(feature = "scylla")]
+            DatabaseError::ScyllaNewSession(_) | DatabaseError::ScyllaQuery(_) => true,
+            #[cfg(feature = "mongo")]
+            DatabaseError::MongoDBInit(_) => true,
+            #[cfg(feature = "postgres")]
+            DatabaseError::Postgres(_) => true,
+            #[cfg(feature = "sqlite")]
+            DatabaseError::Sqlite(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Failures of the connection to the database rather than of the data itself
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(feature = "scylla")]
+            DatabaseError::ScyllaNewSession(_) | DatabaseError::ScyllaQuery(_) => true,
+            // Bad credentials or options would fail the same way on every retry
+            #[cfg(feature = "mongo")]
+            DatabaseError::MongoDBInit(e) => {
+                matches!(
+                    *e.kind,
+                    MongoError::ErrorKind::Io(_)
+                        | MongoError::ErrorKind::ConnectionPoolCleared { .. }
+                        | MongoError::ErrorKind::ServerSelection { .. }
+                        | MongoError::ErrorKind::DnsResolve { .. }
+                ) || e.contains_label(MongoError::TRANSIENT_TRANSACTION_ERROR)
+                    || e.contains_label(MongoError::RETRYABLE_WRITE_ERROR)
+            }
+            #[cfg(feature = "postgres")]
+            DatabaseError::Postgres(e) => e.as_db_error().is_none(),
+            #[cfg(feature = "sqlite")]
+            DatabaseError::Sqlite(SqliteError::SqliteFailure(e, _)) => matches!(
+                e.code,
+                rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+            ),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -194,6 +317,33 @@ pub enum SourceError {
     #[cfg(feature = "pubsub")]
     #[error("Decode message error: {0}")]
     DecodeMessageError(String),
+    #[error("RPC request failed: {0}")]
+    RpcRequestFail(#[from] web3::Error),
+    #[error("Block #{0} is not available from RPC")]
+    RpcMissingBlock(u64),
+    #[error("Logs do not match block #{0}, chain probably reorganized")]
+    RpcInconsistentLogs(u64),
+    #[cfg(feature = "file")]
+    #[error("File source error: {0}")]
+    FileSourceError(String),
+}
+
+impl SourceError {
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SourceError::Nats(_)
+            | SourceError::TrinoConnectionFail
+            | SourceError::TrinoQueryFail
+            | SourceError::RpcRequestFail(_)
+            | SourceError::RpcMissingBlock(_)
+            | SourceError::RpcInconsistentLogs(_) => true,
+            #[cfg(feature = "deltalake")]
+            SourceError::DeltaTableError(_) => true,
+            #[cfg(feature = "pubsub")]
+            SourceError::PubSubError(_) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -218,6 +368,72 @@ pub enum RPCError {
     Revert(String),
     #[error("Get latest-block failed")]
     GetLatestBlockFail,
+    #[error("Get transaction receipt failed")]
+    GetTransactionReceiptFail,
+    #[error("Trace transaction failed")]
+    CallTraceFail,
+    #[error("Call traces are not supported by the node: {0}")]
+    CallTraceUnsupported(String),
+    #[error("Request timed out")]
+    Timeout,
+}
+
+impl RPCError {
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            RPCError::Timeout
+                | RPCError::GetLatestBlockFail
+                | RPCError::GetTransactionReceiptFail
+                | RPCError::CallTraceFail
+                | RPCError::ContractCallFail
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IpfsError {
+    #[error("No ipfs resolver configured")]
+    NotConfigured,
+    #[error("Invalid ipfs path: {0}")]
+    InvalidPath(String),
+    #[error("File not found: {0}")]
+    NotFound(String),
+    #[error("File exceeds the size limit: {0}")]
+    FileTooLarge(String),
+    #[error("Timed out fetching: {0}")]
+    Timeout(String),
+    #[error("Gateway request failed: {0}")]
+    Gateway(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl IpfsError {
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            IpfsError::Timeout(_) | IpfsError::Gateway(_) | IpfsError::Io(_)
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GraphQLError {
+    #[error("Query parsing failed: {0}")]
+    Parse(String),
+    #[error("No query operation found")]
+    MissingOperation,
+    #[error("Variable `${0}` is not defined")]
+    UndefinedVariable(String),
+    #[error("Fragment `{0}` is not defined")]
+    UndefinedFragment(String),
+    #[error("Type `{1}` has no field `{0}`")]
+    UnknownField(String, String),
+    #[error("Invalid argument `{0}`: {1}")]
+    InvalidArgument(String, String),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
 }
 
 #[derive(Debug, Error)]
@@ -228,4 +444,68 @@ pub enum MainError {
     Subgraph(#[from] SubgraphError),
     #[error("filter error: `{0}`")]
     Filter(#[from] FilterError),
+    #[error("manifest error: `{0}`")]
+    Manifest(#[from] ManifestLoaderError),
+    #[error("rpc error: `{0}`")]
+    Rpc(#[from] RPCError),
+    #[error("ipfs error: `{0}`")]
+    Ipfs(#[from] IpfsError),
+    #[error("block source error: `{0}`")]
+    Source(#[from] SourceError),
+}
+
+impl MainError {
+    /// Errors worth retrying from the last committed block, eg: a database timeout or an rpc blip.
+    /// Anything caused by the subgraph or its data would fail the same way again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            MainError::Database(e) => e.is_transient(),
+            MainError::Rpc(e) => e.is_transient(),
+            MainError::Ipfs(e) => e.is_transient(),
+            MainError::Source(e) => e.is_transient(),
+            MainError::Subgraph(e) => e.is_transient(),
+            MainError::Filter(_) | MainError::Manifest(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(MainError::from(RPCError::Timeout).is_transient());
+        assert!(MainError::from(SourceError::RpcMissingBlock(10)).is_transient());
+        assert!(MainError::from(IpfsError::Timeout("Qm".to_string())).is_transient());
+        assert!(!MainError::from(RPCError::BadABI).is_transient());
+        assert!(MainError::from(RPCError::GetTransactionReceiptFail).is_transient());
+        assert!(!MainError::from(RPCError::CallTraceUnsupported(String::new())).is_transient());
+        assert!(!MainError::from(DatabaseError::MissingID).is_transient());
+        assert!(
+            !MainError::from(SubgraphError::GasExhausted("handleSwap".to_string())).is_transient()
+        );
+        assert!(!MainError::from(ManifestLoaderError::SchemaParsingError).is_transient());
+        assert!(MainError::from(SubgraphError::NonDeterministic(
+            "handleSwap".to_string(),
+            NonDeterministicError::Rpc(RPCError::ContractCallFail)
+        ))
+        .is_transient());
+        assert!(!MainError::from(SubgraphError::NonDeterministic(
+            "handleSwap".to_string(),
+            NonDeterministicError::Ipfs(IpfsError::NotConfigured)
+        ))
+        .is_transient());
+    }
+
+    #[test]
+    fn test_host_failures_are_non_deterministic() {
+        let err = RuntimeError::from(DatabaseError::MutexLockFailed);
+        assert!(matches!(
+            err.downcast::<NonDeterministicError>(),
+            Ok(NonDeterministicError::Database(_))
+        ));
+        let err = RuntimeError::from(DatabaseError::MissingID);
+        assert!(err.downcast::<NonDeterministicError>().is_err());
+    }
 }
@@ -0,0 +1,325 @@
+use crate::common::FieldKind;
+use crate::common::RawEntity;
+use crate::common::Schema;
+use crate::database::EntityFilter;
+use crate::database::EntityOrder;
+use crate::database::EntityQuery;
+use crate::database::Operator;
+use crate::errors::GraphQLError;
+use crate::runtime::asc::native_types::store::Bytes;
+use crate::runtime::asc::native_types::store::StoreValueKind;
+use crate::runtime::asc::native_types::store::Value;
+use crate::runtime::bignumber::bigdecimal::BigDecimal;
+use crate::runtime::bignumber::bigint::BigInt;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const DEFAULT_FIRST: usize = 100;
+const MAX_FIRST: usize = 1000;
+
+// NOTE: suffixes sharing an ending must come longest-first, eg `_not_in` before `_in`
+const OPERATOR_SUFFIXES: [(&str, Operator); 13] = [
+    ("_not_starts_with", Operator::NotStartsWith),
+    ("_not_ends_with", Operator::NotEndsWith),
+    ("_not_contains", Operator::NotContains),
+    ("_starts_with", Operator::StartsWith),
+    ("_ends_with", Operator::EndsWith),
+    ("_contains", Operator::Contains),
+    ("_not_in", Operator::NotIn),
+    ("_not", Operator::Not),
+    ("_gte", Operator::Gte),
+    ("_lte", Operator::Lte),
+    ("_in", Operator::In),
+    ("_gt", Operator::Gt),
+    ("_lt", Operator::Lt),
+];
+
+fn parse_filter(
+    schema: &Schema,
+    entity_type: &str,
+    arg: &JsonValue,
+) -> Result<EntityFilter, GraphQLError> {
+    let object = arg
+        .as_object()
+        .ok_or_else(|| invalid_argument("where", "expected an input object"))?;
+    let mut filters = vec![];
+
+    for (key, value) in object {
+        if key == "and" || key == "or" {
+            let inner = value
+                .as_array()
+                .ok_or_else(|| invalid_argument(key, "expected a list of input objects"))?
+                .iter()
+                .map(|item| parse_filter(schema, entity_type, item))
+                .collect::<Result<Vec<_>, _>>()?;
+            filters.push(match key.as_str() {
+                "and" => EntityFilter::And(inner),
+                _ => EntityFilter::Or(inner),
+            });
+            continue;
+        }
+
+        let (field, op) = split_filter_key(schema, key)
+            .ok_or_else(|| GraphQLError::UnknownField(key.to_owned(), entity_type.to_owned()))?;
+        let field_kind = schema.get(&field).unwrap();
+        let value_kind = match op {
+            Operator::In | Operator::NotIn => FieldKind {
+                kind: StoreValueKind::Array,
+                relation: None,
+                list_inner_kind: Some(field_kind.kind),
+            },
+            _ => field_kind.clone(),
+        };
+        let value = json_to_store_value(value, &value_kind).ok_or_else(|| {
+            invalid_argument(key, &format!("value does not match `{field}`'s type"))
+        })?;
+        filters.push(EntityFilter::Field { field, op, value });
+    }
+
+    Ok(EntityFilter::And(filters))
+}
+
+fn parse_order(
+    schema: &Schema,
+    entity_type: &str,
+    arguments: &HashMap<String, JsonValue>,
+) -> Result<EntityOrder, GraphQLError> {
+    let field = match arguments.get("orderBy") {
+        None | Some(JsonValue::Null) => "id".to_owned(),
+        Some(JsonValue::String(field)) if schema.contains_key(field) => field.to_owned(),
+        Some(JsonValue::String(field)) => {
+            return Err(GraphQLError::UnknownField(
+                field.to_owned(),
+                entity_type.to_owned(),
+            ))
+        }
+        Some(_) => return Err(invalid_argument("orderBy", "expected a field name")),
+    };
+    let descending = match arguments.get("orderDirection") {
+        None | Some(JsonValue::Null) => false,
+        Some(JsonValue::String(direction)) if direction == "asc" => false,
+        Some(JsonValue::String(direction)) if direction == "desc" => true,
+        Some(_) => {
+            return Err(invalid_argument(
+                "orderDirection",
+                "expected `asc` or `desc`",
+            ))
+        }
+    };
+    Ok(EntityOrder { field, descending })
+}
+
+/// Arguments shared by every list field: `where`, `orderBy`, `orderDirection`, `first` & `skip`
+#[derive(Clone, Debug)]
+pub struct CollectionArgs(EntityQuery);
+
+impl CollectionArgs {
+    pub fn parse(
+        schema: &Schema,
+        entity_type: &str,
+        arguments: &HashMap<String, JsonValue>,
+    ) -> Result<Self, GraphQLError> {
+        let filter = match arguments.get("where") {
+            None | Some(JsonValue::Null) => None,
+            Some(arg) => Some(parse_filter(schema, entity_type, arg)?),
+        };
+        let order = parse_order(schema, entity_type, arguments)?;
+        let first = parse_usize_argument(arguments, "first")?.unwrap_or(DEFAULT_FIRST);
+        let skip = parse_usize_argument(arguments, "skip")?.unwrap_or(0);
+
+        if first > MAX_FIRST {
+            return Err(invalid_argument(
+                "first",
+                &format!("must be at most {MAX_FIRST}"),
+            ));
+        }
+
+        Ok(CollectionArgs(EntityQuery {
+            filter,
+            order,
+            first: Some(first),
+            skip,
+        }))
+    }
+
+    pub fn query(&self) -> &EntityQuery {
+        &self.0
+    }
+
+    pub fn apply(&self, entities: Vec<RawEntity>) -> Vec<RawEntity> {
+        self.0.apply(entities)
+    }
+}
+
+fn parse_usize_argument(
+    arguments: &HashMap<String, JsonValue>,
+    name: &str,
+) -> Result<Option<usize>, GraphQLError> {
+    match arguments.get(name) {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|n| Some(n as usize))
+            .ok_or_else(|| invalid_argument(name, "expected a non-negative integer")),
+    }
+}
+
+fn invalid_argument(name: &str, reason: &str) -> GraphQLError {
+    GraphQLError::InvalidArgument(name.to_owned(), reason.to_owned())
+}
+
+fn split_filter_key(schema: &Schema, key: &str) -> Option<(String, Operator)> {
+    if schema.contains_key(key) {
+        return Some((key.to_owned(), Operator::Equal));
+    }
+
+    OPERATOR_SUFFIXES.iter().find_map(|(suffix, op)| {
+        key.strip_suffix(*suffix)
+            .filter(|field| schema.contains_key(*field))
+            .map(|field| (field.to_owned(), *op))
+    })
+}
+
+fn json_to_store_value(value: &JsonValue, field_kind: &FieldKind) -> Option<Value> {
+    if value.is_null() {
+        return Some(Value::Null);
+    }
+
+    let number_or_string = || match value {
+        JsonValue::String(s) => Some(s.to_owned()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+
+    match field_kind.kind {
+        StoreValueKind::String => value.as_str().map(|s| Value::String(s.to_owned())),
+        StoreValueKind::Int => value
+            .as_i64()
+            .and_then(|n| i32::try_from(n).ok())
+            .map(Value::Int),
+        StoreValueKind::Int8 => number_or_string()
+            .and_then(|n| n.parse::<i64>().ok())
+            .map(Value::Int8),
+        StoreValueKind::Bool => value.as_bool().map(Value::Bool),
+        StoreValueKind::BigInt => number_or_string()
+            .and_then(|n| BigInt::from_str(&n).ok())
+            .map(Value::BigInt),
+        StoreValueKind::BigDecimal => number_or_string()
+            .and_then(|n| BigDecimal::from_str(&n).ok())
+            .map(Value::BigDecimal),
+        StoreValueKind::Bytes => value
+            .as_str()
+            .and_then(|s| Bytes::from_str(s).ok())
+            .map(Value::Bytes),
+        StoreValueKind::Array => {
+            let inner_kind = FieldKind {
+                kind: field_kind.list_inner_kind?,
+                relation: None,
+                list_inner_kind: None,
+            };
+            value
+                .as_array()?
+                .iter()
+                .map(|item| json_to_store_value(item, &inner_kind))
+                .collect::<Option<Vec<_>>>()
+                .map(Value::List)
+        }
+        StoreValueKind::Null => Some(Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entity;
+    use crate::schema;
+    use serde_json::json;
+
+    fn tokens() -> (Schema, Vec<RawEntity>) {
+        let mut schema: Schema = schema!(
+            id => StoreValueKind::String,
+            symbol => StoreValueKind::String,
+            supply => StoreValueKind::BigInt,
+            tags => StoreValueKind::Array
+        );
+        schema.get_mut("tags").unwrap().list_inner_kind = Some(StoreValueKind::String);
+
+        let entities = [
+            ("a", "USDT", 300, "stable"),
+            ("b", "WETH", 100, "wrapped"),
+            ("c", "USDC", 200, "stable"),
+        ]
+        .into_iter()
+        .map(|(id, symbol, supply, tag)| {
+            entity! {
+                id => Value::String(id.to_string()),
+                symbol => Value::String(symbol.to_string()),
+                supply => Value::BigInt(BigInt::from(supply)),
+                tags => Value::List(vec![Value::String(tag.to_string())])
+            }
+        })
+        .collect();
+
+        (schema, entities)
+    }
+
+    fn ids(entities: &[RawEntity]) -> Vec<String> {
+        entities
+            .iter()
+            .map(|e| match e.get("id") {
+                Some(Value::String(id)) => id.to_owned(),
+                _ => panic!("missing id"),
+            })
+            .collect()
+    }
+
+    fn apply(arguments: JsonValue) -> Result<Vec<String>, GraphQLError> {
+        let (schema, entities) = tokens();
+        let arguments = serde_json::from_value::<HashMap<String, JsonValue>>(arguments).unwrap();
+        let args = CollectionArgs::parse(&schema, "Token", &arguments)?;
+        Ok(ids(&args.apply(entities)))
+    }
+
+    #[test]
+    fn test_collection_args() {
+        assert_eq!(apply(json!({})).unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(
+            apply(json!({ "orderBy": "supply", "orderDirection": "desc" })).unwrap(),
+            vec!["a", "c", "b"]
+        );
+        assert_eq!(
+            apply(json!({ "orderBy": "supply", "first": 2, "skip": 1 })).unwrap(),
+            vec!["c", "a"]
+        );
+        assert_eq!(
+            apply(json!({ "where": { "supply_gte": "200" } })).unwrap(),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            apply(json!({ "where": { "symbol_starts_with": "USD", "supply_lt": 250 } })).unwrap(),
+            vec!["c"]
+        );
+        assert_eq!(
+            apply(json!({ "where": { "id_not_in": ["a", "c"] } })).unwrap(),
+            vec!["b"]
+        );
+        assert_eq!(
+            apply(json!({ "where": { "tags_contains": ["stable"] } })).unwrap(),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            apply(json!({ "where": { "or": [{ "id": "b" }, { "symbol": "USDC" }] } })).unwrap(),
+            vec!["b", "c"]
+        );
+
+        assert!(matches!(
+            apply(json!({ "where": { "price_gt": 1 } })),
+            Err(GraphQLError::UnknownField(_, _))
+        ));
+        assert!(matches!(
+            apply(json!({ "first": 5000 })),
+            Err(GraphQLError::InvalidArgument(_, _))
+        ));
+    }
+}
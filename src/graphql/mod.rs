@@ -0,0 +1,94 @@
+mod filter;
+mod query;
+mod resolver;
+
+use crate::common::Schemas;
+use crate::database::ExternDB;
+use query::parse_query;
+use resolver::Resolver;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::RwLock;
+use warp::http::StatusCode;
+use warp::Filter;
+use warp::Rejection;
+use warp::Reply;
+
+#[derive(Deserialize, Debug)]
+struct GraphQLRequest {
+    query: String,
+    #[serde(default)]
+    variables: Option<Map<String, JsonValue>>,
+}
+
+/// Resolvers of the hosted subgraphs by name, kept in sync as subgraphs are added or removed
+#[derive(Clone, Default)]
+pub struct GraphQLRoutes(Arc<RwLock<HashMap<String, Arc<Resolver>>>>);
+
+impl GraphQLRoutes {
+    pub fn sync(&self, stores: Vec<(String, Arc<ExternDB>, Schemas)>) {
+        let resolvers = stores
+            .into_iter()
+            .map(|(name, db, schemas)| (name, Arc::new(Resolver::new(db, schemas))))
+            .collect();
+        *self.0.write().unwrap() = resolvers;
+    }
+
+    fn get(&self, subgraph: &str) -> Option<Arc<Resolver>> {
+        self.0.read().unwrap().get(subgraph).cloned()
+    }
+}
+
+async fn graphql_handler(
+    subgraph: String,
+    request: GraphQLRequest,
+    routes: GraphQLRoutes,
+) -> Result<impl Reply, Rejection> {
+    let Some(resolver) = routes.get(&subgraph) else {
+        let message = format!("Subgraph `{subgraph}` is not hosted");
+        let response = json!({ "data": null, "errors": [{ "message": message }] });
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+
+    let variables = request.variables.unwrap_or_default();
+    let result = match parse_query(&request.query, &variables) {
+        Ok(fields) => resolver.resolve(fields).await,
+        Err(err) => Err(err),
+    };
+
+    let response = match result {
+        Ok(data) => json!({ "data": data }),
+        Err(err) => {
+            crate::warn!(GraphQL, "Query failed"; subgraph => subgraph, error => err.to_string());
+            json!({ "data": null, "errors": [{ "message": err.to_string() }] })
+        }
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        StatusCode::OK,
+    ))
+}
+
+/// Every hosted subgraph is served at `/subgraphs/name/<name>/graphql`,
+/// the main one at `/graphql` as well
+pub async fn run_graphql_server(port: u16, routes: GraphQLRoutes, main_subgraph: String) {
+    crate::info!(GraphQL, format!("Start graphql server at port: {port}"));
+    let main_route = warp::path!("graphql").map(move || main_subgraph.clone());
+    let named_route = warp::path!("subgraphs" / "name" / String / "graphql");
+    let graphql_route = main_route
+        .or(named_route)
+        .unify()
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || routes.clone()))
+        .and_then(graphql_handler);
+    warp::serve(graphql_route).run(([0, 0, 0, 0], port)).await;
+}
@@ -0,0 +1,279 @@
+use crate::errors::GraphQLError;
+use apollo_parser::cst;
+use apollo_parser::cst::CstNode;
+use apollo_parser::cst::Definition;
+use apollo_parser::cst::Selection;
+use apollo_parser::Parser;
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+const MAX_QUERY_DEPTH: usize = 32;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Field {
+    pub alias: Option<String>,
+    pub name: String,
+    pub arguments: HashMap<String, JsonValue>,
+    pub selections: Vec<Field>,
+}
+
+impl Field {
+    pub fn response_key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+struct QueryContext {
+    variables: Map<String, JsonValue>,
+    fragments: HashMap<String, cst::SelectionSet>,
+}
+
+/// Parse the first operation of a query document into a field tree,
+/// with variables substituted and fragments inlined
+pub fn parse_query(
+    query: &str,
+    variables: &Map<String, JsonValue>,
+) -> Result<Vec<Field>, GraphQLError> {
+    let ast = Parser::new(query).parse();
+
+    if let Some(err) = ast.errors().next() {
+        return Err(GraphQLError::Parse(err.message().to_string()));
+    }
+
+    let doc = ast.document();
+    let mut operation = None;
+    let mut fragments = HashMap::new();
+
+    for def in doc.definitions() {
+        match def {
+            Definition::OperationDefinition(op) if operation.is_none() => operation = Some(op),
+            Definition::FragmentDefinition(fragment) => {
+                let name = fragment
+                    .fragment_name()
+                    .and_then(|f| f.name())
+                    .map(|n| n.text().to_string());
+                if let (Some(name), Some(selection_set)) = (name, fragment.selection_set()) {
+                    fragments.insert(name, selection_set);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let operation = operation.ok_or(GraphQLError::MissingOperation)?;
+    let mut ctx = QueryContext {
+        variables: variables.clone(),
+        fragments,
+    };
+
+    if let Some(definitions) = operation.variable_definitions() {
+        for def in definitions.variable_definitions() {
+            let name = def
+                .variable()
+                .and_then(|v| v.name())
+                .map(|n| n.text().to_string());
+            let default_value = def.default_value().and_then(|v| v.value());
+            if let (Some(name), Some(default_value)) = (name, default_value) {
+                if !ctx.variables.contains_key(&name) {
+                    let value = parse_value(default_value, &ctx.variables)?;
+                    ctx.variables.insert(name, value);
+                }
+            }
+        }
+    }
+
+    let selection_set = operation
+        .selection_set()
+        .ok_or(GraphQLError::MissingOperation)?;
+    parse_selection_set(selection_set, &ctx, 0)
+}
+
+fn parse_selection_set(
+    selection_set: cst::SelectionSet,
+    ctx: &QueryContext,
+    depth: usize,
+) -> Result<Vec<Field>, GraphQLError> {
+    if depth > MAX_QUERY_DEPTH {
+        return Err(GraphQLError::Parse(format!(
+            "query is nested deeper than {MAX_QUERY_DEPTH} levels"
+        )));
+    }
+
+    let mut fields = vec![];
+
+    for selection in selection_set.selections() {
+        match selection {
+            Selection::Field(field) => fields.push(parse_field(field, ctx, depth)?),
+            Selection::FragmentSpread(spread) => {
+                let name = spread
+                    .fragment_name()
+                    .and_then(|f| f.name())
+                    .map(|n| n.text().to_string())
+                    .unwrap_or_default();
+                let fragment = ctx
+                    .fragments
+                    .get(&name)
+                    .cloned()
+                    .ok_or(GraphQLError::UndefinedFragment(name))?;
+                fields.extend(parse_selection_set(fragment, ctx, depth + 1)?);
+            }
+            Selection::InlineFragment(fragment) => {
+                if let Some(selection_set) = fragment.selection_set() {
+                    fields.extend(parse_selection_set(selection_set, ctx, depth + 1)?);
+                }
+            }
+        }
+    }
+
+    Ok(fields)
+}
+
+fn parse_field(
+    field: cst::Field,
+    ctx: &QueryContext,
+    depth: usize,
+) -> Result<Field, GraphQLError> {
+    let name = field
+        .name()
+        .map(|n| n.text().to_string())
+        .ok_or_else(|| GraphQLError::Parse(format!("invalid field: {}", field.source_string())))?;
+    let alias = field
+        .alias()
+        .and_then(|a| a.name())
+        .map(|n| n.text().to_string());
+
+    let mut arguments = HashMap::new();
+    if let Some(args) = field.arguments() {
+        for arg in args.arguments() {
+            if let (Some(arg_name), Some(value)) = (arg.name(), arg.value()) {
+                let value = parse_value(value, &ctx.variables)?;
+                arguments.insert(arg_name.text().to_string(), value);
+            }
+        }
+    }
+
+    let selections = match field.selection_set() {
+        Some(selection_set) => parse_selection_set(selection_set, ctx, depth + 1)?,
+        None => vec![],
+    };
+
+    Ok(Field {
+        alias,
+        name,
+        arguments,
+        selections,
+    })
+}
+
+fn parse_value(
+    value: cst::Value,
+    variables: &Map<String, JsonValue>,
+) -> Result<JsonValue, GraphQLError> {
+    let json = match value {
+        cst::Value::Variable(var) => {
+            let name = var.name().map(|n| n.text().to_string()).unwrap_or_default();
+            variables
+                .get(&name)
+                .cloned()
+                .ok_or(GraphQLError::UndefinedVariable(name))?
+        }
+        cst::Value::StringValue(val) => JsonValue::String(String::from(val)),
+        cst::Value::IntValue(val) => {
+            let text = val.source_string();
+            let number = text
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| GraphQLError::Parse(format!("invalid integer: {text}")))?;
+            JsonValue::from(number)
+        }
+        cst::Value::FloatValue(val) => {
+            let text = val.source_string();
+            text.trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(JsonValue::Number)
+                .ok_or_else(|| GraphQLError::Parse(format!("invalid float: {text}")))?
+        }
+        cst::Value::BooleanValue(val) => JsonValue::Bool(val.source_string().trim() == "true"),
+        cst::Value::NullValue(_) => JsonValue::Null,
+        cst::Value::EnumValue(val) => JsonValue::String(val.source_string().trim().to_owned()),
+        cst::Value::ListValue(list) => {
+            let values = list
+                .values()
+                .map(|v| parse_value(v, variables))
+                .collect::<Result<Vec<_>, _>>()?;
+            JsonValue::Array(values)
+        }
+        cst::Value::ObjectValue(object) => {
+            let mut map = Map::new();
+            for field in object.object_fields() {
+                if let (Some(name), Some(value)) = (field.name(), field.value()) {
+                    map.insert(name.text().to_string(), parse_value(value, variables)?);
+                }
+            }
+            JsonValue::Object(map)
+        }
+    };
+
+    Ok(json)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_query() {
+        let query = r#"
+            query Tokens($minSupply: BigInt, $first: Int = 5) {
+                tokens(first: $first, where: { supply_gte: $minSupply, symbol_in: ["A", "B"] }, orderBy: supply, orderDirection: desc, block: { number: 100 }) {
+                    ...TokenFields
+                    holders { id }
+                }
+                usdt: token(id: "0xdac17f958d2ee523a2206206994597c13d831ec7") { id }
+            }
+
+            fragment TokenFields on Token {
+                id
+                supply
+            }
+        "#;
+        let variables = json!({ "minSupply": "1000" });
+        let fields = parse_query(query, variables.as_object().unwrap()).unwrap();
+        assert_eq!(fields.len(), 2);
+
+        let tokens = &fields[0];
+        assert_eq!(tokens.response_key(), "tokens");
+        assert_eq!(tokens.arguments.get("first"), Some(&json!(5)));
+        assert_eq!(
+            tokens.arguments.get("where"),
+            Some(&json!({ "supply_gte": "1000", "symbol_in": ["A", "B"] }))
+        );
+        assert_eq!(tokens.arguments.get("orderBy"), Some(&json!("supply")));
+        assert_eq!(tokens.arguments.get("orderDirection"), Some(&json!("desc")));
+        assert_eq!(tokens.arguments.get("block"), Some(&json!({ "number": 100 })));
+
+        let selections = tokens
+            .selections
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(selections, vec!["id", "supply", "holders"]);
+        assert_eq!(tokens.selections[2].selections[0].name, "id");
+
+        let usdt = &fields[1];
+        assert_eq!(usdt.name, "token");
+        assert_eq!(usdt.response_key(), "usdt");
+        assert_eq!(
+            usdt.arguments.get("id"),
+            Some(&json!("0xdac17f958d2ee523a2206206994597c13d831ec7"))
+        );
+
+        let undefined = parse_query("{ tokens(first: $first) { id } }", &Map::new());
+        assert!(matches!(undefined, Err(GraphQLError::UndefinedVariable(_))));
+    }
+}
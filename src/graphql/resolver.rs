@@ -0,0 +1,414 @@
+use super::filter::CollectionArgs;
+use super::query::Field;
+use crate::common::EntityType;
+use crate::common::FieldKind;
+use crate::common::RawEntity;
+use crate::common::Schemas;
+use crate::database::EntityFilter;
+use crate::database::EntityQuery;
+use crate::database::ExternDB;
+use crate::database::ExternDBTrait;
+use crate::database::Operator;
+use crate::errors::GraphQLError;
+use crate::runtime::asc::native_types::store::StoreValueKind;
+use crate::runtime::asc::native_types::store::Value;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+enum QueryField {
+    Single(EntityType),
+    Collection(EntityType),
+}
+
+/// Resolves query fields against the snapshots in the extern database.
+/// Every entity type `Token` is exposed as `token(id)` and `tokens(...)`
+pub struct Resolver {
+    db: Arc<ExternDB>,
+    schemas: Schemas,
+    entity_types: HashSet<EntityType>,
+    query_fields: HashMap<String, QueryField>,
+}
+
+impl Resolver {
+    pub fn new(db: Arc<ExternDB>, schemas: Schemas) -> Self {
+        let entity_types = schemas
+            .get_entity_names()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut query_fields = HashMap::new();
+
+        for entity_type in entity_types.iter() {
+            let single = lowercase_first(entity_type);
+            query_fields.insert(
+                pluralize(&single),
+                QueryField::Collection(entity_type.to_owned()),
+            );
+            query_fields.insert(single, QueryField::Single(entity_type.to_owned()));
+        }
+
+        Resolver {
+            db,
+            schemas,
+            entity_types,
+            query_fields,
+        }
+    }
+
+    pub async fn resolve(&self, fields: Vec<Field>) -> Result<JsonValue, GraphQLError> {
+        let mut data = Map::new();
+        for field in fields.iter() {
+            let value = self.resolve_query_field(field).await?;
+            data.insert(field.response_key().to_owned(), value);
+        }
+        Ok(JsonValue::Object(data))
+    }
+
+    async fn resolve_query_field(&self, field: &Field) -> Result<JsonValue, GraphQLError> {
+        if field.name == "__typename" {
+            return Ok(JsonValue::from("Query"));
+        }
+
+        let query_field = self
+            .query_fields
+            .get(&field.name)
+            .ok_or_else(|| GraphQLError::UnknownField(field.name.clone(), "Query".to_owned()))?;
+        let block = parse_block_constraint(&field.arguments)?;
+
+        match query_field {
+            QueryField::Single(entity_type) => {
+                let id = field
+                    .arguments
+                    .get("id")
+                    .and_then(|id| id.as_str())
+                    .ok_or_else(|| {
+                        GraphQLError::InvalidArgument(
+                            "id".to_owned(),
+                            "expected a string".to_owned(),
+                        )
+                    })?;
+                match self.load_entity(entity_type, id, block).await? {
+                    Some(entity) => Ok(self
+                        .resolve_entities(entity_type, vec![entity], &field.selections, block)
+                        .await?
+                        .pop()
+                        .unwrap_or_default()),
+                    None => Ok(JsonValue::Null),
+                }
+            }
+            QueryField::Collection(entity_type) => {
+                let schema = self.schemas.get_schema(entity_type);
+                let args = CollectionArgs::parse(&schema, entity_type, &field.arguments)?;
+                let entities = self
+                    .db
+                    .query_entities(entity_type, args.query(), block)
+                    .await?;
+                let values = self
+                    .resolve_entities(entity_type, entities, &field.selections, block)
+                    .await?;
+                Ok(JsonValue::Array(values))
+            }
+        }
+    }
+
+    async fn load_entity(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        block: Option<u64>,
+    ) -> Result<Option<RawEntity>, GraphQLError> {
+        let entity = match block {
+            Some(block_number) => {
+                self.db
+                    .load_entity_at_block(entity_type, entity_id, block_number)
+                    .await?
+            }
+            None => self.db.load_entity(entity_type, entity_id).await?,
+        };
+        Ok(entity)
+    }
+
+    /// Resolves the selections of all entities together, so that a relation
+    /// costs one database query per nesting level instead of one per entity
+    fn resolve_entities<'a>(
+        &'a self,
+        entity_type: &'a str,
+        entities: Vec<RawEntity>,
+        selections: &'a [Field],
+        block: Option<u64>,
+    ) -> BoxFuture<'a, Result<Vec<JsonValue>, GraphQLError>> {
+        async move {
+            let schema = self.schemas.get_schema(entity_type);
+            let mut results = vec![Map::new(); entities.len()];
+
+            for field in selections {
+                let key = field.response_key();
+
+                if field.name == "__typename" {
+                    for result in results.iter_mut() {
+                        result.insert(key.to_owned(), JsonValue::from(entity_type));
+                    }
+                    continue;
+                }
+
+                let field_kind = schema.get(&field.name).ok_or_else(|| {
+                    GraphQLError::UnknownField(field.name.clone(), entity_type.to_owned())
+                })?;
+
+                match &field_kind.relation {
+                    Some((related_type, related_field))
+                        if self.entity_types.contains(related_type)
+                            && !field.selections.is_empty() =>
+                    {
+                        let related = self
+                            .load_related(
+                                &entities,
+                                field,
+                                field_kind,
+                                (related_type.as_str(), related_field.as_str()),
+                                block,
+                            )
+                            .await?;
+                        let counts = related.iter().map(Vec::len).collect::<Vec<_>>();
+                        let related = related.into_iter().flatten().collect();
+                        let mut values = self
+                            .resolve_entities(related_type, related, &field.selections, block)
+                            .await?
+                            .into_iter();
+
+                        for (result, count) in results.iter_mut().zip(counts) {
+                            let group = values.by_ref().take(count).collect::<Vec<_>>();
+                            let value = if field_kind.kind == StoreValueKind::Array {
+                                JsonValue::Array(group)
+                            } else {
+                                group.into_iter().next().unwrap_or_default()
+                            };
+                            result.insert(key.to_owned(), value);
+                        }
+                    }
+                    _ => {
+                        for (result, entity) in results.iter_mut().zip(entities.iter()) {
+                            let value = entity.get(&field.name).unwrap_or(&Value::Null);
+                            result.insert(key.to_owned(), store_value_to_json(value));
+                        }
+                    }
+                }
+            }
+
+            Ok(results.into_iter().map(JsonValue::Object).collect())
+        }
+        .boxed()
+    }
+
+    /// Related entities of each entity, in the same order. Relations either hold the
+    /// related ids themselves, or are derived from a field of the related entity pointing back
+    async fn load_related(
+        &self,
+        entities: &[RawEntity],
+        field: &Field,
+        field_kind: &FieldKind,
+        relation: (&str, &str),
+        block: Option<u64>,
+    ) -> Result<Vec<Vec<RawEntity>>, GraphQLError> {
+        let (related_type, related_field) = relation;
+
+        let related = if related_field == "id" {
+            let ids_of = entities
+                .iter()
+                .map(|entity| related_ids(entity.get(&field.name)))
+                .collect::<Vec<_>>();
+            let by_id = self
+                .query_by_ids(related_type, "id", ids_of.iter().flatten().copied(), block)
+                .await?
+                .into_iter()
+                .filter_map(|entity| Some((id_key(entity.get("id")?)?, entity)))
+                .collect::<HashMap<_, _>>();
+            ids_of
+                .into_iter()
+                .map(|ids| {
+                    ids.into_iter()
+                        .filter_map(|id| by_id.get(&id_key(id)?).cloned())
+                        .collect()
+                })
+                .collect::<Vec<_>>()
+        } else {
+            let parent_ids = entities.iter().filter_map(|entity| entity.get("id"));
+            let mut by_parent = HashMap::<String, Vec<RawEntity>>::new();
+            for related_entity in self
+                .query_by_ids(related_type, related_field, parent_ids, block)
+                .await?
+            {
+                for parent_id in related_ids(related_entity.get(related_field)) {
+                    if let Some(parent_id) = id_key(parent_id) {
+                        by_parent
+                            .entry(parent_id)
+                            .or_default()
+                            .push(related_entity.clone());
+                    }
+                }
+            }
+            entities
+                .iter()
+                .map(|entity| {
+                    entity
+                        .get("id")
+                        .and_then(id_key)
+                        .and_then(|id| by_parent.get(&id).cloned())
+                        .unwrap_or_default()
+                })
+                .collect()
+        };
+
+        if field_kind.kind != StoreValueKind::Array {
+            return Ok(related);
+        }
+
+        let schema = self.schemas.get_schema(related_type);
+        let args = CollectionArgs::parse(&schema, related_type, &field.arguments)?;
+        Ok(related.into_iter().map(|group| args.apply(group)).collect())
+    }
+
+    /// Entities whose `field` holds any of `ids`, in a single query
+    async fn query_by_ids(
+        &self,
+        entity_type: &str,
+        field: &str,
+        ids: impl Iterator<Item = &Value>,
+        block: Option<u64>,
+    ) -> Result<Vec<RawEntity>, GraphQLError> {
+        let mut unique = HashSet::new();
+        let ids = ids
+            .filter(|id| id_key(id).is_some_and(|key| unique.insert(key)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let is_list = self
+            .schemas
+            .get_schema(entity_type)
+            .get(field)
+            .is_some_and(|field_kind| field_kind.kind == StoreValueKind::Array);
+        let filter = if is_list {
+            EntityFilter::Or(
+                ids.into_iter()
+                    .map(|id| EntityFilter::Field {
+                        field: field.to_owned(),
+                        op: Operator::Contains,
+                        value: Value::List(vec![id]),
+                    })
+                    .collect(),
+            )
+        } else {
+            EntityFilter::Field {
+                field: field.to_owned(),
+                op: Operator::In,
+                value: Value::List(ids),
+            }
+        };
+        let query = EntityQuery {
+            filter: Some(filter),
+            ..Default::default()
+        };
+        Ok(self.db.query_entities(entity_type, &query, block).await?)
+    }
+}
+
+/// Relations point at ids, either strings or bytes
+fn id_key(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.to_owned()),
+        Value::Bytes(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn related_ids(value: Option<&Value>) -> Vec<&Value> {
+    match value {
+        Some(Value::List(list)) => list.iter().filter(|id| id_key(id).is_some()).collect(),
+        Some(id) if id_key(id).is_some() => vec![id],
+        _ => vec![],
+    }
+}
+
+/// Time-travel queries: `block: { number: N }` reads the latest snapshots at or before block N
+fn parse_block_constraint(
+    arguments: &HashMap<String, JsonValue>,
+) -> Result<Option<u64>, GraphQLError> {
+    let invalid =
+        |reason: &str| GraphQLError::InvalidArgument("block".to_owned(), reason.to_owned());
+
+    match arguments.get("block") {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(JsonValue::Object(block)) => match block.get("number") {
+            Some(number) => number
+                .as_u64()
+                .map(Some)
+                .ok_or_else(|| invalid("`number` must be a non-negative integer")),
+            None => Err(invalid("only `number` is supported")),
+        },
+        Some(_) => Err(invalid("expected `{ number: Int }`")),
+    }
+}
+
+fn store_value_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::String(s) => JsonValue::from(s.as_str()),
+        Value::Int(n) => JsonValue::from(*n),
+        Value::Int8(n) => JsonValue::from(*n),
+        Value::BigInt(n) => JsonValue::from(n.to_string()),
+        Value::BigDecimal(n) => JsonValue::from(n.to_string()),
+        Value::Bool(b) => JsonValue::from(*b),
+        Value::Bytes(bytes) => JsonValue::from(bytes.to_string()),
+        Value::List(list) => JsonValue::Array(list.iter().map(store_value_to_json).collect()),
+        Value::Null => JsonValue::Null,
+    }
+}
+
+fn lowercase_first(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn pluralize(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix('y') {
+        if !stem.is_empty() && !stem.ends_with(|c: char| "aeiou".contains(c)) {
+            return format!("{stem}ies");
+        }
+    }
+
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+    {
+        return format!("{name}es");
+    }
+
+    format!("{name}s")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query_field_names() {
+        assert_eq!(pluralize(&lowercase_first("Token")), "tokens");
+        assert_eq!(pluralize(&lowercase_first("Factory")), "factories");
+        assert_eq!(pluralize(&lowercase_first("Day")), "days");
+        assert_eq!(pluralize(&lowercase_first("Transaction")), "transactions");
+        assert_eq!(pluralize(&lowercase_first("Mint")), "mints");
+        assert_eq!(pluralize(&lowercase_first("Swap")), "swaps");
+        assert_eq!(pluralize(&lowercase_first("Batch")), "batches");
+        assert_eq!(pluralize(&lowercase_first("Box")), "boxes");
+    }
+}
@@ -0,0 +1,54 @@
+use super::IpfsResolverTrait;
+use crate::errors::IpfsError;
+use async_trait::async_trait;
+use reqwest::StatusCode;
+
+/// Fetches content through an HTTP gateway, eg: `https://ipfs.io`
+pub struct GatewayResolver {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl GatewayResolver {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl IpfsResolverTrait for GatewayResolver {
+    async fn cat(&self, path: &str, max_size: usize) -> Result<Vec<u8>, IpfsError> {
+        let mut response = self
+            .client
+            .get(format!("{}/ipfs/{}", self.url, path))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(IpfsError::NotFound(path.to_owned()));
+        }
+
+        response = response.error_for_status()?;
+
+        if response
+            .content_length()
+            .is_some_and(|length| length as usize > max_size)
+        {
+            return Err(IpfsError::FileTooLarge(path.to_owned()));
+        }
+
+        // Content-Length is optional, so the limit is enforced while streaming as well
+        let mut data = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() + chunk.len() > max_size {
+                return Err(IpfsError::FileTooLarge(path.to_owned()));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(data)
+    }
+}
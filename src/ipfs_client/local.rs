@@ -0,0 +1,37 @@
+use super::IpfsResolverTrait;
+use crate::errors::IpfsError;
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+/// Content-addressed directory where every file is stored under its `<cid>[/sub/path]`
+pub struct LocalResolver {
+    dir: PathBuf,
+}
+
+impl LocalResolver {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+#[async_trait]
+impl IpfsResolverTrait for LocalResolver {
+    async fn cat(&self, path: &str, max_size: usize) -> Result<Vec<u8>, IpfsError> {
+        let file = self.dir.join(path);
+        let metadata = tokio::fs::metadata(&file)
+            .await
+            .map_err(|_| IpfsError::NotFound(path.to_owned()))?;
+
+        if !metadata.is_file() {
+            return Err(IpfsError::NotFound(path.to_owned()));
+        }
+
+        if metadata.len() as usize > max_size {
+            return Err(IpfsError::FileTooLarge(path.to_owned()));
+        }
+
+        Ok(tokio::fs::read(&file).await?)
+    }
+}
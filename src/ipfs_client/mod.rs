@@ -0,0 +1,203 @@
+mod gateway;
+mod local;
+
+use crate::config::IpfsConfig;
+use crate::config::IpfsResolverConfig;
+use crate::errors::IpfsError;
+use crate::warn;
+use async_trait::async_trait;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use tiny_keccak::Hasher;
+
+#[async_trait]
+pub trait IpfsResolverTrait {
+    /// Fetch the content at `path`, failing once more than `max_size` bytes are read
+    async fn cat(&self, path: &str, max_size: usize) -> Result<Vec<u8>, IpfsError>;
+}
+
+pub enum IpfsResolver {
+    None,
+    Gateway(gateway::GatewayResolver),
+    Local(local::LocalResolver),
+}
+
+#[async_trait]
+impl IpfsResolverTrait for IpfsResolver {
+    async fn cat(&self, path: &str, max_size: usize) -> Result<Vec<u8>, IpfsError> {
+        match self {
+            IpfsResolver::Gateway(resolver) => resolver.cat(path, max_size).await,
+            IpfsResolver::Local(resolver) => resolver.cat(path, max_size).await,
+            IpfsResolver::None => Err(IpfsError::NotConfigured),
+        }
+    }
+}
+
+pub struct IpfsClient {
+    resolver: IpfsResolver,
+    timeout: Duration,
+    max_file_size: usize,
+    cache_dir: Option<PathBuf>,
+}
+
+impl IpfsClient {
+    fn new(cfg: &IpfsConfig) -> Result<Self, IpfsError> {
+        let resolver = match &cfg.resolver {
+            IpfsResolverConfig::Gateway { url } => {
+                IpfsResolver::Gateway(gateway::GatewayResolver::new(url))
+            }
+            IpfsResolverConfig::Local { dir } => {
+                IpfsResolver::Local(local::LocalResolver::new(dir))
+            }
+        };
+
+        let cache_dir = cfg.cache_dir.as_ref().map(PathBuf::from);
+        if let Some(dir) = &cache_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self {
+            resolver,
+            timeout: Duration::from_millis(cfg.timeout_ms),
+            max_file_size: cfg.max_file_size,
+            cache_dir,
+        })
+    }
+
+    /// Accepts a bare `<cid>[/sub/path]` as well as `/ipfs/<cid>` and `ipfs://<cid>` links
+    fn normalize_path(link: &str) -> String {
+        link.trim()
+            .trim_start_matches("ipfs://")
+            .trim_start_matches("/ipfs/")
+            .trim_matches('/')
+            .to_string()
+    }
+
+    /// Cached files are named after the hash of the full path, so distinct paths never collide
+    fn cache_file(&self, path: &str) -> Option<PathBuf> {
+        let mut hasher = tiny_keccak::Keccak::v256();
+        let mut hash = [0u8; 32];
+        hasher.update(path.as_bytes());
+        hasher.finalize(&mut hash);
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(hex::encode(hash)))
+    }
+
+    pub async fn cat(&self, link: &str) -> Result<Vec<u8>, IpfsError> {
+        let path = Self::normalize_path(link);
+        if path.is_empty() || path.split('/').any(|segment| segment == "..") {
+            return Err(IpfsError::InvalidPath(link.to_owned()));
+        }
+
+        let cache_file = self.cache_file(&path);
+        if let Some(file) = &cache_file {
+            if let Ok(data) = tokio::fs::read(file).await {
+                return Ok(data);
+            }
+        }
+
+        let fetch = self.resolver.cat(&path, self.max_file_size);
+        let data = tokio::time::timeout(self.timeout, fetch)
+            .await
+            .map_err(|_| IpfsError::Timeout(path.clone()))??;
+
+        if let Some(file) = &cache_file {
+            if let Err(e) = tokio::fs::write(file, &data).await {
+                warn!(IpfsClient, "Failed to cache ipfs file"; path => path, error => e.to_string());
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+#[derive(Clone)]
+pub struct IpfsAgent(Rc<RefCell<IpfsClient>>);
+
+unsafe impl Send for IpfsAgent {}
+
+impl IpfsAgent {
+    pub fn new(cfg: Option<&IpfsConfig>) -> Result<Self, IpfsError> {
+        match cfg {
+            Some(cfg) => Ok(Self(Rc::new(RefCell::new(IpfsClient::new(cfg)?)))),
+            None => Ok(Self::new_mock()),
+        }
+    }
+
+    pub fn new_mock() -> Self {
+        let client = IpfsClient {
+            resolver: IpfsResolver::None,
+            timeout: Duration::from_secs(0),
+            max_file_size: 0,
+            cache_dir: None,
+        };
+        Self(Rc::new(RefCell::new(client)))
+    }
+
+    pub fn cat(&self, link: &str) -> Result<Vec<u8>, IpfsError> {
+        let client = self.0.borrow();
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(client.cat(link))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use df_logger::loggers::init_logger;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_resolver_with_cache() {
+        init_logger();
+
+        let root = std::env::temp_dir().join("dfr_ipfs_client_test");
+        std::fs::remove_dir_all(&root).ok();
+        let content_dir = root.join("content");
+        let cache_dir = root.join("cache");
+        std::fs::create_dir_all(content_dir.join("QmDir")).unwrap();
+        std::fs::write(content_dir.join("QmFile"), b"{\"name\":\"token\"}").unwrap();
+        std::fs::write(content_dir.join("QmDir/1.json"), b"{}").unwrap();
+        std::fs::write(content_dir.join("QmDir_1.json"), b"[]").unwrap();
+        std::fs::write(content_dir.join("QmLarge"), vec![0u8; 64]).unwrap();
+
+        let cfg = IpfsConfig {
+            resolver: IpfsResolverConfig::Local {
+                dir: content_dir.to_string_lossy().to_string(),
+            },
+            timeout_ms: 1000,
+            max_file_size: 32,
+            cache_dir: Some(cache_dir.to_string_lossy().to_string()),
+        };
+        let ipfs = IpfsAgent::new(Some(&cfg)).unwrap();
+
+        assert_eq!(ipfs.cat("QmFile").unwrap(), b"{\"name\":\"token\"}");
+        assert_eq!(ipfs.cat("ipfs://QmDir/1.json").unwrap(), b"{}");
+        assert_eq!(ipfs.cat("QmDir_1.json").unwrap(), b"[]");
+        assert!(matches!(
+            ipfs.cat("QmLarge"),
+            Err(IpfsError::FileTooLarge(_))
+        ));
+        assert!(matches!(ipfs.cat("QmMissing"), Err(IpfsError::NotFound(_))));
+        assert!(matches!(
+            ipfs.cat("/ipfs/../secret"),
+            Err(IpfsError::InvalidPath(_))
+        ));
+
+        // Cached content is still served after the source is gone
+        std::fs::remove_file(content_dir.join("QmFile")).unwrap();
+        assert_eq!(ipfs.cat("/ipfs/QmFile").unwrap(), b"{\"name\":\"token\"}");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_unconfigured_resolver() {
+        init_logger();
+        let ipfs = IpfsAgent::new(None).unwrap();
+        assert!(matches!(ipfs.cat("QmFile"), Err(IpfsError::NotConfigured)));
+    }
+}
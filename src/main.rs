@@ -1,18 +1,21 @@
+mod admin;
 mod chain;
 mod common;
 mod components;
 mod config;
 mod database;
 mod errors;
+mod graphql;
+mod ipfs_client;
 // mod logger_macros;
 mod metrics;
 mod proto;
 mod rpc_client;
 mod runtime;
 
+use admin::AdminState;
 use components::*;
 use config::Config;
-use database::DatabaseAgent;
 use df_logger::critical;
 use df_logger::debug;
 use df_logger::error;
@@ -20,11 +23,17 @@ use df_logger::info;
 use df_logger::loggers::init_logger;
 use df_logger::warn;
 use errors::MainError;
+use graphql::run_graphql_server;
+use graphql::GraphQLRoutes;
 use metrics::default_registry;
 use metrics::run_metric_server;
-use rpc_client::RpcAgent;
 use std::fmt::Debug;
 use std::fs;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often the config file is checked for added or removed subgraphs
+const CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
 
 fn welcome() {
     // TODO: include file in build script
@@ -39,6 +48,139 @@ fn handle_task_result<E: Debug>(r: Result<(), E>, task_name: &str) {
     info!(main, format!("{task_name} has finished"); result => format!("{:?}", r));
 }
 
+/// Returns true when the stream has to start over
+async fn apply_admin_commands(
+    host: &mut SubgraphHost,
+    valve: &Valve,
+    admin: &AdminState,
+) -> Result<bool, MainError> {
+    let mut restart = false;
+    for command in admin.take_commands() {
+        restart |= host.apply(command).await?;
+    }
+    if restart {
+        admin.update_status(valve.downloaded(), valve.finished(), host.status());
+    }
+    Ok(restart)
+}
+
+/// Feed every hosted subgraph from a new block stream. Returns true when
+/// the stream has to start over, false once the source has no more blocks
+async fn stream_blocks(
+    host: &mut SubgraphHost,
+    config: &mut Config,
+    valve: &Valve,
+    admin: &AdminState,
+    graphql_routes: &GraphQLRoutes,
+    failures: &mut u32,
+) -> Result<bool, MainError> {
+    let start_block = host.start_block();
+    let block_source = BlockSource::new(config, start_block.clone(), default_registry()).await?;
+    info!(main, "BlockSource ready!"; start_block => start_block);
+
+    let (sender, recv) = kanal::bounded_async(1);
+    // The source drops the sender once it has no more blocks,
+    // the batches still queued are received before the channel reports it
+    let query_blocks = block_source.run(sender, valve.clone());
+
+    let restart = {
+        let process_blocks = async {
+            let mut config_checked_at = Instant::now();
+            // Kept across iterations, dropping it could lose a batch being received
+            let mut next_batch = Box::pin(recv.recv());
+
+            loop {
+                // Admin commands do not wait for the next batch
+                let blocks = tokio::select!(
+                    received = &mut next_batch => match received {
+                        Ok(blocks) => blocks,
+                        Err(_) => break,
+                    },
+                    _ = admin.command_queued() => {
+                        if apply_admin_commands(host, valve, admin).await? {
+                            return Ok(true);
+                        }
+                        continue;
+                    }
+                );
+                next_batch = Box::pin(recv.recv());
+                info!(
+                    main,
+                    "block batch recevied and about to be processed 🚀";
+                    total_block => blocks.len()
+                );
+
+                // Nor for processing to be resumed
+                while admin.is_paused() {
+                    tokio::select!(
+                        _ = admin.wait_while_paused() => (),
+                        _ = admin.command_queued() => {
+                            if apply_admin_commands(host, valve, admin).await? {
+                                return Ok(true);
+                            }
+                        }
+                    );
+                }
+
+                if apply_admin_commands(host, valve, admin).await? {
+                    return Ok(true);
+                }
+
+                let last_block = match blocks.last() {
+                    Some(block) => block.get_block_ptr(),
+                    None => continue,
+                };
+                // Nothing queued behind this batch means the source has caught up
+                host.handle_blocks(blocks, recv.is_empty()).await?;
+                *failures = 0;
+                valve.set_finished(last_block.number);
+                admin.update_status(valve.downloaded(), valve.finished(), host.status());
+
+                if config_checked_at.elapsed() < CONFIG_RELOAD_INTERVAL {
+                    continue;
+                }
+                config_checked_at = Instant::now();
+
+                match Config::try_load() {
+                    Ok(new_config)
+                        if new_config.hosted_subgraphs() != config.hosted_subgraphs() =>
+                    {
+                        info!(main, "Subgraphs changed in config, reloading");
+                        let restart = host.reload(&new_config, last_block.number + 1).await?;
+                        graphql_routes.sync(host.stores());
+                        *config = new_config;
+                        if restart {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        warn!(main, "Failed to reload config"; error => e.to_string());
+                    }
+                }
+            }
+
+            warn!(main, "No more messages returned from block-stream");
+            Ok::<bool, MainError>(false)
+        };
+        tokio::pin!(process_blocks);
+
+        // Only a failing source cuts processing short,
+        // otherwise every batch it has sent gets processed
+        tokio::select!(
+            r = query_blocks => {
+                r?;
+                info!(main, "block-source has finished");
+                process_blocks.await?
+            },
+            r = &mut process_blocks => r?
+        )
+    };
+
+    host.finish().await?;
+    Ok(restart)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_logger();
@@ -50,126 +192,185 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let registry = default_registry();
 
-    let manifest = ManifestAgent::new(&config.subgraph_dir).await?;
-    info!(main, "Manifest loaded!");
-
     let valve = Valve::new(&config.valve, registry);
-    let source_valve = valve.clone();
 
-    let db = DatabaseAgent::new(&config.database, manifest.schemas(), registry).await?;
-    info!(main, "Database ready!");
+    let mut host = SubgraphHost::new(&config).await?;
+    info!(main, "Subgraphs ready!"; number_of_subgraphs => config.hosted_subgraphs().len());
 
-    let mut inspector = Inspector::new(
-        db.get_recent_block_pointers(config.reorg_threshold).await?,
-        manifest.min_start_block(),
-        config.reorg_threshold,
+    // Queries are served from the stores the subgraphs write to
+    let graphql_routes = GraphQLRoutes::default();
+    graphql_routes.sync(host.stores());
+    let graphql_server = run_graphql_server(
+        config.graphql_port.unwrap_or(8000),
+        graphql_routes.clone(),
+        config.subgraph_name.clone(),
     );
-    info!(main, "BlockInspector ready!"; next_start_block => inspector.get_expected_block_number());
+    let metric_port = config.metric_port.unwrap_or(8081);
 
-    let block_source =
-        BlockSource::new(&config, inspector.get_expected_block_number(), registry).await?;
-    info!(main, "BlockSource ready!");
-
-    let filter = DataFilter::new(
-        config.chain.clone(),
-        manifest.datasource_and_templates().into(),
-        manifest.abis(),
-    )?;
-    info!(main, "DataFilter ready!");
-
-    let mut rpc = RpcAgent::new(&config, manifest.abis(), registry).await?;
-    info!(main, "Rpc-Client ready!");
-
-    let mut subgraph = Subgraph::new(&db, &rpc, &manifest, registry);
-    info!(main, "Subgraph ready!");
-
-    let (sender, recv) = kanal::bounded_async(1);
-
-    let query_blocks = block_source.run(sender, source_valve);
-
-    subgraph.create_sources()?;
+    let admin = AdminState::new(config.admin_token.clone());
+    admin.update_status(valve.downloaded(), valve.finished(), host.status());
+    let admin_server = run_metric_server(metric_port, admin.clone());
 
     let main_flow = async move {
-        while let Ok(blocks) = recv.recv().await {
-            info!(
-                main,
-                "block batch recevied and about to be processed 🚀";
-                total_block => blocks.len()
-            );
-
-            let time = std::time::Instant::now();
-            let blocks = filter.filter_multi(blocks)?;
-            let count_blocks = blocks.len();
-            let last_block = blocks.last().map(|b| b.get_block_ptr()).unwrap();
-
-            info!(
-                main,
-                "data scanned & filtered 🔎";
-                exec_time => format!("{:?}", time.elapsed()),
-                count_blocks => count_blocks
-            );
-
-            let time = std::time::Instant::now();
-
-            for block in blocks {
-                let block_ptr = block.get_block_ptr();
-                rpc.set_block_ptr(&block_ptr);
-                manifest.set_block_ptr(&block_ptr);
-
-                match inspector.check_block(block_ptr.clone()) {
-                    BlockInspectionResult::UnexpectedBlock
-                    | BlockInspectionResult::UnrecognizedBlock => {
-                        panic!("Bad block data from source");
-                    }
-                    BlockInspectionResult::BlockAlreadyProcessed
-                    | BlockInspectionResult::MaybeReorg => {
-                        continue;
-                    }
-                    BlockInspectionResult::ForkBlock => {
-                        db.revert_from_block(block_ptr.number).await?;
-                    }
-                    BlockInspectionResult::OkToProceed => (),
-                };
-
-                if subgraph.should_process(&block) {
-                    subgraph.process(block)?;
-                    rpc.clear_block_level_cache();
+        let mut config = config;
+        // Consecutive transient failures, cleared by every processed batch
+        let mut failures = 0;
+
+        loop {
+            let stream = async {
+                if failures > 0 {
+                    host.reset().await?;
                 }
-
-                valve.set_finished(block_ptr.number);
-            }
-
-            let elapsed = time.elapsed();
-
-            db.commit_data(last_block.clone()).await?;
-            db.remove_outdated_snapshots(last_block.number).await?;
-            db.flush_cache().await?;
-
-            if let Some(history_size) = config.block_data_retention {
-                if last_block.number > history_size {
-                    db.clean_data_history(last_block.number - history_size)
-                        .await?;
+                stream_blocks(
+                    &mut host,
+                    &mut config,
+                    &valve,
+                    &admin,
+                    &graphql_routes,
+                    &mut failures,
+                )
+                .await
+            };
+
+            match stream.await {
+                Ok(true) => info!(main, "Restarting block stream"),
+                Ok(false) => return Ok(()),
+                Err(e) if e.is_transient() && config.retry.should_retry(failures) => {
+                    let delay = config.retry.delay(failures);
+                    failures += 1;
+                    error!(
+                        main,
+                        "Main flow failed, resuming from the last committed block";
+                        error => e.to_string(),
+                        attempt => failures,
+                        retry_in => format!("{:?}", delay)
+                    );
+                    tokio::time::sleep(delay).await;
                 }
+                Err(e) => return Err(e),
             }
-
-            info!(
-                main,
-                "BLOCK BATCH PROCESSED DONE  🎉🎉🎉🎉";
-                exec_time => format!("{:?}", elapsed),
-                number_of_blocks => count_blocks,
-                avg_speed => format!("~{:?} blocks/sec", { count_blocks as u64 / elapsed.as_secs() })
-            );
         }
-
-        warn!(main, "No more messages returned from block-stream");
-        Ok::<(), MainError>(())
     };
 
     tokio::select!(
-        r = query_blocks => handle_task_result(r, "block-source"),
         r = main_flow => handle_task_result(r, "Main flow stopped"),
-        _ = tokio::spawn(run_metric_server(config.metric_port.unwrap_or(8081))) => ()
+        _ = tokio::spawn(admin_server) => (),
+        _ = tokio::spawn(graphql_server) => ()
     );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "file", feature = "sqlite"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stream_blocks_commits_last_block() {
+        use crate::common::StartBlock;
+        use crate::database::ExternDBTrait;
+        use crate::proto::ethereum::Block;
+        use crate::proto::ethereum::Header;
+        use df_logger::loggers::init_logger;
+        use figment::providers::Format;
+        use figment::providers::Toml;
+        use figment::Figment;
+
+        init_logger();
+        let subgraph_dir = "../subgraph-testing/packages/uniswap-v3/build";
+        let start_block = match ManifestAgent::new(subgraph_dir)
+            .await
+            .unwrap()
+            .min_start_block()
+        {
+            StartBlock::Number(block_number) => block_number,
+            StartBlock::Latest => 0,
+        };
+        let last_block = start_block + 9;
+
+        let dir = std::env::temp_dir().join("dfr_stream_blocks_test");
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let hash = |n: u64| format!("0x{:064x}", n);
+        let lines = (start_block..=last_block)
+            .map(|block_number| {
+                let block = Block {
+                    chain_id: 1,
+                    block_hash: hash(block_number + 1),
+                    parent_hash: hash(block_number),
+                    block_number,
+                    header: Some(Header {
+                        author: format!("0x{:040x}", 0),
+                        state_root: hash(0),
+                        transactions_root: hash(0),
+                        receipts_root: hash(0),
+                        gas_used: "0".to_string(),
+                        gas_limit: "0".to_string(),
+                        timestamp: "0".to_string(),
+                        difficulty: "0".to_string(),
+                        total_difficulty: "0".to_string(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                serde_json::to_string(&block).unwrap()
+            })
+            .collect::<Vec<_>>();
+        std::fs::write(dir.join("blocks.jsonl"), lines.join("\n")).unwrap();
+
+        // Without backpressure from the valve, the source is done
+        // long before the batches it has queued are processed
+        let mut config: Config = Figment::new()
+            .merge(Toml::string(&format!(
+                r#"
+                chain = "ethereum"
+                subgraph_name = "uniswap-v3"
+                subgraph_dir = "{subgraph_dir}"
+                reorg_threshold = 10
+                rpc_endpoint = "wss://eth.merkle.io"
+
+                [source.file]
+                dir = "{}"
+                format = "jsonl"
+                compression = false
+                query_step = 3
+
+                [database.sqlite]
+                path = "{}"
+
+                [valve]
+                allowed_lag = 1000000000
+                wait_time = 0
+                "#,
+                dir.display(),
+                dir.join("subgraph.db").display()
+            )))
+            .extract()
+            .unwrap();
+
+        let registry = default_registry();
+        let valve = Valve::new(&config.valve, registry);
+        let admin = AdminState::default();
+        let mut host = SubgraphHost::new(&config).await.unwrap();
+        let routes = GraphQLRoutes::default();
+        let mut failures = 0;
+
+        let restart = stream_blocks(
+            &mut host,
+            &mut config,
+            &valve,
+            &admin,
+            &routes,
+            &mut failures,
+        )
+        .await
+        .unwrap();
+        assert!(!restart);
+
+        let (_, db, _) = host.stores().pop().unwrap();
+        let committed = db.load_recent_block_ptrs(1).await.unwrap();
+        assert_eq!(committed.first().map(|ptr| ptr.number), Some(last_block));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,15 +1,63 @@
+use crate::admin::AdminState;
 pub use prometheus::default_registry;
+use prometheus::proto::MetricFamily;
+use prometheus::Registry;
 use prometheus::TextEncoder;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use warp::Filter;
 use warp::Rejection;
 use warp::Reply;
 
+static SUBGRAPH_REGISTRIES: Mutex<BTreeMap<String, Registry>> = Mutex::new(BTreeMap::new());
+
+/// Registry of a hosted subgraph, whose metrics are labeled with the subgraph's name
+pub fn subgraph_registry(name: &str) -> Registry {
+    let labels = HashMap::from([("subgraph".to_string(), name.to_owned())]);
+    let registry = Registry::new_custom(None, Some(labels)).expect("Invalid subgraph name");
+    SUBGRAPH_REGISTRIES
+        .lock()
+        .unwrap()
+        .insert(name.to_owned(), registry.clone());
+    registry
+}
+
+pub fn remove_subgraph_registry(name: &str) {
+    SUBGRAPH_REGISTRIES.lock().unwrap().remove(name);
+}
+
+/// Metrics of the same name from every registry are merged into a single family
+fn gather() -> Vec<MetricFamily> {
+    let registries = SUBGRAPH_REGISTRIES.lock().unwrap();
+    let mut families = BTreeMap::<String, MetricFamily>::new();
+
+    for mut family in prometheus::gather()
+        .into_iter()
+        .chain(registries.values().flat_map(Registry::gather))
+    {
+        match families.entry(family.get_name().to_owned()) {
+            Entry::Vacant(entry) => {
+                entry.insert(family);
+            }
+            Entry::Occupied(mut entry) => {
+                for metric in family.take_metric() {
+                    entry.get_mut().mut_metric().push(metric);
+                }
+            }
+        }
+    }
+
+    families.into_values().collect()
+}
+
 async fn metrics_handler() -> Result<impl Reply, Rejection> {
     let encoder = TextEncoder::new();
     let mut buffer = String::from("");
 
     encoder
-        .encode_utf8(&prometheus::gather(), &mut buffer)
+        .encode_utf8(&gather(), &mut buffer)
         .expect("Failed to encode metrics");
 
     let response = buffer.clone();
@@ -18,8 +66,10 @@ async fn metrics_handler() -> Result<impl Reply, Rejection> {
     Ok(response)
 }
 
-pub async fn run_metric_server(port: u16) {
+/// Serves `/metrics` along with the admin routes
+pub async fn run_metric_server(port: u16, admin: AdminState) {
     crate::info!(Prometheus, format!("Start metrics server at port: {port}"));
     let metrics_route = warp::path!("metrics").and_then(metrics_handler);
-    warp::serve(metrics_route).run(([0, 0, 0, 0], port)).await;
+    let routes = metrics_route.or(admin.routes());
+    warp::serve(routes).run(([0, 0, 0, 0], port)).await;
 }
@@ -4,6 +4,7 @@ use super::types::CallResponse;
 use super::RPCTrait;
 use crate::chain::ethereum::ethereum_call::EthereumContractCall;
 use crate::chain::ethereum::ethereum_call::UnresolvedContractCall;
+use crate::chain::ethereum::transaction::EthereumTransactionReceipt;
 use crate::common::ABIs;
 use crate::common::BlockPtr;
 use crate::error;
@@ -17,6 +18,7 @@ use web3::types::Block;
 use web3::types::BlockId;
 use web3::types::BlockNumber;
 use web3::types::H256;
+use web3::Transport;
 use web3::Web3;
 
 const ETH_CALL_GAS: u32 = 50_000_000;
@@ -34,8 +36,8 @@ impl<T> From<Block<T>> for BlockPtr {
 #[derive(Default)]
 struct CacheRPC(HashMap<CallRequest, CallResponse>);
 
-pub struct EthereumRPC {
-    client: Web3<WebSocket>,
+pub struct EthereumRPC<T: Transport = WebSocket> {
+    client: Web3<T>,
     supports_eip_1898: bool,
     abis: ABIs,
     cache: CacheRPC,
@@ -58,7 +60,9 @@ impl EthereumRPC {
             cache: CacheRPC::default(),
         })
     }
+}
 
+impl<T: Transport> EthereumRPC<T> {
     fn parse_contract_call_request(
         &self,
         call: UnresolvedContractCall,
@@ -206,7 +210,16 @@ impl EthereumRPC {
                     block_number => block_ptr.number,
                     block_hash => block_ptr.hash
                 );
-                RPCError::ContractCallFail
+                match e {
+                    // The node ran the call and rejected it, which is the same on every node.
+                    // Other errors depend on the node, eg: rate limits or missing state
+                    web3::Error::Rpc(e)
+                        if e.code.code() == 3 || e.message.to_lowercase().contains("revert") =>
+                    {
+                        RPCError::Revert(e.message)
+                    }
+                    _ => RPCError::ContractCallFail,
+                }
             })?;
 
         let result = request_data
@@ -254,6 +267,58 @@ impl RPCTrait for EthereumRPC {
             .unwrap()
     }
 
+    async fn get_transaction_receipt(
+        &mut self,
+        hash: H256,
+    ) -> Result<EthereumTransactionReceipt, RPCError> {
+        self.client
+            .eth()
+            .transaction_receipt(hash)
+            .await
+            .map_err(|e| {
+                error!(
+                    EthereumRPC,
+                    "get transaction receipt failed";
+                    error => format!("{:?}", e),
+                    transaction_hash => format!("{:?}", hash)
+                );
+                RPCError::GetTransactionReceiptFail
+            })?
+            // A node lagging behind the block source has no receipt yet, retrying catches up
+            .map(EthereumTransactionReceipt::from)
+            .ok_or(RPCError::GetTransactionReceiptFail)
+    }
+
+    async fn get_call_output(&mut self, hash: H256) -> Result<Vec<u8>, RPCError> {
+        let params = vec![
+            serde_json::json!(hash),
+            serde_json::json!({ "tracer": "callTracer", "tracerConfig": { "onlyTopCall": true } }),
+        ];
+        let trace = self
+            .client
+            .transport()
+            .execute("debug_traceTransaction", params)
+            .await
+            .map_err(|e| {
+                error!(
+                    EthereumRPC,
+                    "trace transaction failed";
+                    error => format!("{:?}", e),
+                    transaction_hash => format!("{:?}", hash)
+                );
+                match e {
+                    // The node does not serve the debug namespace, retrying will not help
+                    web3::Error::Rpc(e) => RPCError::CallTraceUnsupported(e.message),
+                    _ => RPCError::CallTraceFail,
+                }
+            })?;
+        let output = trace
+            .get("output")
+            .and_then(|output| output.as_str())
+            .unwrap_or("0x");
+        hex::decode(output.trim_start_matches("0x")).map_err(|_| RPCError::DataDecodingFail)
+    }
+
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse> {
         self.cache.0.get(call).cloned()
     }
@@ -273,8 +338,90 @@ mod tests {
     use df_logger::loggers::init_logger;
     use ethabi::Address;
     use ethabi::Token;
+    use futures_util::future::BoxFuture;
+    use jsonrpc_core::Call;
+    use jsonrpc_core::ErrorCode;
+    use serde_json::Value;
     use std::fs;
     use std::str::FromStr;
+    use web3::helpers::build_request;
+    use web3::RequestId;
+
+    /// Fails every request with the same JSON-RPC error
+    #[derive(Debug, Clone)]
+    struct MockProvider(jsonrpc_core::Error);
+
+    impl Transport for MockProvider {
+        type Out = BoxFuture<'static, web3::error::Result<Value>>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+            (1, build_request(1, method, params))
+        }
+
+        fn send(&self, _id: RequestId, _request: Call) -> Self::Out {
+            let error = self.0.clone();
+            Box::pin(async move { Err(web3::Error::Rpc(error)) })
+        }
+    }
+
+    async fn call_with_error(code: i64, message: &str) -> RPCError {
+        let mut abis = ABIs::default();
+        abis.insert(
+            "ERC20".to_string(),
+            serde_json::json!([{
+                "constant": true,
+                "inputs": [],
+                "name": "symbol",
+                "outputs": [{ "name": "", "type": "string" }],
+                "stateMutability": "view",
+                "type": "function"
+            }]),
+        );
+        let rpc = EthereumRPC {
+            client: Web3::new(MockProvider(jsonrpc_core::Error {
+                code: ErrorCode::ServerError(code),
+                message: message.to_string(),
+                data: None,
+            })),
+            supports_eip_1898: false,
+            abis,
+            cache: CacheRPC::default(),
+        };
+        let data = UnresolvedContractCall {
+            contract_name: "ERC20".to_string(),
+            contract_address: Address::zero(),
+            function_name: "symbol".to_string(),
+            function_signature: None,
+            function_args: vec![],
+        };
+        let block_ptr = BlockPtr {
+            number: 1,
+            ..Default::default()
+        };
+        rpc.handle_contract_call(data, block_ptr).await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_rpc_call_errors() {
+        init_logger();
+
+        let error = call_with_error(3, "execution reverted").await;
+        assert!(matches!(error, RPCError::Revert(_)));
+        let error =
+            call_with_error(-32000, "VM Exception while processing transaction: revert").await;
+        assert!(matches!(error, RPCError::Revert(_)));
+
+        // Errors of the node itself are retried instead of handing `null` to the mapping
+        for message in [
+            "header not found",
+            "missing trie node",
+            "rate limit exceeded",
+        ] {
+            let error = call_with_error(-32000, message).await;
+            assert!(matches!(error, RPCError::ContractCallFail));
+            assert!(error.is_transient());
+        }
+    }
 
     #[tokio::test]
     async fn test_rpc_call_symbol() {
@@ -3,6 +3,7 @@ mod metrics;
 mod types;
 
 use self::metrics::RpcMetrics;
+use crate::chain::ethereum::transaction::EthereumTransactionReceipt;
 use crate::common::ABIs;
 use crate::common::BlockPtr;
 use crate::common::Chain;
@@ -12,13 +13,22 @@ use async_trait::async_trait;
 use prometheus::Registry;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::future::Future;
 use std::rc::Rc;
+use std::time::Duration;
 pub use types::*;
+use web3::types::H256;
 
 #[async_trait]
 pub trait RPCTrait {
     async fn handle_request(&mut self, call: CallRequestContext) -> Result<CallResponse, RPCError>;
     async fn get_latest_block(&mut self) -> Result<BlockPtr, RPCError>;
+    async fn get_transaction_receipt(
+        &mut self,
+        hash: H256,
+    ) -> Result<EthereumTransactionReceipt, RPCError>;
+    /// Raw return data of the top-level call of a transaction
+    async fn get_call_output(&mut self, hash: H256) -> Result<Vec<u8>, RPCError>;
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse>;
     fn cache_set(&mut self, call: &CallRequest, result: &CallResponse);
 }
@@ -48,6 +58,23 @@ impl RPCTrait for RPCChain {
         }
     }
 
+    async fn get_transaction_receipt(
+        &mut self,
+        hash: H256,
+    ) -> Result<EthereumTransactionReceipt, RPCError> {
+        match self {
+            RPCChain::Ethereum(client) => client.get_transaction_receipt(hash).await,
+            RPCChain::None => Err(RPCError::InvalidChain),
+        }
+    }
+
+    async fn get_call_output(&mut self, hash: H256) -> Result<Vec<u8>, RPCError> {
+        match self {
+            RPCChain::Ethereum(client) => client.get_call_output(hash).await,
+            RPCChain::None => Err(RPCError::InvalidChain),
+        }
+    }
+
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse> {
         match self {
             RPCChain::Ethereum(client) => client.cache_get(call),
@@ -68,6 +95,7 @@ pub struct RpcClient {
     block_ptr: BlockPtr,
     cache_by_block: HashMap<CallRequestContext, CallResponse>,
     metrics: RpcMetrics,
+    timeout: Option<Duration>,
 }
 
 impl RpcClient {
@@ -83,6 +111,7 @@ impl RpcClient {
             block_ptr: BlockPtr::default(),
             cache_by_block: HashMap::new(),
             metrics: RpcMetrics::new(registry),
+            timeout: config.timeout.rpc_ms.map(Duration::from_millis),
         })
     }
 
@@ -105,7 +134,8 @@ impl RpcClient {
         }
 
         let timer = self.metrics.rpc_request_duration.start_timer();
-        let result = self.rpc_client.handle_request(call_context.clone()).await?;
+        let request = self.rpc_client.handle_request(call_context.clone());
+        let result = with_timeout(self.timeout, request).await?;
         self.cache_by_block.insert(call_context, result.clone());
         timer.stop_and_record();
 
@@ -119,6 +149,25 @@ impl RpcClient {
         Ok(result)
     }
 
+    pub async fn get_transaction_receipt(
+        &mut self,
+        hash: H256,
+    ) -> Result<EthereumTransactionReceipt, RPCError> {
+        let timer = self.metrics.rpc_request_duration.start_timer();
+        let request = self.rpc_client.get_transaction_receipt(hash);
+        let result = with_timeout(self.timeout, request).await?;
+        timer.stop_and_record();
+        Ok(result)
+    }
+
+    pub async fn get_call_output(&mut self, hash: H256) -> Result<Vec<u8>, RPCError> {
+        let timer = self.metrics.rpc_request_duration.start_timer();
+        let request = self.rpc_client.get_call_output(hash);
+        let result = with_timeout(self.timeout, request).await?;
+        timer.stop_and_record();
+        Ok(result)
+    }
+
     pub fn set_block_ptr(&mut self, block_ptr: &BlockPtr) {
         self.block_ptr = block_ptr.clone();
     }
@@ -128,6 +177,18 @@ impl RpcClient {
     }
 }
 
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, RPCError>>,
+) -> Result<T, RPCError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| RPCError::Timeout)?,
+        None => request.await,
+    }
+}
+
 #[derive(Clone)]
 pub struct RpcAgent(Rc<RefCell<RpcClient>>);
 
@@ -145,6 +206,7 @@ impl RpcAgent {
             block_ptr: BlockPtr::default(),
             cache_by_block: HashMap::new(),
             metrics: RpcMetrics::new(registry),
+            timeout: None,
         };
         RpcAgent(Rc::new(RefCell::new(rpc_client)))
     }
@@ -156,6 +218,23 @@ impl RpcAgent {
         })
     }
 
+    pub fn get_transaction_receipt(
+        &mut self,
+        hash: H256,
+    ) -> Result<EthereumTransactionReceipt, RPCError> {
+        let mut rpc = self.0.borrow_mut();
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(rpc.get_transaction_receipt(hash))
+        })
+    }
+
+    pub fn get_call_output(&mut self, hash: H256) -> Result<Vec<u8>, RPCError> {
+        let mut rpc = self.0.borrow_mut();
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(rpc.get_call_output(hash))
+        })
+    }
+
     pub fn set_block_ptr(&mut self, block_ptr: &BlockPtr) {
         let mut rpc = self.0.borrow_mut();
         rpc.set_block_ptr(block_ptr);
@@ -195,6 +274,7 @@ pub mod tests {
             block_ptr,
             cache_by_block: HashMap::new(),
             metrics: RpcMetrics::new(&Registry::new()),
+            timeout: None,
         };
 
         RpcAgent(Rc::new(RefCell::new(client)))
@@ -15,9 +15,11 @@ use typed_array::TypedArray;
 
 use crate::errors::AscError;
 use crate::impl_asc_type_struct;
+use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscHeap;
 use crate::runtime::asc::base::AscIndexId;
 use crate::runtime::asc::base::AscPtr;
+use crate::runtime::asc::base::AscType;
 use crate::runtime::asc::base::AscValue;
 use crate::runtime::asc::base::FromAscObj;
 use crate::runtime::asc::base::IndexForAscTypeId;
@@ -93,3 +95,35 @@ impl AscIndexId for AscResult<AscPtr<AscJson>, bool> {
 impl AscIndexId for AscResult<AscPtr<AscEnum<JsonValueKind>>, bool> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ResultJsonValueBool;
 }
+
+impl<V, VAsc, E, EAsc> ToAscObj<AscResult<AscPtr<VAsc>, EAsc>> for Result<V, E>
+where
+    V: ToAscObj<VAsc>,
+    VAsc: AscType + AscIndexId,
+    AscWrapped<AscPtr<VAsc>>: AscIndexId,
+    E: Clone + Into<EAsc>,
+    EAsc: AscValue,
+    AscWrapped<EAsc>: AscIndexId,
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscResult<AscPtr<VAsc>, EAsc>, AscError> {
+        Ok(match self {
+            Ok(value) => {
+                let inner = asc_new(heap, value)?;
+                AscResult {
+                    value: asc_new(heap, &AscWrapped { inner })?,
+                    error: AscPtr::null(),
+                }
+            }
+            Err(e) => {
+                let inner = e.clone().into();
+                AscResult {
+                    value: AscPtr::null(),
+                    error: asc_new(heap, &AscWrapped { inner })?,
+                }
+            }
+        })
+    }
+}
@@ -265,6 +265,53 @@ impl ToAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
     }
 }
 
+impl FromAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
+    fn from_asc_obj<H: AscHeap + ?Sized>(
+        asc_enum: AscEnum<JsonValueKind>,
+        heap: &H,
+        depth: usize,
+    ) -> Result<Self, AscError> {
+        use serde_json::Value;
+
+        let payload = asc_enum.payload;
+        Ok(match asc_enum.kind {
+            JsonValueKind::Null => Value::Null,
+            JsonValueKind::Bool => Value::Bool(bool::from(payload)),
+            JsonValueKind::Number => {
+                let ptr: AscPtr<AscString> = AscPtr::from(payload);
+                let number: String = asc_get(heap, ptr, depth)?;
+                let number = number
+                    .parse()
+                    .map_err(|e: serde_json::Error| AscError::Plain(e.to_string()))?;
+                Value::Number(number)
+            }
+            JsonValueKind::String => {
+                let ptr: AscPtr<AscString> = AscPtr::from(payload);
+                Value::String(asc_get(heap, ptr, depth)?)
+            }
+            JsonValueKind::Array => {
+                let ptr: AscEnumArray<JsonValueKind> = AscPtr::from(payload);
+                Value::Array(asc_get(heap, ptr, depth)?)
+            }
+            JsonValueKind::Object => {
+                let ptr: AscPtr<AscJson> = AscPtr::from(payload);
+                Value::Object(asc_get(heap, ptr, depth)?)
+            }
+        })
+    }
+}
+
+impl FromAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
+    fn from_asc_obj<H: AscHeap + ?Sized>(
+        asc_map: AscJson,
+        heap: &H,
+        depth: usize,
+    ) -> Result<Self, AscError> {
+        let entries: Vec<(String, serde_json::Value)> = asc_get(heap, asc_map.entries, depth)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
 impl ToAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
     fn to_asc_obj<H: AscHeap + ?Sized>(&self, heap: &mut H) -> Result<AscJson, AscError> {
         Ok(AscTypedMap {
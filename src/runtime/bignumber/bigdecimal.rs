@@ -153,3 +153,35 @@ impl Div for BigDecimal {
         Self::from(self.0.div(other.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected values are the outputs of graph-node for the same operations
+    #[test]
+    fn test_graph_node_parity() {
+        let one_third = BigDecimal::from(1) / BigDecimal::from(3);
+        assert_eq!(
+            one_third.to_string(),
+            "0.3333333333333333333333333333333333"
+        );
+
+        let two_thirds = BigDecimal::from(2) / BigDecimal::from(3);
+        assert_eq!(
+            two_thirds.to_string(),
+            "0.6666666666666666666666666666666667"
+        );
+
+        // bigInt.dividedByDecimal
+        let x = BigDecimal::new(BigInt::from(10), 0);
+        assert_eq!((x / BigDecimal::from_str("4").unwrap()).to_string(), "2.5");
+
+        assert_eq!(BigDecimal::from_str("1.500").unwrap().to_string(), "1.5");
+        assert_eq!(BigDecimal::from_str("-0.000").unwrap(), BigDecimal::zero());
+        assert_eq!(
+            BigDecimal::from_str("0.1").unwrap() + BigDecimal::from_str("0.2").unwrap(),
+            BigDecimal::from_str("0.3").unwrap()
+        );
+    }
+}
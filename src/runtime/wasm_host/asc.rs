@@ -1,22 +1,29 @@
+use crate::common::RawEntity;
 use crate::errors::AscError;
 use crate::runtime::asc::base::AscHeap;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::base::IndexForAscTypeId;
+use crate::runtime::wasm_host::gas::metered_points;
 use crate::runtime::wasm_host::Env;
 use df_logger::log;
 use semver::Version;
 use std::mem::MaybeUninit;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
 use wasmer::AsStoreMut;
 use wasmer::AsStoreRef;
 use wasmer::FromToNativeWasmType;
+use wasmer::FunctionEnv;
 use wasmer::FunctionEnvMut;
 use wasmer::Instance;
 use wasmer::Memory;
 use wasmer::Store;
 use wasmer::TypedFunction;
 use wasmer::Value;
+use wasmer_middlewares::metering::get_remaining_points;
+use wasmer_middlewares::metering::set_remaining_points;
+use wasmer_middlewares::metering::MeteringPoints;
 
 pub type ArenaStartPtr = Arc<Mutex<i32>>;
 
@@ -118,12 +125,14 @@ impl AscHeap for FunctionEnvMut<'_, Env> {
 /// We should find a way to unify them
 pub struct AscHost {
     pub store: Store,
+    pub env: FunctionEnv<Env>,
     pub instance: Instance,
     pub memory: Memory,
     pub api_version: Version,
     pub id_of_type: Option<TypedFunction<u32, u32>>,
     pub memory_allocate: Option<TypedFunction<i32, i32>>,
     pub arena_start_ptr: ArenaStartPtr,
+    pub gas_limit: u64,
 }
 
 impl AscHost {
@@ -131,6 +140,51 @@ impl AscHost {
         let arena_start_ptr = self.arena_start_ptr.lock().unwrap();
         *arena_start_ptr
     }
+
+    /// Point the host at the template instance about to be handled,
+    /// so `dataSource.address` & `dataSource.context` reflect that instance
+    pub fn set_datasource_instance(&mut self, address: Option<String>, context: Option<RawEntity>) {
+        let env = self.env.as_mut(&mut self.store);
+        env.address = address;
+        env.context = context;
+    }
+
+    /// Every handler call starts with the full gas limit, metered up to its deadline
+    pub fn reset_gas(&mut self) {
+        let env = self.env.as_mut(&mut self.store);
+        env.gas_left = self.gas_limit;
+        let metered = metered_points(env);
+        env.metered = metered;
+        set_remaining_points(&mut self.store, &self.instance, metered);
+    }
+
+    fn out_of_points(&mut self) -> bool {
+        matches!(
+            get_remaining_points(&mut self.store, &self.instance),
+            MeteringPoints::Exhausted | MeteringPoints::Remaining(0)
+        )
+    }
+
+    /// The handler used up its gas, rather than being stopped at its deadline
+    pub fn gas_exhausted(&mut self) -> bool {
+        let env = self.env.as_ref(&self.store);
+        let capped_by_deadline = env.metered < env.gas_left;
+        self.out_of_points() && !capped_by_deadline
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        let env = self.env.as_mut(&mut self.store);
+        env.deadline = deadline;
+        env.timed_out = false;
+    }
+
+    /// Whether a trapped call was stopped by its deadline, a call that returned is never a timeout
+    pub fn timed_out(&mut self) -> bool {
+        let env = self.env.as_ref(&self.store);
+        let capped_by_deadline = env.metered < env.gas_left;
+        let timed_out = env.timed_out || env.deadline_passed();
+        timed_out || (capped_by_deadline && self.out_of_points())
+    }
 }
 
 impl AscHeap for AscHost {
@@ -53,9 +53,9 @@ pub fn big_decimal_divided_by(
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     if y == BigDecimal::from(0) {
-        return Err(RuntimeError::new(
-            "Divide by zero | big_decimal_divided_by error!",
-        ));
+        return Err(RuntimeError::new(format!(
+            "attempted to divide BigDecimal `{x}` by zero"
+        )));
     }
 
     let result = x / y;
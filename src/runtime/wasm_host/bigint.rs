@@ -1,3 +1,5 @@
+use super::gas;
+use super::gas::charge_gas;
 use super::Env;
 use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
@@ -19,6 +21,7 @@ pub fn big_int_plus(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     let result = x + y;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -31,6 +34,7 @@ pub fn big_int_minus(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     let result = x - y;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -43,6 +47,7 @@ pub fn big_int_times(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     let result = x * y;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -55,8 +60,11 @@ pub fn big_int_divided_by(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     if y == BigInt::from(0) {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!(
+            "attempted to divide BigInt `{x}` by zero"
+        )));
     }
     let result = x / y;
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -70,9 +78,7 @@ pub fn big_int_bit_or(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
-    if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
-    }
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     let result = x | y;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -85,9 +91,7 @@ pub fn big_int_bit_and(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
-    if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
-    }
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     let result = x & y;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -101,7 +105,9 @@ pub fn big_int_divided_by_decimal(
     let x: BigDecimal = BigDecimal::new(asc_get(&fenv, bigint_x_ptr, 0)?, 0);
     let y: BigDecimal = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!(
+            "attempted to divide BigDecimal `{x}` by zero"
+        )));
     }
     let result = x / y;
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -115,8 +121,11 @@ pub fn big_int_mod(
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + y.bits()))?;
     if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!(
+            "attempted to calculate the remainder of `{x}` with a divisor of zero"
+        )));
     }
     // NOTE: 20 %-9 = 2 => Đéo hiểu tại sao = 2
     let result = x.rem(y);
@@ -130,6 +139,9 @@ pub fn big_int_pow(
     exp: i32,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
+    let exp = u8::try_from(exp)
+        .map_err(|_| RuntimeError::new("Exponent must be a positive integer less than 256"))?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() * exp as usize))?;
     let result = x.pow(exp as u32)?;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -143,6 +155,7 @@ pub fn big_int_left_shift(
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let bits = u8::try_from(exp)
         .map_err(|_| RuntimeError::new("Exponent must be a positive integer less than 256"))?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + bits as usize))?;
     let result = x << bits;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -156,6 +169,7 @@ pub fn big_int_right_shift(
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let bits = u8::try_from(exp)
         .map_err(|_| RuntimeError::new("Exponent must be a positive integer less than 256"))?;
+    charge_gas(&mut fenv, gas::big_math(x.bits() + bits as usize))?;
     let result = x >> bits;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
@@ -174,13 +188,81 @@ pub fn big_int_from_string(
 #[cfg(test)]
 mod tests {
     use super::super::test::*;
+    use super::super::Env;
     use crate::host_fn_test;
     use crate::runtime::asc::base::asc_get;
+    use crate::runtime::asc::base::asc_new;
     use crate::runtime::asc::base::AscPtr;
     use crate::runtime::asc::bignumber::AscBigDecimal;
     use crate::runtime::asc::bignumber::AscBigInt;
     use crate::runtime::bignumber::bigdecimal::BigDecimal;
     use crate::runtime::bignumber::bigint::BigInt;
+    use prometheus::Registry;
+    use rstest::rstest;
+    use semver::Version;
+    use std::str::FromStr;
+    use wasmer::FunctionEnvMut;
+    use wasmer::RuntimeError;
+
+    type BinaryFn = fn(
+        FunctionEnvMut<Env>,
+        AscPtr<AscBigInt>,
+        AscPtr<AscBigInt>,
+    ) -> Result<AscPtr<AscBigInt>, RuntimeError>;
+
+    fn binary(fenv: &mut FunctionEnvMut<Env>, func: BinaryFn, x: &str, y: &str) -> String {
+        let x = asc_new(fenv, &BigInt::from_str(x).unwrap()).unwrap();
+        let y = asc_new(fenv, &BigInt::from_str(y).unwrap()).unwrap();
+        let result = func(fenv.as_mut(), x, y).unwrap();
+        let result: BigInt = asc_get(&*fenv, result, 0).unwrap();
+        result.to_string()
+    }
+
+    fn pow(fenv: &mut FunctionEnvMut<Env>, x: &str, exp: i32) -> Result<String, RuntimeError> {
+        let x = asc_new(fenv, &BigInt::from_str(x).unwrap()).unwrap();
+        let result = super::big_int_pow(fenv.as_mut(), x, exp)?;
+        let result: BigInt = asc_get(&*fenv, result, 0).unwrap();
+        Ok(result.to_string())
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_big_int_bitwise_with_zero(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+        let bit_or: BinaryFn = super::big_int_bit_or;
+        let bit_and: BinaryFn = super::big_int_bit_and;
+
+        assert_eq!(binary(&mut fenv, bit_or, "0", "0"), "0");
+        assert_eq!(binary(&mut fenv, bit_or, "0", "1960"), "1960");
+        assert_eq!(binary(&mut fenv, bit_or, "1960", "0"), "1960");
+        assert_eq!(binary(&mut fenv, bit_and, "0", "0"), "0");
+        assert_eq!(binary(&mut fenv, bit_and, "0", "1960"), "0");
+        assert_eq!(binary(&mut fenv, bit_and, "1960", "0"), "0");
+        assert_eq!(binary(&mut fenv, bit_or, "1000", "1960"), "2040");
+        assert_eq!(binary(&mut fenv, bit_and, "1000", "1960"), "960");
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_big_int_pow_bounds(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        assert_eq!(pow(&mut fenv, "7", 0).unwrap(), "1");
+        assert_eq!(pow(&mut fenv, "0", 0).unwrap(), "1");
+        assert_eq!(
+            pow(&mut fenv, "-2", 255).unwrap(),
+            "-57896044618658097711785492504343953926634992332820282019728792003956564819968"
+        );
+        assert!(pow(&mut fenv, "2", 256).is_err());
+        assert!(pow(&mut fenv, "2", -1).is_err());
+        assert!(pow(&mut fenv, "2", i32::MIN).is_err());
+    }
 
     host_fn_test!("TestTypes", test_big_int_plus, host, ptr {
         let asc_ptr = AscPtr::<AscBigInt>::new(ptr);
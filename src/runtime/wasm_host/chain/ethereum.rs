@@ -3,6 +3,8 @@ use crate::chain::ethereum::ethereum_call::AscUnresolvedContractCall;
 use crate::chain::ethereum::ethereum_call::AscUnresolvedContractCallV4;
 use crate::chain::ethereum::ethereum_call::UnresolvedContractCall;
 use crate::errors::AscError;
+use crate::errors::NonDeterministicError;
+use crate::errors::RPCError;
 use crate::rpc_client::CallRequest;
 use crate::rpc_client::CallResponse;
 use crate::runtime::asc::base::asc_get;
@@ -12,12 +14,15 @@ use crate::runtime::asc::native_types::r#enum::AscEnum;
 use crate::runtime::asc::native_types::r#enum::AscEnumArray;
 use crate::runtime::asc::native_types::string::AscString;
 use crate::runtime::asc::native_types::Uint8Array;
+use crate::runtime::wasm_host::gas;
+use crate::runtime::wasm_host::gas::charge_gas;
 use crate::runtime::wasm_host::Env;
 use ethabi::decode;
 use ethabi::param_type::Reader;
 use semver::Version;
 use tiny_keccak::Hasher;
 use wasmer::FunctionEnvMut;
+use wasmer::RuntimeError;
 
 pub fn ethereum_encode(
     mut fenv: FunctionEnvMut<Env>,
@@ -68,7 +73,8 @@ pub fn crypto_keccak_256(
 pub fn ethereum_call(
     mut fenv: FunctionEnvMut<Env>,
     wasm_ptr: i32,
-) -> Result<AscEnumArray<EthereumValueKind>, AscError> {
+) -> Result<AscEnumArray<EthereumValueKind>, RuntimeError> {
+    charge_gas(&mut fenv, gas::ETHEREUM_CALL)?;
     let asc_ptr = wasm_ptr as u32;
     let call: UnresolvedContractCall = if fenv.data().api_version >= Version::new(0, 0, 4) {
         asc_get::<_, AscUnresolvedContractCallV4, _>(&fenv, asc_ptr.into(), 0)?
@@ -84,14 +90,26 @@ pub fn ethereum_call(
             let asc_result = asc_new(&mut fenv, tokens.as_slice())?;
             Ok(asc_result)
         }
+        // Unlike a reverted call, a timeout is not deterministic and must not reach the mapping
+        Err(RPCError::Timeout) => {
+            fenv.data_mut().timed_out = true;
+            Err(RuntimeError::new("ethereum.call timed out"))
+        }
+        // The node could not be reached, so the call never actually ran
+        Err(e @ (RPCError::ContractCallFail | RPCError::GetLatestBlockFail)) => {
+            Err(NonDeterministicError::from(e).into())
+        }
         Err(_) => Ok(AscPtr::null()),
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::chain::ethereum::block::AscEthereumBlock;
     use crate::chain::ethereum::block::EthereumBlockData;
+    use crate::chain::ethereum::event::AscEthereumEvent;
     use crate::chain::ethereum::event::EthereumEventData;
+    use crate::chain::ethereum::transaction::AscEthereumTransaction;
     use crate::chain::ethereum::transaction::EthereumTransactionData;
     use crate::host_fn_test;
     use crate::rpc_client::tests::create_rpc_client_test;
@@ -129,7 +147,8 @@ mod test {
             address: Address::from_str("0x388c818ca8b9251b393131c08a736a67ccb19297").unwrap(),
             ..Default::default()
         };
-        let asc_event = asc_new(&mut host, &event).unwrap();
+        let asc_event: AscPtr<AscEthereumEvent<AscEthereumTransaction, AscEthereumBlock>> =
+            asc_new(&mut host, &event).unwrap();
         let event_ptr = asc_event.wasm_ptr() as i32;
 
         [Value::I32(block_ptr), Value::I32(tx_ptr), Value::I32(event_ptr)]
@@ -1,3 +1,4 @@
+use crate::common::RawEntity;
 use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscPtr;
@@ -18,18 +19,25 @@ pub fn datasource_create(
     let source_params: Vec<String> = asc_get(&fenv, params_ptr, 0)?;
     let env = fenv.data_mut();
     env.manifest
-        .create_datasource(&source_name, source_params)
-        .unwrap();
+        .create_datasource(&source_name, source_params, None)
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
     Ok(())
 }
 
 pub fn datasource_create_context(
-    _fenv: FunctionEnvMut<Env>,
-    _name_ptr: AscPtr<AscString>,
-    _params_ptr: AscPtr<Array<AscPtr<AscString>>>,
-    _context_ptr: AscPtr<AscEntity>,
+    mut fenv: FunctionEnvMut<Env>,
+    name_ptr: AscPtr<AscString>,
+    params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+    context_ptr: AscPtr<AscEntity>,
 ) -> Result<(), RuntimeError> {
-    todo!()
+    let source_name: String = asc_get(&fenv, name_ptr, 0)?;
+    let source_params: Vec<String> = asc_get(&fenv, params_ptr, 0)?;
+    let context: RawEntity = asc_get(&fenv, context_ptr, 0)?;
+    let env = fenv.data_mut();
+    env.manifest
+        .create_datasource(&source_name, source_params, Some(context))
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    Ok(())
 }
 
 pub fn datasource_address(
@@ -39,7 +47,9 @@ pub fn datasource_address(
         .data()
         .address
         .as_ref()
-        .map(|a| a.as_bytes().to_vec())
+        .map(|a| hex::decode(a.trim_start_matches("0x")))
+        .transpose()
+        .map_err(|e| RuntimeError::new(format!("Invalid datasource address: {e}")))?
         .unwrap_or(vec![]);
     let address_ptr = asc_new(&mut fenv, address.as_slice())?;
     Ok(address_ptr)
@@ -53,6 +63,40 @@ pub fn datasource_network(
     Ok(network_ptr)
 }
 
-pub fn datasource_context(_fenv: FunctionEnvMut<Env>) -> Result<AscPtr<AscEntity>, RuntimeError> {
-    todo!()
+pub fn datasource_context(
+    mut fenv: FunctionEnvMut<Env>,
+) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    let context = fenv.data().context.clone().unwrap_or_default();
+    let context_ptr = asc_new(&mut fenv, &context.into_iter().collect::<Vec<_>>())?;
+    Ok(context_ptr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::*;
+    use super::*;
+    use prometheus::Registry;
+    use rstest::rstest;
+    use semver::Version;
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_datasource_create_and_address(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        host.set_datasource_instance(Some(address.to_string()), None);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        // An unknown template fails the handler instead of panicking
+        let name: AscPtr<AscString> = asc_new(&mut fenv, "Pool").unwrap();
+        let params: AscPtr<Array<AscPtr<AscString>>> =
+            asc_new(&mut fenv, [address.to_string()].as_slice()).unwrap();
+        assert!(datasource_create(fenv.as_mut(), name, params).is_err());
+
+        let address_ptr = datasource_address(fenv.as_mut()).unwrap();
+        let bytes: Vec<u8> = asc_get(&fenv, address_ptr, 0).unwrap();
+        assert_eq!(bytes, hex::decode(&address[2..]).unwrap());
+    }
 }
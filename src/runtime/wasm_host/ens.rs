@@ -0,0 +1,41 @@
+use super::Env;
+use crate::runtime::asc::base::asc_get;
+use crate::runtime::asc::base::AscPtr;
+use crate::runtime::asc::native_types::string::AscString;
+use wasmer::FunctionEnvMut;
+use wasmer::RuntimeError;
+
+/// graph-node resolves the hash against an ENS rainbow-table imported into its store.
+/// No such table is loaded here, so this behaves like graph-node with an empty table
+/// and every name is reported to the mapping as unknown (null)
+pub fn ens_name_by_hash(
+    fenv: FunctionEnvMut<Env>,
+    hash_ptr: AscPtr<AscString>,
+) -> Result<AscPtr<AscString>, RuntimeError> {
+    let _hash: String = asc_get(&fenv, hash_ptr, 0)?;
+    Ok(AscPtr::null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::*;
+    use super::*;
+    use crate::runtime::asc::base::asc_new;
+    use prometheus::Registry;
+    use rstest::rstest;
+    use semver::Version;
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_ens_name_by_hash(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        let hash = "0x4a8a6ba1d5b0d3c8f4e1f1b0e7d8c4c1a3e1f0d2b5c6a7e8f9d0c1b2a3e4f5d6";
+        let hash = asc_new(&mut fenv, hash).unwrap();
+        let name = ens_name_by_hash(fenv.as_mut(), hash).unwrap();
+        assert!(name.is_null());
+    }
+}
@@ -0,0 +1,75 @@
+use super::Env;
+use std::time::Instant;
+use wasmer::wasmparser::Operator;
+use wasmer::FunctionEnvMut;
+use wasmer::RuntimeError;
+use wasmer_middlewares::metering::get_remaining_points;
+use wasmer_middlewares::metering::set_remaining_points;
+use wasmer_middlewares::metering::MeteringPoints;
+
+pub const STORE_GET: u64 = 50_000;
+pub const STORE_SET: u64 = 100_000;
+pub const STORE_REMOVE: u64 = 100_000;
+pub const ETHEREUM_CALL: u64 = 5_000_000;
+const BIG_MATH_BASE: u64 = 100;
+
+/// Every wasm instruction costs the same, so the gas used by a handler is deterministic
+pub fn operator_cost(_operator: &Operator) -> u64 {
+    1
+}
+
+/// Arithmetic on big numbers is charged by the size of its operands
+pub fn big_math(bits: usize) -> u64 {
+    BIG_MATH_BASE + bits as u64
+}
+
+/// Points metered until the next host call: the gas left to the handler, capped by
+/// the gas of the time left before its deadline. A handler looping without calling
+/// the host then runs out of points shortly after its deadline
+pub fn metered_points(env: &Env) -> u64 {
+    match env.deadline {
+        Some(deadline) => {
+            let time_left = deadline.saturating_duration_since(Instant::now());
+            let gas_of_time_left = (time_left.as_millis() as u64).saturating_mul(env.gas_per_ms);
+            env.gas_left.min(gas_of_time_left)
+        }
+        None => env.gas_left,
+    }
+}
+
+/// Charge a host call against the gas left to the running handler.
+/// This is also where a handler running past its deadline gets interrupted
+pub fn charge_gas(fenv: &mut FunctionEnvMut<Env>, cost: u64) -> Result<(), RuntimeError> {
+    let (env, mut store) = fenv.data_and_store_mut();
+    if env.deadline_passed() {
+        env.timed_out = true;
+        return Err(RuntimeError::new("Handler deadline exceeded"));
+    }
+
+    let instance = match env.instance.clone() {
+        Some(instance) => instance,
+        // Still instantiating, nothing to charge yet
+        None => return Ok(()),
+    };
+
+    let points = match get_remaining_points(&mut store, &instance) {
+        MeteringPoints::Remaining(points) => points,
+        MeteringPoints::Exhausted => 0,
+    };
+    env.gas_left = env
+        .gas_left
+        .saturating_sub(env.metered.saturating_sub(points));
+
+    if env.gas_left < cost {
+        env.gas_left = 0;
+        env.metered = 0;
+        set_remaining_points(&mut store, &instance, 0);
+        return Err(RuntimeError::new("Gas limit exceeded"));
+    }
+
+    env.gas_left -= cost;
+    let metered = metered_points(env);
+    env.metered = metered;
+    set_remaining_points(&mut store, &instance, metered);
+    Ok(())
+}
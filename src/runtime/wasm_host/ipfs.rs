@@ -0,0 +1,94 @@
+use super::Env;
+use crate::errors::NonDeterministicError;
+use crate::runtime::asc::base::asc_get;
+use crate::runtime::asc::base::asc_new;
+use crate::runtime::asc::base::AscPtr;
+use crate::runtime::asc::native_types::array::Array;
+use crate::runtime::asc::native_types::r#enum::AscEnum;
+use crate::runtime::asc::native_types::store::StoreValueKind;
+use crate::runtime::asc::native_types::string::AscString;
+use crate::runtime::asc::native_types::Uint8Array;
+use crate::warn;
+use wasmer::FunctionEnvMut;
+use wasmer::RuntimeError;
+use wasmer::Value;
+
+pub fn ipfs_cat(
+    mut fenv: FunctionEnvMut<Env>,
+    link_ptr: AscPtr<AscString>,
+) -> Result<AscPtr<Uint8Array>, RuntimeError> {
+    let link: String = asc_get(&fenv, link_ptr, 0)?;
+    let env = fenv.data();
+
+    // Same as graph-node, unavailable files are reported to the mapping as null
+    match env.ipfs.cat(&link) {
+        Ok(data) => Ok(asc_new(&mut fenv, data.as_slice())?),
+        Err(e) => {
+            warn!(IpfsCat, "Failed to fetch ipfs file"; link => link, error => e.to_string());
+            Ok(AscPtr::null())
+        }
+    }
+}
+
+/// Calls `callback(value, userData)` for every JSON value of a newline-delimited file
+pub fn ipfs_map(
+    mut fenv: FunctionEnvMut<Env>,
+    link_ptr: AscPtr<AscString>,
+    callback_ptr: AscPtr<AscString>,
+    user_data_ptr: AscPtr<AscEnum<StoreValueKind>>,
+    flags_ptr: AscPtr<Array<AscPtr<AscString>>>,
+) -> Result<(), RuntimeError> {
+    let link: String = asc_get(&fenv, link_ptr, 0)?;
+    let callback_name: String = asc_get(&fenv, callback_ptr, 0)?;
+    let flags: Vec<String> = asc_get(&fenv, flags_ptr, 0)?;
+
+    if flags != ["json"] {
+        return Err(RuntimeError::new(format!(
+            "ipfs.map: flags must be exactly [\"json\"], got {:?}",
+            flags
+        )));
+    }
+
+    let env = fenv.data();
+    let callback = env
+        .instance
+        .as_ref()
+        .and_then(|instance| instance.exports.get_function(&callback_name).ok())
+        .cloned()
+        .ok_or_else(|| {
+            RuntimeError::new(format!("ipfs.map: no such callback `{callback_name}`"))
+        })?;
+
+    // Unlike `ipfs.cat`, a missing file fails the handler since the callbacks never run
+    let data = env.ipfs.cat(&link).map_err(|e| {
+        if e.is_transient() {
+            NonDeterministicError::from(e).into()
+        } else {
+            RuntimeError::new(format!("ipfs.map: {e}"))
+        }
+    })?;
+
+    for (line_number, line) in data.split(|byte| *byte == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let value: serde_json::Value = serde_json::from_slice(line).map_err(|e| {
+            RuntimeError::new(format!(
+                "ipfs.map: invalid JSON at line {}: {e}",
+                line_number + 1
+            ))
+        })?;
+        let value_ptr = asc_new(&mut fenv, &value)?;
+
+        callback.call(
+            &mut fenv,
+            &[
+                Value::I32(value_ptr.wasm_ptr() as i32),
+                Value::I32(user_data_ptr.wasm_ptr() as i32),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
@@ -3,7 +3,11 @@ use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::bignumber::AscBigInt;
+use crate::runtime::asc::native_types::json::JsonValueKind;
+use crate::runtime::asc::native_types::r#enum::AscEnum;
 use crate::runtime::asc::native_types::string::AscString;
+use crate::runtime::asc::native_types::AscResult;
+use crate::runtime::asc::native_types::Uint8Array;
 use crate::runtime::bignumber::bigint::BigInt;
 use std::str::FromStr;
 use wasmer::FunctionEnvMut;
@@ -18,3 +22,171 @@ pub fn json_to_bigint(
     let asc_bigint = asc_new(&mut fenv, &value)?;
     Ok(asc_bigint)
 }
+
+pub fn json_from_bytes(
+    mut fenv: FunctionEnvMut<Env>,
+    bytes_ptr: AscPtr<Uint8Array>,
+) -> Result<AscPtr<AscEnum<JsonValueKind>>, RuntimeError> {
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
+    let value: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| RuntimeError::new(format!("Failed to parse JSON from byte array: {e}")))?;
+    let asc_value = asc_new(&mut fenv, &value)?;
+    Ok(asc_value)
+}
+
+/// Same as `json.fromBytes`, but a malformed input is returned to the mapping as an error
+pub fn json_try_from_bytes(
+    mut fenv: FunctionEnvMut<Env>,
+    bytes_ptr: AscPtr<Uint8Array>,
+) -> Result<AscPtr<AscResult<AscPtr<AscEnum<JsonValueKind>>, bool>>, RuntimeError> {
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
+    let result = serde_json::from_slice::<serde_json::Value>(&bytes).map_err(|_| true);
+    let asc_result = asc_new(&mut fenv, &result)?;
+    Ok(asc_result)
+}
+
+pub fn json_to_i64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<i64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    i64::from_str(&value)
+        .map_err(|_| RuntimeError::new(format!("JSON `{value}` cannot be parsed as i64")))
+}
+
+pub fn json_to_u64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<u64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    u64::from_str(&value)
+        .map_err(|_| RuntimeError::new(format!("JSON `{value}` cannot be parsed as u64")))
+}
+
+pub fn json_to_f64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<f64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    f64::from_str(&value)
+        .map_err(|_| RuntimeError::new(format!("JSON `{value}` cannot be parsed as f64")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::*;
+    use super::*;
+    use prometheus::Registry;
+    use rstest::rstest;
+    use semver::Version;
+    use serde_json::json;
+
+    fn from_bytes(
+        fenv: &mut FunctionEnvMut<Env>,
+        input: &str,
+    ) -> Result<AscPtr<AscEnum<JsonValueKind>>, RuntimeError> {
+        let bytes: AscPtr<Uint8Array> = asc_new(fenv, input.as_bytes())?;
+        json_from_bytes(fenv.as_mut(), bytes)
+    }
+
+    fn try_from_bytes(
+        fenv: &mut FunctionEnvMut<Env>,
+        input: &str,
+    ) -> Result<serde_json::Value, bool> {
+        let bytes: AscPtr<Uint8Array> = asc_new(fenv, input.as_bytes()).unwrap();
+        let result = json_try_from_bytes(fenv.as_mut(), bytes)
+            .unwrap()
+            .read_ptr(&*fenv)
+            .unwrap();
+        if result.value.is_null() {
+            let error: bool = asc_get(&*fenv, result.error, 0).unwrap();
+            return Err(error);
+        }
+        let value: AscPtr<AscEnum<JsonValueKind>> = asc_get(&*fenv, result.value, 0).unwrap();
+        Ok(asc_get(&*fenv, value, 0).unwrap())
+    }
+
+    fn string(fenv: &mut FunctionEnvMut<Env>, value: &str) -> AscPtr<AscString> {
+        asc_new(fenv, value).unwrap()
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_json_from_bytes(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        let value = json!({
+            "name": "pool",
+            "tokens": ["0xa", "0xb", null],
+            "fee": 3000,
+            "price": -0.25,
+            "stats": { "active": true, "owner": null, "volumes": [[1, 2], []] }
+        });
+        let ptr = from_bytes(&mut fenv, &value.to_string()).unwrap();
+        let parsed: serde_json::Value = asc_get(&fenv, ptr, 0).unwrap();
+        assert_eq!(parsed, value);
+
+        // Numbers reach the mapping as text, so big ones keep every digit for `toBigInt`
+        let big = "-123456789012345678901234567890";
+        let ptr = from_bytes(&mut fenv, big).unwrap();
+        let asc_enum = ptr.read_ptr(&fenv).unwrap();
+        assert!(matches!(asc_enum.kind, JsonValueKind::Number));
+        let bigint = json_to_bigint(fenv.as_mut(), AscPtr::from(asc_enum.payload)).unwrap();
+        let bigint: BigInt = asc_get(&fenv, bigint, 0).unwrap();
+        assert_eq!(bigint.to_string(), big);
+
+        // Malformed input aborts the handler
+        assert!(from_bytes(&mut fenv, r#"{"fee": "#).is_err());
+        assert!(from_bytes(&mut fenv, "").is_err());
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_json_try_from_bytes(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        let value = json!([{ "id": "1", "amounts": [1.5, 18446744073709551616_u128] }, false]);
+        let parsed = try_from_bytes(&mut fenv, &value.to_string());
+        assert_eq!(parsed, Ok(value));
+        assert_eq!(
+            try_from_bytes(&mut fenv, "null"),
+            Ok(serde_json::Value::Null)
+        );
+
+        // Malformed input is handed back to the mapping instead
+        assert_eq!(try_from_bytes(&mut fenv, r#"{"fee": "#), Err(true));
+        assert_eq!(try_from_bytes(&mut fenv, "[1,]"), Err(true));
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_json_to_numbers(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        let ptr = string(&mut fenv, "-9223372036854775808");
+        assert_eq!(json_to_i64(fenv.as_mut(), ptr).unwrap(), i64::MIN);
+        let ptr = string(&mut fenv, "18446744073709551615");
+        assert_eq!(json_to_u64(fenv.as_mut(), ptr).unwrap(), u64::MAX);
+        let ptr = string(&mut fenv, "-1.5e3");
+        assert_eq!(json_to_f64(fenv.as_mut(), ptr).unwrap(), -1500.0);
+
+        // Out of range or of the wrong kind
+        let ptr = string(&mut fenv, "9223372036854775808");
+        assert!(json_to_i64(fenv.as_mut(), ptr).is_err());
+        let ptr = string(&mut fenv, "-1");
+        assert!(json_to_u64(fenv.as_mut(), ptr).is_err());
+        let ptr = string(&mut fenv, "1.5");
+        assert!(json_to_i64(fenv.as_mut(), ptr).is_err());
+        let ptr = string(&mut fenv, "abc");
+        assert!(json_to_f64(fenv.as_mut(), ptr).is_err());
+    }
+}
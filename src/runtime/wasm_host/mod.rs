@@ -3,7 +3,10 @@ mod bigdecimal;
 mod bigint;
 mod chain;
 mod datasource;
+mod ens;
+mod gas;
 mod global;
+mod ipfs;
 mod json;
 mod macros;
 mod store;
@@ -14,25 +17,39 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::common::DatasourceBundle;
+use crate::common::RawEntity;
 use crate::components::ManifestAgent;
+use crate::config::WasmHostConfig;
 use crate::database::DatabaseAgent;
 use crate::errors::WasmHostError;
+use crate::ipfs_client::IpfsAgent;
 use crate::rpc_client::RpcAgent;
+use crate::warn;
 use asc::ArenaStartPtr;
 pub use asc::AscHost;
 use semver::Version;
+use std::collections::BTreeMap;
+use std::time::Instant;
 use wasmer::imports;
+use wasmer::CompilerConfig;
+use wasmer::Cranelift;
+use wasmer::EngineBuilder;
+use wasmer::ExternType;
 use wasmer::Function;
 use wasmer::FunctionEnv;
+use wasmer::Imports;
 use wasmer::Instance;
 use wasmer::Memory;
 use wasmer::Module;
+use wasmer::RuntimeError;
 use wasmer::Store;
 use wasmer::TypedFunction;
+use wasmer_middlewares::Metering;
 
 #[derive(Clone)]
 pub struct Env {
     pub memory: Option<Memory>,
+    pub instance: Option<Instance>,
     pub memory_allocate: Option<TypedFunction<i32, i32>>,
     pub api_version: Version,
     pub id_of_type: Option<TypedFunction<u32, u32>>,
@@ -40,9 +57,27 @@ pub struct Env {
     pub host_name: String,
     pub network: String,
     pub address: Option<String>,
+    pub context: Option<RawEntity>,
     pub db: DatabaseAgent,
     pub rpc: RpcAgent,
+    pub ipfs: IpfsAgent,
     pub manifest: ManifestAgent,
+    /// Host calls made after this instant fail the running handler,
+    /// see `gas::metered_points` for handlers that do not call the host
+    pub deadline: Option<Instant>,
+    pub timed_out: bool,
+    pub gas_per_ms: u64,
+    /// Gas left to the running handler when the metering points were last set
+    pub gas_left: u64,
+    /// Metering points last set, below `gas_left` when capped by the deadline
+    pub metered: u64,
+}
+
+impl Env {
+    pub fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() > deadline)
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -51,18 +86,26 @@ fn create_wasm_host(
     wasm_bytes: Vec<u8>,
     host_name: String,
     rpc: RpcAgent,
+    ipfs: IpfsAgent,
     manifest: ManifestAgent,
     address: Option<String>,
+    context: Option<RawEntity>,
     network: String,
     db: DatabaseAgent,
+    cfg: &WasmHostConfig,
 ) -> Result<AscHost, WasmHostError> {
-    let mut store = Store::default();
+    // Handlers are metered so that a runaway mapping fails deterministically
+    let metering = Arc::new(Metering::new(cfg.gas_limit, gas::operator_cost));
+    let mut compiler = Cranelift::default();
+    compiler.push_middleware(metering);
+    let mut store = Store::new(EngineBuilder::new(compiler));
     let module = Module::new(&store, wasm_bytes)?;
 
     let env = FunctionEnv::new(
         &mut store,
         Env {
             memory: None,
+            instance: None,
             memory_allocate: None,
             id_of_type: None,
             api_version: api_version.clone(),
@@ -70,14 +113,20 @@ fn create_wasm_host(
             host_name,
             db,
             rpc,
+            ipfs,
             manifest,
             address,
+            context,
             network,
+            deadline: None,
+            timed_out: false,
+            gas_per_ms: cfg.gas_per_ms,
+            gas_left: cfg.gas_limit,
+            metered: cfg.gas_limit,
         },
     );
 
-    // Running cargo-run will immediately tell which functions are missing
-    let import_object = imports! {
+    let mut import_object = imports! {
         "env" => {
             "abort" => Function::new_typed_with_env(&mut store, &env, global::abort)
         },
@@ -113,6 +162,11 @@ fn create_wasm_host(
         },
         "json" => {
             "json.toBigInt" =>Function::new_typed_with_env(&mut store, &env, json::json_to_bigint),
+            "json.fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_from_bytes),
+            "json.try_fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_try_from_bytes),
+            "json.toI64" => Function::new_typed_with_env(&mut store, &env, json::json_to_i64),
+            "json.toU64" => Function::new_typed_with_env(&mut store, &env, json::json_to_u64),
+            "json.toF64" => Function::new_typed_with_env(&mut store, &env, json::json_to_f64),
         },
         "ethereum" => {
             //Ethereum fn
@@ -144,6 +198,23 @@ fn create_wasm_host(
             "typeConversion.bytesToBase58" => Function::new_typed_with_env(&mut store, &env, types_conversion::bytes_to_base58),
             //Log
             "log.log" => Function::new_typed_with_env(&mut store, &env, wasm_log::log_log),
+            // Json
+            "json.toBigInt" => Function::new_typed_with_env(&mut store, &env, json::json_to_bigint),
+            "json.fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_from_bytes),
+            "json.try_fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_try_from_bytes),
+            "json.toI64" => Function::new_typed_with_env(&mut store, &env, json::json_to_i64),
+            "json.toU64" => Function::new_typed_with_env(&mut store, &env, json::json_to_u64),
+            "json.toF64" => Function::new_typed_with_env(&mut store, &env, json::json_to_f64),
+            // Ethereum
+            "ethereum.encode" => Function::new_typed_with_env(&mut store, &env, chain::ethereum::ethereum_encode),
+            "ethereum.decode" => Function::new_typed_with_env(&mut store, &env, chain::ethereum::ethereum_decode),
+            "ethereum.call" => Function::new_typed_with_env(&mut store, &env, chain::ethereum::ethereum_call),
+            "crypto.keccak256" => Function::new_typed_with_env(&mut store, &env, chain::ethereum::crypto_keccak_256),
+            // Ens
+            "ens.nameByHash" => Function::new_typed_with_env(&mut store, &env, ens::ens_name_by_hash),
+            // Ipfs
+            "ipfs.cat" => Function::new_typed_with_env(&mut store, &env, ipfs::ipfs_cat),
+            "ipfs.map" => Function::new_typed_with_env(&mut store, &env, ipfs::ipfs_map),
             // Datasource
             "dataSource.create" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_create),
             "dataSource.createWithContext" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_create_context),
@@ -175,11 +246,11 @@ fn create_wasm_host(
         }
     };
 
-    // Running cargo-run will immediately tell which functions are missing
-    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    resolve_missing_imports(&mut store, &env, &module, &mut import_object, cfg)?;
+    let instance = Instance::new(&mut store, &module, &import_object)?;
 
     // Bind guest memory ref & __alloc to env
-    let mut env_mut = env.into_mut(&mut store);
+    let mut env_mut = env.clone().into_mut(&mut store);
     let (data_mut, mut store_mut) = env_mut.data_and_store_mut();
 
     data_mut.memory = Some(
@@ -191,6 +262,9 @@ fn create_wasm_host(
             .clone(),
     );
     assert!(data_mut.memory.is_some(), "Global Memory set");
+    // Needed by host functions calling back into the mapping, eg: `ipfs.map`,
+    // and to charge host calls against the remaining gas
+    data_mut.instance = Some(instance.clone());
 
     data_mut.memory_allocate = match api_version.clone() {
         version if version <= Version::new(0, 0, 4) => instance
@@ -231,30 +305,109 @@ fn create_wasm_host(
 
     Ok(AscHost {
         store,
+        env,
         instance,
         api_version,
         memory,
         memory_allocate,
         id_of_type,
         arena_start_ptr,
+        gas_limit: cfg.gas_limit,
     })
 }
 
-impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)> for AscHost {
+/// Report every import of the mapping that the runtime does not provide, grouped by namespace.
+/// With `stub_missing_imports`, missing functions are replaced by stubs that only fail if called
+fn resolve_missing_imports(
+    store: &mut Store,
+    env: &FunctionEnv<Env>,
+    module: &Module,
+    import_object: &mut Imports,
+    cfg: &WasmHostConfig,
+) -> Result<(), WasmHostError> {
+    let missing_imports = module
+        .imports()
+        .filter(|import| {
+            import_object
+                .get_export(import.module(), import.name())
+                .is_none()
+        })
+        .collect::<Vec<_>>();
+
+    if missing_imports.is_empty() {
+        return Ok(());
+    }
+
+    let all_functions = missing_imports
+        .iter()
+        .all(|import| matches!(import.ty(), ExternType::Function(_)));
+
+    if !cfg.stub_missing_imports || !all_functions {
+        let mut by_namespace = BTreeMap::<&str, Vec<&str>>::new();
+        for import in missing_imports.iter() {
+            by_namespace
+                .entry(import.module())
+                .or_default()
+                .push(import.name());
+        }
+        let description = by_namespace
+            .into_iter()
+            .map(|(namespace, names)| format!("{namespace}: [{}]", names.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(WasmHostError::MissingImports(description));
+    }
+
+    for import in missing_imports {
+        if let ExternType::Function(ty) = import.ty() {
+            let import_name = format!("{}.{}", import.module(), import.name());
+            warn!(WasmHost, "Stubbing unsupported host function"; function => import_name);
+            let stub = Function::new_with_env(store, env, ty.clone(), move |_, _| {
+                Err(RuntimeError::new(format!(
+                    "Host function `{import_name}` is not supported by the runtime"
+                )))
+            });
+            import_object.define(import.module(), import.name(), stub);
+        }
+    }
+
+    Ok(())
+}
+
+impl
+    TryFrom<(
+        DatasourceBundle,
+        DatabaseAgent,
+        RpcAgent,
+        IpfsAgent,
+        ManifestAgent,
+        WasmHostConfig,
+    )> for AscHost
+{
     type Error = WasmHostError;
 
     fn try_from(
-        (ds, db, rpc, manifest): (DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent),
+        (ds, db, rpc, ipfs, manifest, cfg): (
+            DatasourceBundle,
+            DatabaseAgent,
+            RpcAgent,
+            IpfsAgent,
+            ManifestAgent,
+            WasmHostConfig,
+        ),
     ) -> Result<Self, Self::Error> {
         create_wasm_host(
             ds.api_version(),
             ds.wasm(),
             ds.name(),
             rpc,
+            ipfs,
             manifest,
             ds.address(),
+            ds.context(),
             ds.network(),
             db,
+            &cfg,
         )
     }
 }
@@ -262,8 +415,10 @@ impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)> for Asc
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use df_logger::loggers;
     use prometheus::Registry;
     use std::path::PathBuf;
+    use wasmer::Value;
 
     pub fn mock_wasm_host(
         api_version: Version,
@@ -286,14 +441,180 @@ pub mod test {
             wasm_bytes,
             "test".to_string(),
             rpc,
+            IpfsAgent::new_mock(),
             ManifestAgent::default(),
             None,
+            None,
             "Test".to_string(),
             db,
+            &WasmHostConfig::default(),
+        )
+        .unwrap()
+    }
+
+    /// Host over a bare module that only bump-allocates, to call host functions directly
+    /// against the heap layout of `api_version` without a compiled mapping
+    pub fn mock_heap_host(api_version: Version, registry: &Registry) -> AscHost {
+        let wat = r#"(module
+            (memory (export "memory") 4)
+            (global $next (mut i32) (i32.const 1024))
+            (func $allocate (param $size i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr
+                    (i32.and (i32.add (global.get $next) (i32.const 15)) (i32.const -16)))
+                ;; Room for the header offset the host adds on 0.0.5
+                (global.set $next
+                    (i32.add (local.get $ptr) (i32.add (local.get $size) (i32.const 16))))
+                (local.get $ptr))
+            (func (export "id_of_type") (param i32) (result i32) (local.get 0))
+            (export "memory.allocate" (func $allocate))
+            (export "allocate" (func $allocate)))"#;
+
+        create_wasm_host(
+            api_version,
+            wat.as_bytes().to_vec(),
+            "test".to_string(),
+            RpcAgent::new_mock(registry),
+            IpfsAgent::new_mock(),
+            ManifestAgent::default(),
+            None,
+            None,
+            "Test".to_string(),
+            DatabaseAgent::empty(registry),
+            &WasmHostConfig::default(),
         )
         .unwrap()
     }
 
+    #[test]
+    fn test_missing_imports() {
+        loggers::init_logger();
+        let wat = r#"(module
+            (import "env" "abort" (func (param i32 i32 i32 i32)))
+            (import "index" "box.profile" (func (param i32) (result i32)))
+            (import "index" "arweave.transactionData" (func (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "callArweave") (result i32) (call 2 (i32.const 0))))"#;
+        let registry = Registry::new();
+        let create_host = |cfg: &WasmHostConfig| {
+            create_wasm_host(
+                Version::new(0, 0, 5),
+                wat.as_bytes().to_vec(),
+                "test".to_string(),
+                RpcAgent::new_mock(&registry),
+                IpfsAgent::new_mock(),
+                ManifestAgent::default(),
+                None,
+                None,
+                "Test".to_string(),
+                DatabaseAgent::empty(&registry),
+                cfg,
+            )
+        };
+
+        let Err(WasmHostError::MissingImports(missing)) = create_host(&WasmHostConfig::default())
+        else {
+            panic!("Missing imports should be reported");
+        };
+        assert_eq!(missing, "index: [box.profile, arweave.transactionData]");
+
+        let cfg = WasmHostConfig {
+            stub_missing_imports: true,
+            ..Default::default()
+        };
+        let Ok(mut host) = create_host(&cfg) else {
+            panic!("Missing imports should be stubbed");
+        };
+        let func = host
+            .instance
+            .exports
+            .get_function("callArweave")
+            .unwrap()
+            .clone();
+        let error = func.call(&mut host.store, &[]).unwrap_err();
+        assert!(error.message().contains("index.arweave.transactionData"));
+    }
+
+    #[test]
+    fn test_gas_limit() {
+        loggers::init_logger();
+        let wat = r#"(module
+            (memory (export "memory") 1)
+            (func (export "spin") (loop (br 0)))
+            (func (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1))))"#;
+        let registry = Registry::new();
+        let cfg = WasmHostConfig {
+            gas_limit: 10_000,
+            ..Default::default()
+        };
+        let mut host = create_wasm_host(
+            Version::new(0, 0, 5),
+            wat.as_bytes().to_vec(),
+            "test".to_string(),
+            RpcAgent::new_mock(&registry),
+            IpfsAgent::new_mock(),
+            ManifestAgent::default(),
+            None,
+            None,
+            "Test".to_string(),
+            DatabaseAgent::empty(&registry),
+            &cfg,
+        )
+        .unwrap();
+
+        let spin = host.instance.exports.get_function("spin").unwrap().clone();
+        assert!(spin.call(&mut host.store, &[]).is_err());
+        assert!(host.gas_exhausted());
+
+        // Gas is refilled for the next handler call
+        host.reset_gas();
+        assert!(!host.gas_exhausted());
+        let add = host.instance.exports.get_function("add").unwrap().clone();
+        let result = add
+            .call(&mut host.store, &[Value::I32(1), Value::I32(2)])
+            .unwrap();
+        assert_eq!(result[0].unwrap_i32(), 3);
+    }
+
+    #[test]
+    fn test_deadline_without_host_calls() {
+        loggers::init_logger();
+        let wat = r#"(module
+            (memory (export "memory") 1)
+            (func (export "spin") (loop (br 0))))"#;
+        let registry = Registry::new();
+        let mut host = create_wasm_host(
+            Version::new(0, 0, 5),
+            wat.as_bytes().to_vec(),
+            "test".to_string(),
+            RpcAgent::new_mock(&registry),
+            IpfsAgent::new_mock(),
+            ManifestAgent::default(),
+            None,
+            None,
+            "Test".to_string(),
+            DatabaseAgent::empty(&registry),
+            &WasmHostConfig::default(),
+        )
+        .unwrap();
+
+        // The default gas limit would keep the loop running for many seconds
+        let started = Instant::now();
+        host.set_deadline(Some(started + std::time::Duration::from_millis(50)));
+        host.reset_gas();
+        let spin = host.instance.exports.get_function("spin").unwrap().clone();
+        assert!(spin.call(&mut host.store, &[]).is_err());
+        assert!(host.timed_out());
+        assert!(!host.gas_exhausted());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        // Without a deadline the next handler gets its whole gas back
+        host.set_deadline(None);
+        host.reset_gas();
+        assert!(!host.timed_out());
+    }
+
     pub fn get_subgraph_testing_resource(version: &str, host_name: &str) -> (Version, String) {
         let version = Version::parse(version).expect("Bad api-version");
         let mut project_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
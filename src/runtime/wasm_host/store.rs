@@ -1,3 +1,5 @@
+use super::gas;
+use super::gas::charge_gas;
 use super::Env;
 use crate::common::RawEntity;
 use crate::common::StoreOperationMessage;
@@ -14,11 +16,12 @@ use wasmer::FunctionEnvMut;
 use wasmer::RuntimeError;
 
 pub fn store_set(
-    fenv: FunctionEnvMut<Env>,
+    mut fenv: FunctionEnvMut<Env>,
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
     data_ptr: AscPtr<AscEntity>,
 ) -> Result<(), RuntimeError> {
+    charge_gas(&mut fenv, gas::STORE_SET)?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
@@ -32,9 +35,7 @@ pub fn store_set(
     }
 
     let request = StoreOperationMessage::Update((entity_type, entity_id, data));
-    let _result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    let _result = db.wasm_send_store_request(request)?;
 
     Ok(())
 }
@@ -44,14 +45,13 @@ pub fn store_get(
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    charge_gas(&mut fenv, gas::STORE_GET)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let env = fenv.data();
     let db = env.db.clone();
     let request = StoreOperationMessage::Load((entity_type, entity_id));
-    let result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    let result = db.wasm_send_store_request(request)?;
 
     match result {
         StoreRequestResult::Load(data) => {
@@ -70,19 +70,18 @@ pub fn store_get(
 }
 
 pub fn store_remove(
-    fenv: FunctionEnvMut<Env>,
+    mut fenv: FunctionEnvMut<Env>,
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<(), RuntimeError> {
+    charge_gas(&mut fenv, gas::STORE_REMOVE)?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
 
     let request = StoreOperationMessage::Delete((entity_type, entity_id));
-    let _result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    let _result = db.wasm_send_store_request(request)?;
 
     Ok(())
 }
@@ -92,13 +91,12 @@ pub fn store_get_in_block(
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    charge_gas(&mut fenv, gas::STORE_GET)?;
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
     let db = fenv.data().db.clone();
     let request = StoreOperationMessage::LoadInBlock((entity_type, entity_id));
-    let result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    let result = db.wasm_send_store_request(request)?;
 
     match result {
         StoreRequestResult::LoadInBlock(raw_entity) => {
@@ -110,7 +108,10 @@ pub fn store_get_in_block(
                 Ok(AscPtr::null())
             }
         }
-        _ => unimplemented!(),
+        other => Err(RuntimeError::new(format!(
+            "Load entity in block failed, recevied response: {:?}",
+            other
+        ))),
     }
 }
 
@@ -120,6 +121,7 @@ pub fn store_load_related(
     entity_id_ptr: AscPtr<AscString>,
     field_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<Array<AscPtr<AscEntity>>>, RuntimeError> {
+    charge_gas(&mut fenv, gas::STORE_GET)?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
@@ -127,9 +129,7 @@ pub fn store_load_related(
     let field_name: String = asc_get(&fenv, field_ptr, 0)?;
 
     let request = StoreOperationMessage::LoadRelated((entity_type, entity_id, field_name));
-    let result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    let result = db.wasm_send_store_request(request)?;
     match result {
         StoreRequestResult::LoadRelated(entities) => {
             let entities = remove_private_field(entities);
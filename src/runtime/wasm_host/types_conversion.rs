@@ -43,7 +43,7 @@ pub fn bytes_to_string(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
-    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
     let string = convert_bytes_to_string(bytes);
     let asc_string = asc_new(&mut fenv, &string)?;
     Ok(asc_string)
@@ -53,7 +53,7 @@ pub fn bytes_to_hex(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
-    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
     let asc_hex = asc_new(&mut fenv, &format!("0x{}", hex::encode(bytes)))?;
     Ok(asc_hex)
 }
@@ -62,7 +62,7 @@ pub fn big_int_to_string(
     mut fenv: FunctionEnvMut<Env>,
     big_int_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
-    let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0).unwrap();
+    let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0)?;
     let big_int_string = asc_new(&mut fenv, &big_int.to_string())?;
     Ok(big_int_string)
 }
@@ -71,7 +71,7 @@ pub fn big_int_to_hex(
     mut fenv: FunctionEnvMut<Env>,
     big_int_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
-    let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0).unwrap();
+    let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0)?;
     if big_int == 0.into() {
         let result = asc_new(&mut fenv, "0x0")?;
         Ok(result)
@@ -89,7 +89,7 @@ pub fn string_to_h160(
     mut fenv: FunctionEnvMut<Env>,
     string_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscH160>, RuntimeError> {
-    let string: String = asc_get(&fenv, string_ptr, 0).unwrap();
+    let string: String = asc_get(&fenv, string_ptr, 0)?;
     let h160 = convert_string_to_h160(&string)?;
     let result = asc_new(&mut fenv, &h160)?;
     Ok(result)
@@ -100,7 +100,7 @@ pub fn bytes_to_base58(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
-    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
     let result = asc_new(&mut fenv, &bs58::encode(bytes).into_string())?;
     Ok(result)
 }
@@ -110,6 +110,59 @@ mod tests {
     use super::super::test::*;
     use super::*;
     use crate::host_fn_test;
+    use prometheus::Registry;
+    use rstest::rstest;
+    use semver::Version;
+
+    fn read_string(fenv: &FunctionEnvMut<Env>, ptr: AscPtr<AscString>) -> String {
+        asc_get(fenv, ptr, 0).unwrap()
+    }
+
+    #[rstest]
+    #[case("0.0.4")]
+    #[case("0.0.5")]
+    fn test_type_conversion_edge_cases(#[case] version: &str) {
+        let registry = Registry::new();
+        let mut host = mock_heap_host(Version::parse(version).unwrap(), &registry);
+        let mut fenv = host.env.clone().into_mut(&mut host.store);
+
+        // Fixed length buffers are padded with null characters
+        let bytes: AscPtr<Uint8Array> = asc_new(&mut fenv, b"abc\0\0".as_slice()).unwrap();
+        let ptr = bytes_to_string(fenv.as_mut(), bytes).unwrap();
+        assert_eq!(read_string(&fenv, ptr), "abc");
+
+        let bytes: AscPtr<Uint8Array> = asc_new(&mut fenv, [0u8; 0].as_slice()).unwrap();
+        let ptr = bytes_to_hex(fenv.as_mut(), bytes).unwrap();
+        assert_eq!(read_string(&fenv, ptr), "0x");
+
+        // Hex of a big int is its magnitude, without leading zeros
+        for (n, hex) in [
+            ("0", "0x0"),
+            ("1", "0x1"),
+            ("4096", "0x1000"),
+            ("-255", "0xff"),
+        ] {
+            let big_int = asc_new(&mut fenv, &BigInt::from_str(n).unwrap()).unwrap();
+            let ptr = big_int_to_hex(fenv.as_mut(), big_int).unwrap();
+            assert_eq!(read_string(&fenv, ptr), hex);
+            let ptr = big_int_to_string(fenv.as_mut(), big_int).unwrap();
+            assert_eq!(read_string(&fenv, ptr), n);
+        }
+
+        let address = "0x5a98fcbea516cf06857215779fd812ca3bef1b32";
+        for input in [address, address.trim_start_matches("0x")] {
+            let string = asc_new(&mut fenv, input).unwrap();
+            let ptr = string_to_h160(fenv.as_mut(), string).unwrap();
+            let h160: Vec<u8> = asc_get(&fenv, ptr, 0).unwrap();
+            assert_eq!(format!("0x{}", hex::encode(h160)), address);
+        }
+        let string = asc_new(&mut fenv, "0x5a98").unwrap();
+        assert!(string_to_h160(fenv.as_mut(), string).is_err());
+
+        let bytes: AscPtr<Uint8Array> = asc_new(&mut fenv, [0u8, 0, 1].as_slice()).unwrap();
+        let ptr = bytes_to_base58(fenv.as_mut(), bytes).unwrap();
+        assert_eq!(read_string(&fenv, ptr), "112");
+    }
 
     host_fn_test!("TestTypes", test_bytes_to_hex, host, ptr {
         let asc_ptr = AscPtr::<AscString>::new(ptr);